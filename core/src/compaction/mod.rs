@@ -2,9 +2,11 @@ use bergloom_codegen::compactor::RewriteFilesStat;
 use iceberg::spec::DataFile;
 use iceberg::{Catalog, TableIdent};
 
-use crate::Result;
-use crate::executor::{InputFileScanTasks, RewriteFilesRequest, RewriteFilesResponse};
-use crate::{CompactionConfig, CompactionExecutor};
+use crate::executor::{
+    DataFileMetadata, ExecutorRegistry, InputFileScanTasks, RewriteFilesRequest,
+    RewriteFilesResponse,
+};
+use crate::{CompactionConfig, CompactionError, CompactionExecutor, ExecutorConfig, Result};
 use futures_async_stream::for_await;
 use iceberg::scan::FileScanTask;
 use iceberg::table::Table;
@@ -13,8 +15,6 @@ use iceberg::writer::file_writer::location_generator::DefaultLocationGenerator;
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::executor::DataFusionExecutor;
-
 pub enum CompactionType {
     Full(TableIdent),
 }
@@ -25,13 +25,29 @@ pub struct Compaction {
 }
 
 impl Compaction {
-    pub fn new(config: Arc<CompactionConfig>, catalog: Arc<dyn Catalog>) -> Self {
-        let executor: Box<dyn CompactionExecutor> = Box::new(DataFusionExecutor::default());
-        Self {
+    /// Creates a compaction running the default executor.
+    pub fn new(config: Arc<CompactionConfig>, catalog: Arc<dyn Catalog>) -> Result<Self> {
+        Self::with_registry(
+            config,
+            catalog,
+            &ExecutorRegistry::default(),
+            &ExecutorConfig::default(),
+        )
+    }
+
+    /// Creates a compaction running the executor named by `executor_config` in `registry`.
+    pub fn with_registry(
+        config: Arc<CompactionConfig>,
+        catalog: Arc<dyn Catalog>,
+        registry: &ExecutorRegistry,
+        executor_config: &ExecutorConfig,
+    ) -> Result<Self> {
+        let executor = registry.build(executor_config)?;
+        Ok(Self {
             config,
             executor,
             catalog,
-        }
+        })
     }
 
     pub async fn compact(&self, compaction_type: CompactionType) -> Result<RewriteFilesStat> {
@@ -43,39 +59,68 @@ impl Compaction {
     async fn full_compact(&self, table_ident: TableIdent) -> Result<RewriteFilesStat> {
         let table = self.catalog.load_table(&table_ident).await?;
         let (data_files, delete_files) = get_old_files_from_table(table.clone()).await?;
-        let input_file_scan_tasks = get_tasks_from_table(table.clone()).await?;
+        // the snapshot producer writes the added files into a manifest of the current partition
+        // spec, so files of an older spec can only be compacted by rewriting them into it
+        if !self.config.rewrite_to_current_spec.unwrap_or(false) {
+            let default_spec_id = table.metadata().default_partition_spec().spec_id();
+            if let Some((data_file, partition_spec_id)) = data_files
+                .iter()
+                .find(|(_, partition_spec_id)| *partition_spec_id != default_spec_id)
+            {
+                return Err(CompactionError::InvalidInput(format!(
+                    "data file {} was written with partition spec {} instead of {}, set rewrite_to_current_spec to compact it",
+                    data_file.file_path(),
+                    partition_spec_id,
+                    default_spec_id
+                )));
+            }
+        }
 
-        let file_io = table.file_io().clone();
-        let schema = table.metadata().current_schema();
         let default_location_generator =
             DefaultLocationGenerator::new(table.metadata().clone()).unwrap();
+        let input_file_scan_tasks =
+            get_tasks_from_table(table.clone(), &data_files, &delete_files).await?;
         let rewrite_files_request = RewriteFilesRequest {
-            file_io,
-            schema: schema.clone(),
+            file_io: table.file_io().clone(),
+            schema: table.metadata().current_schema().clone(),
             input_file_scan_tasks,
             config: self.config.clone(),
-            dir_path: default_location_generator.dir_path,
+            dir_path: default_location_generator.dir_path.clone(),
             partition_spec: table.metadata().default_partition_spec().clone(),
+            table_properties: table.metadata().properties().clone(),
+            sort_order: Some(table.metadata().default_sort_order().clone())
+                .filter(|sort_order| !sort_order.is_unsorted()),
         };
         let RewriteFilesResponse {
             data_files: output_data_files,
+            data_sequence_number,
             stat,
-        } = DataFusionExecutor::default()
-            .rewrite_files(rewrite_files_request)
-            .await?;
+            ..
+        } = self.executor.rewrite_files(rewrite_files_request).await?;
+
         let txn = Transaction::new(&table);
         let mut rewrite_action = txn.rewrite_files(None, vec![])?;
-        rewrite_action.add_data_files(output_data_files.clone())?;
-        rewrite_action.delete_files(data_files)?;
-        rewrite_action.delete_files(delete_files)?;
+        // the compacted rows keep the sequence number of their inputs, so that equality deletes
+        // committed since the files were planned still apply to them
+        if let Some(data_sequence_number) = data_sequence_number {
+            rewrite_action.set_new_data_file_sequence_number(data_sequence_number)?;
+        }
+        rewrite_action.add_data_files(output_data_files)?;
+        rewrite_action.delete_files(
+            data_files
+                .into_iter()
+                .map(|(data_file, _)| data_file)
+                .collect::<Vec<_>>(),
+        )?;
+        rewrite_action.delete_files(
+            delete_files
+                .into_iter()
+                .map(|(delete_file, _)| delete_file)
+                .collect::<Vec<_>>(),
+        )?;
         let txn = rewrite_action.apply().await?;
         txn.commit(self.catalog.as_ref()).await?;
-        Ok(RewriteFilesStat {
-            rewritten_files_count: stat.rewritten_files_count,
-            added_files_count: stat.added_files_count,
-            rewritten_bytes: stat.rewritten_bytes,
-            failed_data_files_count: stat.failed_data_files_count,
-        })
+        Ok(stat)
     }
 
     pub async fn expire_snapshot(&self, table_ident: TableIdent) -> Result<()> {
@@ -87,7 +132,11 @@ impl Compaction {
     }
 }
 
-async fn get_old_files_from_table(table: Table) -> Result<(Vec<DataFile>, Vec<DataFile>)> {
+/// Lists the data files and the delete files of the current snapshot, with the id of their
+/// partition spec.
+async fn get_old_files_from_table(
+    table: Table,
+) -> Result<(Vec<(DataFile, i32)>, Vec<(DataFile, i32)>)> {
     let manifest_list = table
         .metadata()
         .current_snapshot()
@@ -104,13 +153,13 @@ async fn get_old_files_from_table(table: Table) -> Result<(Vec<DataFile>, Vec<Da
         for i in entry {
             match i.content_type() {
                 iceberg::spec::DataContentType::Data => {
-                    data_file.push(i.data_file().clone());
+                    data_file.push((i.data_file().clone(), manifest_file.partition_spec_id));
                 }
                 iceberg::spec::DataContentType::EqualityDeletes => {
-                    delete_file.push(i.data_file().clone());
+                    delete_file.push((i.data_file().clone(), manifest_file.partition_spec_id));
                 }
                 iceberg::spec::DataContentType::PositionDeletes => {
-                    delete_file.push(i.data_file().clone());
+                    delete_file.push((i.data_file().clone(), manifest_file.partition_spec_id));
                 }
             }
         }
@@ -118,7 +167,12 @@ async fn get_old_files_from_table(table: Table) -> Result<(Vec<DataFile>, Vec<Da
     Ok((data_file, delete_file))
 }
 
-async fn get_tasks_from_table(table: Table) -> Result<InputFileScanTasks> {
+/// Plans the scan tasks of `data_files` and of the delete files applying to them.
+async fn get_tasks_from_table(
+    table: Table,
+    data_files: &[(DataFile, i32)],
+    delete_files: &[(DataFile, i32)],
+) -> Result<InputFileScanTasks> {
     let snapshot_id = table.metadata().current_snapshot_id().unwrap();
 
     let scan = table
@@ -127,6 +181,19 @@ async fn get_tasks_from_table(table: Table) -> Result<InputFileScanTasks> {
         .with_delete_file_processing_enabled(true)
         .build()?;
     let file_scan_stream = scan.plan_files().await?;
+    let file_metadata = |files: &[(DataFile, i32)]| {
+        files
+            .iter()
+            .map(|(data_file, partition_spec_id)| {
+                (
+                    data_file.file_path().to_owned(),
+                    DataFileMetadata::from_data_file(data_file, *partition_spec_id),
+                )
+            })
+            .collect::<HashMap<_, _>>()
+    };
+    let data_file_metadata = file_metadata(data_files);
+    let mut delete_file_metadata = file_metadata(delete_files);
 
     let mut position_delete_files = HashMap::new();
     let mut data_files = vec![];
@@ -137,6 +204,9 @@ async fn get_tasks_from_table(table: Table) -> Result<InputFileScanTasks> {
         let task: FileScanTask = task?;
         match task.data_file_content {
             iceberg::spec::DataContentType::Data => {
+                if !data_file_metadata.contains_key(&task.data_file_path) {
+                    continue;
+                }
                 for delete_task in task.deletes.iter() {
                     match &delete_task.data_file_content {
                         iceberg::spec::DataContentType::PositionDeletes => {
@@ -163,16 +233,24 @@ async fn get_tasks_from_table(table: Table) -> Result<InputFileScanTasks> {
             }
         }
     }
+    delete_file_metadata.retain(|path, _| {
+        position_delete_files.contains_key(path) || equality_delete_files.contains_key(path)
+    });
     Ok(InputFileScanTasks {
         data_files,
         position_delete_files: position_delete_files.into_values().collect(),
         equality_delete_files: equality_delete_files.into_values().collect(),
+        // the scan plans the deletes of v2 tables only
+        deletion_vectors: vec![],
+        delete_file_metadata,
+        data_file_metadata,
     })
 }
 
 #[cfg(test)]
 mod tests {
     use iceberg::Catalog;
+    use iceberg::spec::{DataContentType, ManifestStatus};
     use iceberg::{TableIdent, io::FileIOBuilder};
     use iceberg_catalog_sql::{SqlBindStyle, SqlCatalog, SqlCatalogConfig};
     use std::sync::Arc;
@@ -209,11 +287,33 @@ mod tests {
             batch_parallelism: Some(4),
             target_partitions: Some(4),
             data_file_prefix: None,
+            file_prefetch_depth: None,
+            target_split_size: None,
+            rewrite_to_current_spec: None,
         });
-        let compaction = Compaction::new(compaction_config, catalog);
+        let compaction = Compaction::new(compaction_config, catalog.clone()).unwrap();
         compaction
-            .compact(crate::compaction::CompactionType::Full(table_id))
+            .compact(crate::compaction::CompactionType::Full(table_id.clone()))
             .await
             .unwrap();
+
+        // the compacted files keep the sequence number of the files they rewrite, older than the
+        // snapshot adding them
+        let table = catalog.load_table(&table_id).await.unwrap();
+        let snapshot = table.metadata().current_snapshot().unwrap();
+        let manifest_list = snapshot
+            .load_manifest_list(table.file_io(), table.metadata())
+            .await
+            .unwrap();
+        for manifest_file in manifest_list.entries() {
+            let manifest = manifest_file.load_manifest(table.file_io()).await.unwrap();
+            for entry in manifest.entries() {
+                if entry.status() == ManifestStatus::Added
+                    && entry.content_type() == DataContentType::Data
+                {
+                    assert!(entry.sequence_number().unwrap() < snapshot.sequence_number());
+                }
+            }
+        }
     }
 }
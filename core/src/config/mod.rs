@@ -14,13 +14,164 @@
  * limitations under the License.
  */
 
-use serde::Deserialize;
-use serde_with::serde_as;
+use serde::{Deserialize, Serialize};
+use serde_with::formats::CommaSeparator;
+use serde_with::{DisplayFromStr, PickFirst, StringWithSeparator, serde_as};
 
+use crate::error::{CompactionError, Result};
+
+/// Numeric fields accept both numbers (YAML config) and strings (`rewrite_file_config` in proto).
 #[serde_as]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CompactionConfig {
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
     pub batch_parallelism: Option<usize>,
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
     pub target_partitions: Option<usize>,
     pub data_file_prefix: Option<String>,
+    /// Number of files opened and buffered ahead of the one being read in each scan partition
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    pub file_prefetch_depth: Option<usize>,
+    /// Parquet data files larger than this many bytes are split at row group boundaries
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    pub target_split_size: Option<u64>,
+    /// Whether data files written with an older partition spec are rewritten into the partition
+    /// spec of the request, rather than rejected
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    pub rewrite_to_current_spec: Option<bool>,
+}
+
+impl CompactionConfig {
+    /// Returns a config with the fields set in `overrides` taking precedence over `self`.
+    pub fn merge(&self, overrides: &CompactionConfig) -> CompactionConfig {
+        CompactionConfig {
+            batch_parallelism: overrides.batch_parallelism.or(self.batch_parallelism),
+            target_partitions: overrides.target_partitions.or(self.target_partitions),
+            data_file_prefix: overrides
+                .data_file_prefix
+                .clone()
+                .or_else(|| self.data_file_prefix.clone()),
+            file_prefetch_depth: overrides.file_prefetch_depth.or(self.file_prefetch_depth),
+            target_split_size: overrides.target_split_size.or(self.target_split_size),
+            rewrite_to_current_spec: overrides
+                .rewrite_to_current_spec
+                .or(self.rewrite_to_current_spec),
+        }
+    }
+
+    /// Rejects values the executor cannot run with.
+    pub fn validate(&self) -> Result<()> {
+        if self.batch_parallelism == Some(0) {
+            return Err(CompactionError::InvalidInput(
+                "batch_parallelism must be greater than 0".to_owned(),
+            ));
+        }
+        if self.target_partitions == Some(0) {
+            return Err(CompactionError::InvalidInput(
+                "target_partitions must be greater than 0".to_owned(),
+            ));
+        }
+        if self.target_split_size == Some(0) {
+            return Err(CompactionError::InvalidInput(
+                "target_split_size must be greater than 0".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Selects the executor running the rewrites of a process.
+///
+/// Unlike [`CompactionConfig`], it is never taken from a request: the executor decides where the
+/// files and the storage credentials of a request are sent.
+#[serde_as]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExecutorConfig {
+    /// Name of the executor in the `ExecutorRegistry`, `datafusion` if not set
+    pub name: Option<String>,
+    /// Compactor service endpoints the `remote` executor sends rewrites to, a list or a
+    /// comma-separated string
+    #[serde_as(as = "PickFirst<(_, StringWithSeparator<CommaSeparator, String>)>")]
+    pub remote_endpoints: Vec<String>,
+    /// Bytes of data files the `remote` executor sends to a compactor in a single request
+    pub remote_group_size: Option<u64>,
+    /// Seconds the `remote` executor waits for a compactor to rewrite a group
+    pub remote_request_timeout_secs: Option<u64>,
+    /// Memory limit in bytes shared by all operators of the rewrites of the process, unlimited if
+    /// not set. Setting it replaces hash joins, which cannot spill, by sort merge joins
+    pub memory_limit: Option<usize>,
+    /// Directory used by operators to spill to disk once the memory limit is reached
+    pub spill_dir: Option<String>,
+}
+
+impl ExecutorConfig {
+    /// Names of the settings, as found in a serialized config.
+    pub fn field_names() -> Vec<String> {
+        match serde_json::to_value(Self::default()) {
+            Ok(serde_json::Value::Object(fields)) => fields.into_iter().map(|(k, _)| k).collect(),
+            _ => unreachable!("ExecutorConfig serializes to a JSON object"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_deserialize_from_string_map() {
+        let map = HashMap::from([
+            ("batch_parallelism".to_owned(), "8".to_owned()),
+            ("file_prefetch_depth".to_owned(), "3".to_owned()),
+            ("data_file_prefix".to_owned(), "20".to_owned()),
+            ("rewrite_to_current_spec".to_owned(), "true".to_owned()),
+        ]);
+        let config: CompactionConfig =
+            serde_json::from_value(serde_json::to_value(map).unwrap()).unwrap();
+        assert_eq!(config.batch_parallelism, Some(8));
+        assert_eq!(config.file_prefetch_depth, Some(3));
+        assert_eq!(config.data_file_prefix.as_deref(), Some("20"));
+        assert_eq!(config.target_partitions, None);
+        assert_eq!(config.rewrite_to_current_spec, Some(true));
+    }
+
+    #[test]
+    fn test_merge() {
+        let default_config = CompactionConfig {
+            batch_parallelism: Some(4),
+            target_partitions: Some(4),
+            data_file_prefix: Some("10".to_owned()),
+            ..Default::default()
+        };
+        let overrides = CompactionConfig {
+            target_partitions: Some(16),
+            target_split_size: Some(1024),
+            ..Default::default()
+        };
+        let config = default_config.merge(&overrides);
+        assert_eq!(config.batch_parallelism, Some(4));
+        assert_eq!(config.target_partitions, Some(16));
+        assert_eq!(config.data_file_prefix.as_deref(), Some("10"));
+        assert_eq!(config.target_split_size, Some(1024));
+        assert_eq!(config.file_prefetch_depth, None);
+    }
+
+    #[test]
+    fn test_executor_config_field_names() {
+        let mut field_names = ExecutorConfig::field_names();
+        field_names.sort();
+        assert_eq!(
+            field_names,
+            [
+                "memory_limit",
+                "name",
+                "remote_endpoints",
+                "remote_group_size",
+                "remote_request_timeout_secs",
+                "spill_dir",
+            ]
+        );
+    }
 }
@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use datafusion::error::DataFusionError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -24,14 +25,98 @@ pub enum CompactionError {
     #[error("Invalid configuration: {0}")]
     Config(String),
 
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("Storage throttled: {0}")]
+    StorageThrottled(String),
+
+    #[error("Commit conflict: {0}")]
+    CommitConflict(String),
+
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
     #[error("Execution failed: {0}")]
     Execution(String),
 
     #[error("Iceberg error: {0}")]
-    Iceberg(#[from] iceberg::Error),
+    Iceberg(iceberg::Error),
 
     #[error("DataFusion error: {0}")]
-    DataFusion(#[from] datafusion::error::DataFusionError),
+    DataFusion(DataFusionError),
+}
+
+impl CompactionError {
+    /// Whether the same request may succeed when retried later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            CompactionError::StorageThrottled(_) | CompactionError::CommitConflict(_) => true,
+            CompactionError::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+            ),
+            CompactionError::DataFusion(DataFusionError::ResourcesExhausted(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Classifies storage errors from opendal anywhere in the source chain of `err`.
+    fn from_storage_error(err: &(dyn std::error::Error + 'static)) -> Option<CompactionError> {
+        let mut source = Some(err);
+        while let Some(e) = source {
+            if let Some(storage_error) = e.downcast_ref::<opendal::Error>() {
+                return match storage_error.kind() {
+                    opendal::ErrorKind::NotFound => {
+                        Some(CompactionError::NotFound(err.to_string()))
+                    }
+                    opendal::ErrorKind::PermissionDenied => {
+                        Some(CompactionError::PermissionDenied(err.to_string()))
+                    }
+                    opendal::ErrorKind::RateLimited => {
+                        Some(CompactionError::StorageThrottled(err.to_string()))
+                    }
+                    _ => None,
+                };
+            }
+            source = e.source();
+        }
+        None
+    }
+}
+
+impl From<iceberg::Error> for CompactionError {
+    fn from(e: iceberg::Error) -> Self {
+        // the catalog rejected a commit whose requirements no longer hold, as another writer
+        // committed to the table since it was loaded
+        if e.kind() == iceberg::ErrorKind::CatalogCommitConflicts {
+            return CompactionError::CommitConflict(e.to_string());
+        }
+        Self::from_storage_error(&e).unwrap_or(CompactionError::Iceberg(e))
+    }
+}
+
+impl From<DataFusionError> for CompactionError {
+    fn from(e: DataFusionError) -> Self {
+        // errors raised inside a plan are wrapped to cross DataFusion
+        let e = match e {
+            DataFusionError::External(e) => match e.downcast::<CompactionError>() {
+                Ok(e) => return *e,
+                Err(e) => DataFusionError::External(e),
+            },
+            e => e,
+        };
+        Self::from_storage_error(&e).unwrap_or(CompactionError::DataFusion(e))
+    }
 }
 
 pub type Result<T> = std::result::Result<T, CompactionError>;
@@ -0,0 +1,117 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Column bounds of the data files written by the executors.
+
+use iceberg::spec::{Datum, PrimitiveLiteral};
+
+/// Length string and binary bounds are truncated to, as by the default `truncate(16)` metrics
+/// mode of iceberg.
+const TRUNCATED_BOUND_LENGTH: usize = 16;
+
+/// Truncates string and binary bounds to [`TRUNCATED_BOUND_LENGTH`] characters or bytes, an
+/// upper bound being rounded up to stay above the values. `None` if it cannot be rounded up.
+pub(crate) fn truncate_bound(bound: &Datum, lower: bool) -> Option<Datum> {
+    match bound.literal() {
+        PrimitiveLiteral::String(value) => {
+            if value.chars().count() <= TRUNCATED_BOUND_LENGTH {
+                return Some(bound.clone());
+            }
+            let mut chars = value
+                .chars()
+                .take(TRUNCATED_BOUND_LENGTH)
+                .collect::<Vec<_>>();
+            if !lower {
+                loop {
+                    let last = chars.pop()?;
+                    let next = match last {
+                        '\u{D7FF}' => Some('\u{E000}'),
+                        last => char::from_u32(last as u32 + 1),
+                    };
+                    if let Some(next) = next {
+                        chars.push(next);
+                        break;
+                    }
+                }
+            }
+            Some(Datum::string(chars.into_iter().collect::<String>()))
+        }
+        PrimitiveLiteral::Binary(value) => {
+            if value.len() <= TRUNCATED_BOUND_LENGTH {
+                return Some(bound.clone());
+            }
+            let mut bytes = value[..TRUNCATED_BOUND_LENGTH].to_vec();
+            if !lower {
+                loop {
+                    let last = bytes.pop()?;
+                    if last < u8::MAX {
+                        bytes.push(last + 1);
+                        break;
+                    }
+                }
+            }
+            Some(Datum::binary(bytes))
+        }
+        _ => Some(bound.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_bound() {
+        let long = "abcdefghijklmnopqrstuvwxyz";
+        assert_eq!(
+            truncate_bound(&Datum::string(long), true),
+            Some(Datum::string("abcdefghijklmnop"))
+        );
+        assert_eq!(
+            truncate_bound(&Datum::string(long), false),
+            Some(Datum::string("abcdefghijklmnoq"))
+        );
+        assert_eq!(
+            truncate_bound(&Datum::string("short"), false),
+            Some(Datum::string("short"))
+        );
+        // the last characters that cannot be incremented are dropped
+        let max_chars = format!("abcdefghijklmno{}z", char::MAX);
+        assert_eq!(
+            truncate_bound(&Datum::string(&max_chars), false),
+            Some(Datum::string("abcdefghijklmnp"))
+        );
+        assert_eq!(
+            truncate_bound(&Datum::string(char::MAX.to_string().repeat(17)), false),
+            None
+        );
+
+        let mut bytes = vec![1u8; 15];
+        bytes.extend([u8::MAX, 7]);
+        assert_eq!(
+            truncate_bound(&Datum::binary(bytes.clone()), true),
+            Some(Datum::binary(bytes[..16].to_vec()))
+        );
+        assert_eq!(
+            truncate_bound(&Datum::binary(bytes), false),
+            Some(Datum::binary([vec![1u8; 14], vec![2]].concat()))
+        );
+        assert_eq!(
+            truncate_bound(&Datum::binary(vec![u8::MAX; 17]), false),
+            None
+        );
+    }
+}
@@ -1,5 +1,5 @@
 /*
- * Copyright 2025 IC
+ * Copyright 2025 BergLoom
  *
  * Licensed under the Apache License, Version 2.0 (the "License");
  * you may not use this file except in compliance with the License.
@@ -14,33 +14,40 @@
  * limitations under the License.
  */
 
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 use crate::error::{CompactionError, Result};
+use crate::executor::{DataFileMetadata, DeletionVector};
 use datafusion::{
+    common::{Column, JoinType, TableReference},
+    datasource::provider_as_source,
     execution::SendableRecordBatchStream,
+    logical_expr::{Expr, LogicalPlan, LogicalPlanBuilder},
     physical_plan::{
-        ExecutionPlan, ExecutionPlanProperties, Partitioning, execute_stream_partitioned,
-        repartition::RepartitionExec,
+        ExecutionPlan, ExecutionPlanProperties, Partitioning, execute_stream,
+        execute_stream_partitioned, repartition::RepartitionExec, stream::RecordBatchStreamAdapter,
     },
     prelude::SessionContext,
 };
+use futures::{StreamExt, TryStreamExt};
 use iceberg::{
     arrow::schema_to_arrow_schema,
     io::FileIO,
     scan::FileScanTask,
-    spec::{NestedField, PrimitiveType, Schema, Type},
+    spec::{NestedField, PartitionSpec, PrimitiveType, Schema, SortOrderRef, Struct, Type},
 };
 
 use super::file_scan_task_table_provider::IcebergFileScanTaskTableProvider;
+use super::iceberg_file_task_scan::DataFileLayout;
+use super::partition_key::partition_key_exprs;
+use super::position_delete::PositionDeleteIndex;
 
 pub const SYS_HIDDEN_SEQ_NUM: &str = "sys_hidden_seq_num";
-pub const SYS_HIDDEN_FILE_PATH: &str = "sys_hidden_file_path";
-pub const SYS_HIDDEN_POS: &str = "sys_hidden_pos";
-const SYS_HIDDEN_COLS: [&str; 3] = [SYS_HIDDEN_SEQ_NUM, SYS_HIDDEN_FILE_PATH, SYS_HIDDEN_POS];
+pub(crate) const SYS_HIDDEN_COLS: [&str; 1] = [SYS_HIDDEN_SEQ_NUM];
 
 const DATA_FILE_TABLE: &str = "data_file_table";
-const POSITION_DELETE_TABLE: &str = "position_delete_table";
 const EQUALITY_DELETE_TABLE: &str = "equality_delete_table";
 
 pub struct DatafusionProcessor {
@@ -57,9 +64,16 @@ impl DatafusionProcessor {
         datafusion_task_ctx: DataFusionTaskContext,
         batch_parallelism: usize,
         target_partitions: usize,
+        file_prefetch_depth: usize,
+        target_split_size: u64,
         file_io: FileIO,
     ) -> Self {
-        let table_register = DatafusionTableRegister::new(file_io, ctx.clone());
+        let table_register = DatafusionTableRegister::new(
+            file_io,
+            ctx.clone(),
+            file_prefetch_depth,
+            target_split_size,
+        );
         Self {
             datafusion_task_ctx,
             table_register,
@@ -69,39 +83,50 @@ impl DatafusionProcessor {
         }
     }
 
-    pub fn register_tables(&mut self) -> Result<()> {
-        if let Some(datafile_schema) = self.datafusion_task_ctx.data_file_schema.take() {
+    pub async fn register_tables(&mut self) -> Result<()> {
+        let datafile_schema = self.datafusion_task_ctx.data_file_schema.take().unwrap();
+        let need_seq_num = self.datafusion_task_ctx.need_seq_num();
+        let mut data_file_metadata =
+            std::mem::take(&mut self.datafusion_task_ctx.data_file_metadata);
+        for group in &mut self.datafusion_task_ctx.merge_on_read_groups {
+            let data_files = std::mem::take(&mut group.data_files);
+            // position deletes and deletion vectors are applied while scanning the data files,
+            // each group only indexing the deletes of its own partition
+            let position_delete_files = std::mem::take(&mut group.position_delete_files);
+            let deletion_vectors = std::mem::take(&mut group.deletion_vectors);
+            let position_deletes =
+                if position_delete_files.is_empty() && deletion_vectors.is_empty() {
+                    None
+                } else {
+                    Some(Arc::new(
+                        self.table_register
+                            .load_position_deletes(position_delete_files, deletion_vectors)
+                            .await?,
+                    ))
+                };
+            let layout = DataFileLayout {
+                partition_spec: Some(self.datafusion_task_ctx.partition_spec.clone()),
+                sort_order: self.datafusion_task_ctx.sort_order.clone(),
+                data_file_metadata: data_files
+                    .iter()
+                    .filter_map(|task| data_file_metadata.remove_entry(&task.data_file_path))
+                    .collect(),
+            };
             self.table_register.register_data_table_provider(
                 &datafile_schema,
-                self.datafusion_task_ctx.data_files.take().unwrap(),
-                DATA_FILE_TABLE,
-                self.datafusion_task_ctx.need_seq_num(),
-                self.datafusion_task_ctx.need_file_path_and_pos(),
-                self.batch_parallelism,
-            )?;
-        }
-
-        if let Some(position_delete_schema) = self.datafusion_task_ctx.position_delete_schema.take()
-        {
-            self.table_register.register_delete_table_provider(
-                &position_delete_schema,
-                self.datafusion_task_ctx
-                    .position_delete_files
-                    .take()
-                    .unwrap(),
-                POSITION_DELETE_TABLE,
+                data_files,
+                &group.data_file_table_name,
+                need_seq_num,
+                position_deletes,
                 self.batch_parallelism,
+                layout,
             )?;
-        }
 
-        if let Some(equality_delete_metadatas) =
-            self.datafusion_task_ctx.equality_delete_metadatas.take()
-        {
             for EqualityDeleteMetadata {
                 equality_delete_schema,
                 equality_delete_table_name,
                 file_scan_tasks,
-            } in equality_delete_metadatas
+            } in std::mem::take(&mut group.equality_delete_metadatas)
             {
                 self.table_register.register_delete_table_provider(
                     &equality_delete_schema,
@@ -115,53 +140,129 @@ impl DatafusionProcessor {
     }
 
     pub async fn execute(&mut self) -> Result<(Vec<SendableRecordBatchStream>, Schema)> {
-        self.register_tables()?;
-        let df = self.ctx.sql(&self.datafusion_task_ctx.exec_sql).await?;
+        self.register_tables().await?;
+        let input_schema = self.datafusion_task_ctx.input_schema.take().unwrap();
+        let mut groups = std::mem::take(&mut self.datafusion_task_ctx.merge_on_read_groups);
+        if groups.len() > 1 {
+            let batchs = self.execute_by_partition(groups).await?;
+            return Ok((batchs, input_schema));
+        }
+
+        // a request of deletes only has no data files to rewrite
+        let Some(group) = groups.pop() else {
+            return Ok((vec![], input_schema));
+        };
+        let plan = group.plan_builder.build(&self.ctx).await?;
+        let df = self.ctx.execute_logical_plan(plan).await?;
         let physical_plan = df.create_physical_plan().await?;
-        let batchs =
-            if physical_plan.output_partitioning().partition_count() != self.target_partitions {
-                let physical_plan: Arc<dyn ExecutionPlan + 'static> =
-                    Arc::new(RepartitionExec::try_new(
-                        physical_plan,
-                        Partitioning::RoundRobinBatch(self.target_partitions),
-                    )?);
+        // route the rows of a partition to a single writer, so that it is not spread over
+        // small files written by every writer
+        let partition_key_exprs = partition_key_exprs(
+            &self.datafusion_task_ctx.partition_spec,
+            &input_schema,
+            &physical_plan.schema(),
+        )?;
+        let batchs = if let Some(partition_key_exprs) = partition_key_exprs {
+            let partitioning = Partitioning::Hash(partition_key_exprs, self.target_partitions);
+            // the scan may already be grouped by partition
+            if physical_plan.output_partitioning() == &partitioning {
                 execute_stream_partitioned(physical_plan, self.ctx.task_ctx())?
             } else {
+                let physical_plan: Arc<dyn ExecutionPlan + 'static> =
+                    Arc::new(RepartitionExec::try_new(physical_plan, partitioning)?);
                 execute_stream_partitioned(physical_plan, self.ctx.task_ctx())?
-            };
-        Ok((
-            batchs,
-            self.datafusion_task_ctx.input_schema.take().unwrap(),
-        ))
+            }
+        } else if physical_plan.output_partitioning().partition_count() != self.target_partitions {
+            let physical_plan: Arc<dyn ExecutionPlan + 'static> =
+                Arc::new(RepartitionExec::try_new(
+                    physical_plan,
+                    Partitioning::RoundRobinBatch(self.target_partitions),
+                )?);
+            execute_stream_partitioned(physical_plan, self.ctx.task_ctx())?
+        } else {
+            execute_stream_partitioned(physical_plan, self.ctx.task_ctx())?
+        };
+        Ok((batchs, input_schema))
+    }
+
+    /// Runs the plan of each partition on its own, so that the anti joins of a partition only
+    /// build its own equality deletes.
+    ///
+    /// Each output stream reads its partitions one after the other, the largest partitions being
+    /// spread first over the least loaded streams. All rows of a partition go to the same writer.
+    async fn execute_by_partition(
+        &self,
+        groups: Vec<MergeOnReadGroup>,
+    ) -> Result<Vec<SendableRecordBatchStream>> {
+        let mut plans = Vec::with_capacity(groups.len());
+        for group in groups {
+            let plan = group.plan_builder.build(&self.ctx).await?;
+            let df = self.ctx.execute_logical_plan(plan).await?;
+            plans.push((group.data_size, df.create_physical_plan().await?));
+        }
+        let schema = plans[0].1.schema();
+        plans.sort_by_key(|(data_size, _)| Reverse(*data_size));
+        let mut streams = vec![(0, vec![]); self.target_partitions];
+        for (data_size, plan) in plans {
+            let (load, stream_plans) = streams.iter_mut().min_by_key(|(load, _)| *load).unwrap();
+            *load += data_size;
+            stream_plans.push(plan);
+        }
+        Ok(streams
+            .into_iter()
+            .map(|(_, plans)| {
+                let task_ctx = self.ctx.task_ctx();
+                let batches = futures::stream::iter(plans)
+                    .map(move |plan| execute_stream(plan, task_ctx.clone()))
+                    .try_flatten();
+                Box::pin(RecordBatchStreamAdapter::new(schema.clone(), batches))
+                    as SendableRecordBatchStream
+            })
+            .collect())
     }
 }
 
 pub struct DatafusionTableRegister {
     file_io: FileIO,
     ctx: Arc<SessionContext>,
+    file_prefetch_depth: usize,
+    target_split_size: u64,
 }
 
 impl DatafusionTableRegister {
-    pub fn new(file_io: FileIO, ctx: Arc<SessionContext>) -> Self {
-        DatafusionTableRegister { file_io, ctx }
+    pub fn new(
+        file_io: FileIO,
+        ctx: Arc<SessionContext>,
+        file_prefetch_depth: usize,
+        target_split_size: u64,
+    ) -> Self {
+        DatafusionTableRegister {
+            file_io,
+            ctx,
+            file_prefetch_depth,
+            target_split_size,
+        }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn register_data_table_provider(
         &self,
         schema: &Schema,
         file_scan_tasks: Vec<FileScanTask>,
         table_name: &str,
         need_seq_num: bool,
-        need_file_path_and_pos: bool,
+        position_deletes: Option<Arc<PositionDeleteIndex>>,
         batch_parallelism: usize,
+        layout: DataFileLayout,
     ) -> Result<()> {
         self.register_table_provider_impl(
             schema,
             file_scan_tasks,
             table_name,
             need_seq_num,
-            need_file_path_and_pos,
+            position_deletes,
             batch_parallelism,
+            layout,
         )
     }
 
@@ -177,8 +278,9 @@ impl DatafusionTableRegister {
             file_scan_tasks,
             table_name,
             false,
-            false,
+            None,
             batch_parallelism,
+            DataFileLayout::default(),
         )
     }
 
@@ -189,8 +291,9 @@ impl DatafusionTableRegister {
         file_scan_tasks: Vec<FileScanTask>,
         table_name: &str,
         need_seq_num: bool,
-        need_file_path_and_pos: bool,
+        position_deletes: Option<Arc<PositionDeleteIndex>>,
         batch_parallelism: usize,
+        layout: DataFileLayout,
     ) -> Result<()> {
         let schema = schema_to_arrow_schema(schema)?;
         let data_file_table_provider = IcebergFileScanTaskTableProvider::new(
@@ -198,131 +301,161 @@ impl DatafusionTableRegister {
             Arc::new(schema),
             self.file_io.clone(),
             need_seq_num,
-            need_file_path_and_pos,
+            position_deletes,
             batch_parallelism,
+            self.file_prefetch_depth,
+            self.target_split_size,
+            layout,
         );
 
         self.ctx
-            .register_table(table_name, Arc::new(data_file_table_provider))
-            .unwrap();
+            .register_table(table_name, Arc::new(data_file_table_provider))?;
         Ok(())
     }
-}
 
-/// SQL Builder for generating merge-on-read SQL queries
-struct SqlBuilder<'a> {
-    /// Column names to be projected in the query
-    project_names: &'a Vec<String>,
+    /// Loads the deleted positions of the data files from the position delete files and the
+    /// deletion vectors
+    pub async fn load_position_deletes(
+        &self,
+        position_delete_files: Vec<FileScanTask>,
+        deletion_vectors: Vec<DeletionVector>,
+    ) -> Result<PositionDeleteIndex> {
+        Ok(
+            PositionDeleteIndex::load(&self.file_io, position_delete_files, deletion_vectors)
+                .await?,
+        )
+    }
+}
 
-    /// Position delete table name
-    position_delete_table_name: Option<String>,
+/// Builds the merge-on-read logical plan over the registered tables.
+///
+/// Columns are referenced as [`Column`]s rather than SQL text, so names with spaces, reserved
+/// words or mixed case need no quoting.
+pub(crate) struct MergeOnReadPlanBuilder {
+    /// Column names to be projected in the plan
+    project_names: Vec<String>,
 
     /// Data file table name
-    data_file_table_name: Option<String>,
-
-    /// Flag indicating if file path and position columns are needed
-    equality_delete_metadatas: &'a Vec<EqualityDeleteMetadata>,
+    data_file_table_name: String,
 
-    /// Flag indicating if position delete files are needed
-    need_file_path_and_pos: bool,
+    /// Equality delete table names, each with the column names it is joined on
+    equality_deletes: Vec<(String, Vec<String>)>,
 }
 
-impl<'a> SqlBuilder<'a> {
-    /// Creates a new SQL Builder with the specified parameters
+impl MergeOnReadPlanBuilder {
+    /// Creates a new plan builder with the specified parameters
     fn new(
-        project_names: &'a Vec<String>,
-        position_delete_table_name: Option<String>,
-        data_file_table_name: Option<String>,
-        equality_delete_metadatas: &'a Vec<EqualityDeleteMetadata>,
-        need_file_path_and_pos: bool,
+        project_names: Vec<String>,
+        data_file_table_name: String,
+        equality_delete_metadatas: &[EqualityDeleteMetadata],
     ) -> Self {
+        let equality_deletes = equality_delete_metadatas
+            .iter()
+            .map(|metadata| {
+                (
+                    metadata.equality_delete_table_name.clone(),
+                    metadata
+                        .equality_delete_join_names()
+                        .into_iter()
+                        .map(str::to_owned)
+                        .collect(),
+                )
+            })
+            .collect();
         Self {
             project_names,
-            position_delete_table_name,
             data_file_table_name,
-            equality_delete_metadatas,
-            need_file_path_and_pos,
+            equality_deletes,
         }
     }
 
-    /// Builds a merge-on-read SQL query
+    /// Builds a merge-on-read plan
     ///
-    /// This method constructs a SQL query that:
-    /// 1. Selects the specified columns from the data file table
-    /// 2. Optionally joins with position delete files to exclude deleted rows
-    /// 3. Optionally joins with equality delete files to exclude rows based on equality conditions
-    pub fn build_merge_on_read_sql(self) -> Result<String> {
-        let data_file_table_name = self.data_file_table_name.as_ref().ok_or_else(|| {
-            CompactionError::Config("Data file table name is not provided".to_string())
-        })?;
-        // Start with a basic SELECT query from the data file table
-        let mut sql = format!(
-            "SELECT {} FROM {}",
-            self.project_names.join(","),
-            data_file_table_name
-        );
-
-        // Add position delete join if needed
-        // This excludes rows that have been deleted by position
-        if self.need_file_path_and_pos {
-            let position_delete_table_name =
-                self.position_delete_table_name.as_ref().ok_or_else(|| {
-                    CompactionError::Config(
-                        "Position delete table name is not provided".to_string(),
-                    )
-                })?;
-            sql.push_str(&format!(
-                    " LEFT ANTI JOIN {position_delete_table_name} ON {data_file_table_name}.{SYS_HIDDEN_FILE_PATH} = {position_delete_table_name}.{SYS_HIDDEN_FILE_PATH} AND {data_file_table_name}.{SYS_HIDDEN_POS} = {position_delete_table_name}.{SYS_HIDDEN_POS}",
-                ));
-        }
-
-        // Add equality delete join if needed
-        // This excludes rows that match the equality conditions in the delete files
-        if !self.equality_delete_metadatas.is_empty() {
-            for metadata in self.equality_delete_metadatas {
-                // LEFT ANTI JOIN ON equality delete table
-                sql.push_str(&format!(
-                    " LEFT ANTI JOIN {} ON {}",
-                    metadata.equality_delete_table_name,
-                    metadata
-                        .equality_delete_join_names()
+    /// The plan:
+    /// 1. Scans the data file table, which drops the rows deleted by position
+    /// 2. Optionally anti joins each equality delete table to exclude rows matching a newer delete,
+    ///    with nulls matching each other as in Iceberg equality deletes
+    /// 3. Projects the specified columns of the data file table
+    pub(crate) async fn build(&self, ctx: &SessionContext) -> Result<LogicalPlan> {
+        let data_file_table = TableReference::bare(self.data_file_table_name.as_str());
+        let data_column = |name: &str| Column::new(Some(data_file_table.clone()), name);
+        let mut builder = Self::scan(ctx, &data_file_table).await?;
+
+        for (equality_delete_table_name, join_names) in &self.equality_deletes {
+            let equality_delete_table = TableReference::bare(equality_delete_table_name.as_str());
+            let equality_delete_column =
+                |name: &str| Column::new(Some(equality_delete_table.clone()), name);
+            let right = Self::scan(ctx, &equality_delete_table).await?.build()?;
+            // only deletes committed after the data file apply to it
+            let filter = Expr::Column(data_column(SYS_HIDDEN_SEQ_NUM))
+                .lt(Expr::Column(equality_delete_column(SYS_HIDDEN_SEQ_NUM)));
+            builder = builder.join_detailed(
+                right,
+                JoinType::LeftAnti,
+                (
+                    join_names.iter().map(|name| data_column(name)).collect(),
+                    join_names
                         .iter()
-                        .map(|name| format!(
-                            "{data_file_table_name}.{name} = {}.{name}",
-                            metadata.equality_delete_table_name
-                        ))
-                        .collect::<Vec<_>>()
-                        .join(" AND ")
-                ));
-
-                // Add sequence number comparison if needed
-                // This ensures that only newer deletes are applied
-                sql.push_str(&format!(
-                    " AND {data_file_table_name}.{SYS_HIDDEN_SEQ_NUM} < {}.{SYS_HIDDEN_SEQ_NUM}",
-                    metadata.equality_delete_table_name
-                ));
-            }
+                        .map(|name| equality_delete_column(name))
+                        .collect(),
+                ),
+                Some(filter),
+                true,
+            )?;
         }
 
-        Ok(sql)
+        Ok(builder
+            .project(
+                self.project_names
+                    .iter()
+                    .map(|name| Expr::Column(data_column(name))),
+            )?
+            .build()?)
+    }
+
+    async fn scan(ctx: &SessionContext, table: &TableReference) -> Result<LogicalPlanBuilder> {
+        let provider = ctx.table_provider(table.clone()).await?;
+        Ok(LogicalPlanBuilder::scan(
+            table.clone(),
+            provider_as_source(provider),
+            None,
+        )?)
     }
 }
 
 pub struct DataFusionTaskContext {
     pub(crate) data_file_schema: Option<Schema>,
     pub(crate) input_schema: Option<Schema>,
-    pub(crate) data_files: Option<Vec<FileScanTask>>,
-    pub(crate) position_delete_files: Option<Vec<FileScanTask>>,
     pub(crate) equality_delete_files: Option<Vec<FileScanTask>>,
-    pub(crate) position_delete_schema: Option<Schema>,
-    pub(crate) equality_delete_metadatas: Option<Vec<EqualityDeleteMetadata>>,
-    pub(crate) exec_sql: String,
+    /// Data files with their deletes, one group per partition when the deletes can be matched by
+    /// partition
+    pub(crate) merge_on_read_groups: Vec<MergeOnReadGroup>,
+    pub(crate) partition_spec: Arc<PartitionSpec>,
+    pub(crate) sort_order: Option<SortOrderRef>,
+    pub(crate) data_file_metadata: HashMap<String, DataFileMetadata>,
+}
+
+/// Data files merged by one plan with the deletes that may apply to them.
+pub(crate) struct MergeOnReadGroup {
+    pub(crate) data_file_table_name: String,
+    pub(crate) data_files: Vec<FileScanTask>,
+    pub(crate) position_delete_files: Vec<FileScanTask>,
+    pub(crate) deletion_vectors: Vec<DeletionVector>,
+    pub(crate) equality_delete_metadatas: Vec<EqualityDeleteMetadata>,
+    pub(crate) plan_builder: MergeOnReadPlanBuilder,
+    /// Bytes of the data files read by the plan
+    pub(crate) data_size: u64,
 }
 
 pub struct DataFusionTaskContextBuilder {
     schema: Arc<Schema>,
+    partition_spec: Arc<PartitionSpec>,
+    sort_order: Option<SortOrderRef>,
+    data_file_metadata: HashMap<String, DataFileMetadata>,
+    delete_file_metadata: HashMap<String, DataFileMetadata>,
     data_files: Vec<FileScanTask>,
     position_delete_files: Vec<FileScanTask>,
+    deletion_vectors: Vec<DeletionVector>,
     equality_delete_files: Vec<FileScanTask>,
 }
 
@@ -332,6 +465,32 @@ impl DataFusionTaskContextBuilder {
         self
     }
 
+    pub fn with_partition_spec(mut self, partition_spec: Arc<PartitionSpec>) -> Self {
+        self.partition_spec = partition_spec;
+        self
+    }
+
+    pub fn with_sort_order(mut self, sort_order: Option<SortOrderRef>) -> Self {
+        self.sort_order = sort_order;
+        self
+    }
+
+    pub fn with_data_file_metadata(
+        mut self,
+        data_file_metadata: HashMap<String, DataFileMetadata>,
+    ) -> Self {
+        self.data_file_metadata = data_file_metadata;
+        self
+    }
+
+    pub fn with_delete_file_metadata(
+        mut self,
+        delete_file_metadata: HashMap<String, DataFileMetadata>,
+    ) -> Self {
+        self.delete_file_metadata = delete_file_metadata;
+        self
+    }
+
     pub fn with_datafile(mut self, data_files: Vec<FileScanTask>) -> Self {
         self.data_files = data_files;
         self
@@ -342,67 +501,34 @@ impl DataFusionTaskContextBuilder {
         self
     }
 
-    pub fn with_equality_delete_files(mut self, equality_delete_files: Vec<FileScanTask>) -> Self {
-        self.equality_delete_files = equality_delete_files;
+    pub fn with_deletion_vectors(mut self, deletion_vectors: Vec<DeletionVector>) -> Self {
+        self.deletion_vectors = deletion_vectors;
         self
     }
 
-    fn build_position_schema() -> Result<Schema> {
-        let position_delete_schema = Schema::builder()
-            .with_fields(vec![
-                Arc::new(NestedField::new(
-                    1,
-                    SYS_HIDDEN_FILE_PATH,
-                    Type::Primitive(PrimitiveType::String),
-                    true,
-                )),
-                Arc::new(NestedField::new(
-                    2,
-                    SYS_HIDDEN_POS,
-                    Type::Primitive(PrimitiveType::Long),
-                    true,
-                )),
-            ])
-            .build()?;
-        Ok(position_delete_schema)
+    pub fn with_equality_delete_files(mut self, equality_delete_files: Vec<FileScanTask>) -> Self {
+        self.equality_delete_files = equality_delete_files;
+        self
     }
 
     // build data fusion task context
-    pub fn build_merge_on_read(self) -> Result<DataFusionTaskContext> {
-        let mut highest_field_id = self.schema.highest_field_id();
-        // Build scheam for position delete file, file_path + pos
-        let position_delete_schema = Self::build_position_schema()?;
-        // Build schema for equality delete file, equality_ids + seq_num
-        let mut equality_ids: Option<Vec<i32>> = None;
-        let mut equality_delete_metadatas = Vec::new();
-        let mut table_idx = 0;
-        for task in &self.equality_delete_files {
-            if equality_ids
-                .as_ref()
-                .is_none_or(|ids| !ids.eq(&task.equality_ids))
-            {
-                // If ids are different or not assigned, create a new metadata
-                let equality_delete_schema =
-                    self.build_equality_delete_schema(&task.equality_ids, &mut highest_field_id)?;
-                let equality_delete_table_name = format!("{}_{}", EQUALITY_DELETE_TABLE, table_idx);
-                equality_delete_metadatas.push(EqualityDeleteMetadata::new(
-                    equality_delete_schema,
-                    equality_delete_table_name,
-                ));
-                equality_ids = Some(task.equality_ids.clone());
-                table_idx += 1;
-            }
-
-            // Add the file scan task to the last metadata
-            if let Some(last_metadata) = equality_delete_metadatas.last_mut() {
-                last_metadata.add_file_scan_task(task.clone());
-            }
+    pub fn build_merge_on_read(mut self) -> Result<DataFusionTaskContext> {
+        // deletes only apply to data files with a lower sequence number, or the same one for
+        // position deletes
+        if let Some(min_sequence_number) = self
+            .data_files
+            .iter()
+            .map(|task| task.sequence_number)
+            .min()
+        {
+            self.equality_delete_files
+                .retain(|task| task.sequence_number > min_sequence_number);
+            self.position_delete_files
+                .retain(|task| task.sequence_number >= min_sequence_number);
         }
+        let need_seq_num = !self.equality_delete_files.is_empty();
 
-        let need_file_path_and_pos = !self.position_delete_files.is_empty();
-        let need_seq_num = !equality_delete_metadatas.is_empty();
-
-        // Build schema for data file, old schema + seq_num + file_path + pos
+        // Build schema for data file, old schema + seq_num
         let project_names: Vec<_> = self
             .schema
             .as_struct()
@@ -415,28 +541,13 @@ impl DataFusionTaskContextBuilder {
         // add sequence number column if needed
         if need_seq_num {
             add_schema_fields.push(Arc::new(NestedField::new(
-                highest_field_id + 1,
+                next_field_id(highest_field_id, 1)?,
                 SYS_HIDDEN_SEQ_NUM,
                 Type::Primitive(PrimitiveType::Long),
                 true,
             )));
         }
-        // add file path and position column if needed
-        if need_file_path_and_pos {
-            add_schema_fields.push(Arc::new(NestedField::new(
-                highest_field_id + 2,
-                SYS_HIDDEN_FILE_PATH,
-                Type::Primitive(PrimitiveType::String),
-                true,
-            )));
-            add_schema_fields.push(Arc::new(NestedField::new(
-                highest_field_id + 3,
-                SYS_HIDDEN_POS,
-                Type::Primitive(PrimitiveType::Long),
-                true,
-            )));
-        }
-        // data file schema is old schema + seq_num + file_path + pos. used for data file table provider
+        // data file schema is old schema + seq_num. used for data file table provider
         let data_file_schema = self
             .schema
             .as_ref()
@@ -447,41 +558,195 @@ impl DataFusionTaskContextBuilder {
         // input schema is old schema. used for data file writer
         let input_schema = self.schema.as_ref().clone();
 
-        let sql_builder = SqlBuilder::new(
-            &project_names,
-            Some(POSITION_DELETE_TABLE.to_owned()),
-            Some(DATA_FILE_TABLE.to_owned()),
-            &equality_delete_metadatas,
-            need_file_path_and_pos,
-        );
-        let exec_sql = sql_builder.build_merge_on_read_sql()?;
+        let data_files = std::mem::take(&mut self.data_files);
+        let merge_on_read_groups = if self.can_match_deletes_by_partition(&data_files) {
+            let mut deletion_vectors: HashMap<String, Vec<DeletionVector>> = HashMap::new();
+            for deletion_vector in std::mem::take(&mut self.deletion_vectors) {
+                deletion_vectors
+                    .entry(deletion_vector.referenced_data_file.clone())
+                    .or_default()
+                    .push(deletion_vector);
+            }
+            self.partition_data_files(data_files)
+                .into_iter()
+                .enumerate()
+                .map(|(idx, (partition, data_files))| {
+                    let min_sequence_number = data_files
+                        .iter()
+                        .map(|task| task.sequence_number)
+                        .min()
+                        .unwrap_or_default();
+                    let position_delete_files = self
+                        .position_delete_files
+                        .iter()
+                        .filter(|task| {
+                            task.sequence_number >= min_sequence_number
+                                && self.applies_to_partition(task, &partition)
+                        })
+                        .cloned()
+                        .collect();
+                    let deletion_vectors = data_files
+                        .iter()
+                        .flat_map(|task| {
+                            deletion_vectors
+                                .remove(&task.data_file_path)
+                                .unwrap_or_default()
+                        })
+                        .collect();
+                    let equality_delete_files = self
+                        .equality_delete_files
+                        .iter()
+                        .filter(|task| {
+                            task.sequence_number > min_sequence_number
+                                && self.applies_to_partition(task, &partition)
+                        })
+                        .collect();
+                    self.build_merge_on_read_group(
+                        format!("{}_{}", DATA_FILE_TABLE, idx),
+                        format!("{}_{}", EQUALITY_DELETE_TABLE, idx),
+                        data_files,
+                        position_delete_files,
+                        deletion_vectors,
+                        equality_delete_files,
+                        &project_names,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            vec![self.build_merge_on_read_group(
+                DATA_FILE_TABLE.to_owned(),
+                EQUALITY_DELETE_TABLE.to_owned(),
+                data_files,
+                std::mem::take(&mut self.position_delete_files),
+                std::mem::take(&mut self.deletion_vectors),
+                self.equality_delete_files.iter().collect(),
+                &project_names,
+            )?]
+        };
 
         Ok(DataFusionTaskContext {
             data_file_schema: Some(data_file_schema),
             input_schema: Some(input_schema),
-            data_files: Some(self.data_files),
-            position_delete_files: Some(self.position_delete_files),
             equality_delete_files: Some(self.equality_delete_files),
-            position_delete_schema: if need_file_path_and_pos {
-                Some(position_delete_schema)
-            } else {
-                None
-            },
-            equality_delete_metadatas: if need_seq_num {
-                Some(equality_delete_metadatas)
-            } else {
-                None
-            },
-            exec_sql,
+            merge_on_read_groups,
+            partition_spec: self.partition_spec,
+            sort_order: self.sort_order,
+            data_file_metadata: self.data_file_metadata,
         })
     }
 
-    /// Builds an equality delete schema based on the given equality_ids
-    fn build_equality_delete_schema(
+    /// Whether the deletes can be matched with the data files of each partition, which needs the
+    /// partition of every data and delete file. Deletion vectors name their data file. Without
+    /// data files, the deletes are left to the single plan of the request.
+    fn can_match_deletes_by_partition(&self, data_files: &[FileScanTask]) -> bool {
+        let has_partition = |metadata: &HashMap<String, DataFileMetadata>, task: &FileScanTask| {
+            metadata
+                .get(&task.data_file_path)
+                .is_some_and(|metadata| metadata.partition.is_some())
+        };
+        let has_deletes = !self.equality_delete_files.is_empty()
+            || !self.position_delete_files.is_empty()
+            || !self.deletion_vectors.is_empty();
+        has_deletes
+            && !data_files.is_empty()
+            && data_files
+                .iter()
+                .all(|task| has_partition(&self.data_file_metadata, task))
+            && self
+                .equality_delete_files
+                .iter()
+                .chain(&self.position_delete_files)
+                .all(|task| has_partition(&self.delete_file_metadata, task))
+    }
+
+    /// Groups the data files by partition spec id and partition value.
+    fn partition_data_files(
         &self,
-        equality_ids: &[i32],
-        highest_field_id: &mut i32,
-    ) -> Result<Schema> {
+        data_files: Vec<FileScanTask>,
+    ) -> Vec<((i32, Struct), Vec<FileScanTask>)> {
+        let mut partitions: Vec<((i32, Struct), Vec<FileScanTask>)> = vec![];
+        let mut partition_idx = HashMap::new();
+        for task in data_files {
+            let metadata = &self.data_file_metadata[&task.data_file_path];
+            let partition = (
+                metadata.partition_spec_id,
+                metadata.partition.clone().unwrap(),
+            );
+            let idx = *partition_idx.entry(partition.clone()).or_insert_with(|| {
+                partitions.push((partition, vec![]));
+                partitions.len() - 1
+            });
+            partitions[idx].1.push(task);
+        }
+        partitions
+    }
+
+    /// Whether a delete file applies to the data files of `partition`: global deletes, written
+    /// unpartitioned, apply to every partition, other deletes to their own partition only.
+    fn applies_to_partition(&self, task: &FileScanTask, partition: &(i32, Struct)) -> bool {
+        let metadata = &self.delete_file_metadata[&task.data_file_path];
+        metadata.partition.as_ref().is_some_and(|delete_partition| {
+            delete_partition.iter().next().is_none()
+                || (metadata.partition_spec_id == partition.0 && *delete_partition == partition.1)
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_merge_on_read_group(
+        &self,
+        data_file_table_name: String,
+        equality_delete_table_prefix: String,
+        data_files: Vec<FileScanTask>,
+        position_delete_files: Vec<FileScanTask>,
+        deletion_vectors: Vec<DeletionVector>,
+        equality_delete_files: Vec<&FileScanTask>,
+        project_names: &[String],
+    ) -> Result<MergeOnReadGroup> {
+        // one equality delete table per distinct set of equality ids, whatever their order
+        let mut tasks_by_equality_ids: BTreeMap<Vec<i32>, Vec<FileScanTask>> = BTreeMap::new();
+        for task in equality_delete_files {
+            let equality_ids = canonical_equality_ids(&task.equality_ids);
+            let mut task = task.clone();
+            // read the equality columns sorted by field id, the column order of the delete table
+            task.project_field_ids = equality_ids.clone();
+            tasks_by_equality_ids
+                .entry(equality_ids)
+                .or_default()
+                .push(task);
+        }
+        let mut equality_delete_metadatas = Vec::with_capacity(tasks_by_equality_ids.len());
+        for (table_idx, (equality_ids, tasks)) in tasks_by_equality_ids.into_iter().enumerate() {
+            // Build schema for equality delete file, equality_ids + seq_num
+            let mut metadata = EqualityDeleteMetadata::new(
+                self.build_equality_delete_schema(&equality_ids)?,
+                format!("{}_{}", equality_delete_table_prefix, table_idx),
+            );
+            for task in tasks {
+                metadata.add_file_scan_task(task);
+            }
+            equality_delete_metadatas.push(metadata);
+        }
+
+        let plan_builder = MergeOnReadPlanBuilder::new(
+            project_names.to_vec(),
+            data_file_table_name.clone(),
+            &equality_delete_metadatas,
+        );
+        Ok(MergeOnReadGroup {
+            data_file_table_name,
+            data_size: data_files.iter().map(|task| task.length).sum(),
+            data_files,
+            position_delete_files,
+            deletion_vectors,
+            equality_delete_metadatas,
+            plan_builder,
+        })
+    }
+
+    /// Builds an equality delete schema based on the given equality_ids
+    ///
+    /// The sequence number column has the same id as in the data file schema.
+    fn build_equality_delete_schema(&self, equality_ids: &[i32]) -> Result<Schema> {
         let mut equality_delete_fields = Vec::with_capacity(equality_ids.len());
         for id in equality_ids {
             let field = self
@@ -490,9 +755,8 @@ impl DataFusionTaskContextBuilder {
                 .ok_or_else(|| CompactionError::Config("equality_ids not found".to_owned()))?;
             equality_delete_fields.push(field.clone());
         }
-        *highest_field_id += 1;
         equality_delete_fields.push(Arc::new(NestedField::new(
-            *highest_field_id,
+            next_field_id(self.schema.highest_field_id(), 1)?,
             SYS_HIDDEN_SEQ_NUM,
             Type::Primitive(PrimitiveType::Long),
             true,
@@ -509,18 +773,17 @@ impl DataFusionTaskContext {
     pub fn builder() -> Result<DataFusionTaskContextBuilder> {
         Ok(DataFusionTaskContextBuilder {
             schema: Arc::new(Schema::builder().build()?),
+            partition_spec: Arc::new(PartitionSpec::unpartition_spec()),
+            sort_order: None,
+            data_file_metadata: HashMap::new(),
+            delete_file_metadata: HashMap::new(),
             data_files: vec![],
             position_delete_files: vec![],
+            deletion_vectors: vec![],
             equality_delete_files: vec![],
         })
     }
 
-    pub fn need_file_path_and_pos(&self) -> bool {
-        self.position_delete_files
-            .as_ref()
-            .is_some_and(|v| !v.is_empty())
-    }
-
     pub fn need_seq_num(&self) -> bool {
         self.equality_delete_files
             .as_ref()
@@ -528,6 +791,25 @@ impl DataFusionTaskContext {
     }
 }
 
+/// Sorts and deduplicates equality ids, as a delete on `[2, 1]` matches the same rows as one on
+/// `[1, 2]`.
+pub(crate) fn canonical_equality_ids(equality_ids: &[i32]) -> Vec<i32> {
+    let mut equality_ids = equality_ids.to_vec();
+    equality_ids.sort_unstable();
+    equality_ids.dedup();
+    equality_ids
+}
+
+/// Returns the id `offset` after `field_id`, failing instead of overflowing on malformed schemas.
+fn next_field_id(field_id: i32, offset: i32) -> Result<i32> {
+    field_id.checked_add(offset).ok_or_else(|| {
+        CompactionError::InvalidInput(format!(
+            "field id {} leaves no room for hidden columns",
+            field_id
+        ))
+    })
+}
+
 /// Metadata for equality delete files
 #[derive(Debug, Clone)]
 pub(crate) struct EqualityDeleteMetadata {
@@ -563,255 +845,588 @@ impl EqualityDeleteMetadata {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use datafusion::arrow::array::{Int64Array, RecordBatch, StringArray};
+    use datafusion::arrow::datatypes::{
+        DataType, Field, Schema as ArrowSchema, SchemaRef as ArrowSchemaRef,
+    };
+    use datafusion::datasource::MemTable;
+    use datafusion::datasource::empty::EmptyTable;
+    use datafusion::logical_expr::Join;
     use iceberg::spec::{NestedField, PrimitiveType, Schema, Type};
     use std::sync::Arc;
 
-    /// Test building SQL with no delete files
-    #[test]
-    fn test_build_merge_on_read_sql_no_deletes() {
-        let project_names = vec!["id".to_owned(), "name".to_owned()];
-        let equality_join_names = Vec::new();
-
-        let builder = SqlBuilder::new(
-            &project_names,
-            Some(POSITION_DELETE_TABLE.to_owned()),
-            Some(DATA_FILE_TABLE.to_owned()),
-            &equality_join_names,
-            false,
-        );
-        assert_eq!(
-            builder.build_merge_on_read_sql().unwrap(),
-            format!(
-                "SELECT {} FROM {}",
-                project_names.join(","),
-                DATA_FILE_TABLE
-            )
-        );
+    fn arrow_schema(fields: &[(&str, DataType)]) -> ArrowSchemaRef {
+        Arc::new(ArrowSchema::new(
+            fields
+                .iter()
+                .map(|(name, data_type)| Field::new(*name, data_type.clone(), true))
+                .collect::<Vec<_>>(),
+        ))
     }
 
-    /// Test building SQL with position delete files
-    #[test]
-    fn test_build_merge_on_read_sql_with_position_deletes() {
-        let project_names = vec!["id".to_owned(), "name".to_owned()];
-        let equality_join_names = Vec::new();
-
-        let builder = SqlBuilder::new(
-            &project_names,
-            Some(POSITION_DELETE_TABLE.to_owned()),
-            Some(DATA_FILE_TABLE.to_owned()),
-            &equality_join_names,
-            true,
-        );
-        let sql = builder.build_merge_on_read_sql().unwrap();
+    fn register_empty_table(ctx: &SessionContext, name: &str, fields: &[(&str, DataType)]) {
+        ctx.register_table(
+            TableReference::bare(name),
+            Arc::new(EmptyTable::new(arrow_schema(fields))),
+        )
+        .unwrap();
+    }
 
-        assert!(sql.contains(&format!(
-            "LEFT ANTI JOIN {POSITION_DELETE_TABLE} ON {DATA_FILE_TABLE}",
-        )));
-        assert!(sql.contains(&format!(
-            "{POSITION_DELETE_TABLE} ON {DATA_FILE_TABLE}.{SYS_HIDDEN_FILE_PATH} = {POSITION_DELETE_TABLE}.{SYS_HIDDEN_FILE_PATH} AND {DATA_FILE_TABLE}.{SYS_HIDDEN_POS} = {POSITION_DELETE_TABLE}.{SYS_HIDDEN_POS}",
+    fn equality_delete_metadata(table_name: &str, names: &[&str]) -> EqualityDeleteMetadata {
+        let mut fields = names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                Arc::new(NestedField::new(
+                    idx as i32 + 1,
+                    *name,
+                    Type::Primitive(PrimitiveType::Long),
+                    true,
+                ))
+            })
+            .collect::<Vec<_>>();
+        fields.push(Arc::new(NestedField::new(
+            names.len() as i32 + 1,
+            SYS_HIDDEN_SEQ_NUM,
+            Type::Primitive(PrimitiveType::Long),
+            true,
         )));
+        EqualityDeleteMetadata::new(
+            Schema::builder().with_fields(fields).build().unwrap(),
+            table_name.to_owned(),
+        )
     }
 
-    /// Test building SQL with equality delete files
-    #[test]
-    fn test_build_merge_on_read_sql_with_equality_deletes() {
-        let project_names = vec!["id".to_owned(), "name".to_owned()];
-        let equality_delete_table_name = "test".to_owned();
-        let equality_delete_metadatas = vec![EqualityDeleteMetadata::new(
-            Schema::builder()
-                .with_fields(vec![Arc::new(NestedField::new(
-                    1,
-                    "id",
-                    Type::Primitive(PrimitiveType::Int),
-                    true,
-                ))])
-                .build()
-                .unwrap(),
-            equality_delete_table_name.clone(),
-        )];
+    /// Collects the joins of a plan, from the outermost one
+    fn joins(plan: &LogicalPlan) -> Vec<&Join> {
+        let mut joins = vec![];
+        let mut plans = vec![plan];
+        while let Some(plan) = plans.pop() {
+            if let LogicalPlan::Join(join) = plan {
+                joins.push(join);
+            }
+            plans.extend(plan.inputs());
+        }
+        joins
+    }
 
-        let builder = SqlBuilder::new(
-            &project_names,
-            Some(POSITION_DELETE_TABLE.to_owned()),
-            Some(DATA_FILE_TABLE.to_owned()),
-            &equality_delete_metadatas,
-            false,
-        );
-        let sql = builder.build_merge_on_read_sql().unwrap();
-        assert!(sql.contains(&format!(
-            "LEFT ANTI JOIN {equality_delete_table_name} ON {DATA_FILE_TABLE}",
-        )));
-        assert!(sql.contains(&format!(
-            "{equality_delete_table_name} ON {DATA_FILE_TABLE}.id = {equality_delete_table_name}.id",
-        )));
+    fn join_keys(join: &Join) -> Vec<(String, String)> {
+        join.on
+            .iter()
+            .map(|(left, right)| (left.to_string(), right.to_string()))
+            .collect()
     }
 
-    /// Test building SQL with equality delete files AND sequence number comparison
-    #[test]
-    fn test_build_merge_on_read_sql_with_equality_deletes_and_seq_num() {
-        let project_names = vec!["id".to_owned(), "name".to_owned()];
+    fn data_fields(hidden: &[&'static str]) -> Vec<(&'static str, DataType)> {
+        let mut fields = vec![("id", DataType::Int64), ("name", DataType::Utf8)];
+        fields.extend(hidden.iter().map(|name| (*name, DataType::Int64)));
+        fields
+    }
 
-        let equality_delete_table_name = "test".to_owned();
-        let equality_delete_metadatas = vec![EqualityDeleteMetadata::new(
-            Schema::builder()
-                .with_fields(vec![Arc::new(NestedField::new(
-                    1,
-                    "id",
-                    Type::Primitive(PrimitiveType::Int),
-                    true,
-                ))])
-                .build()
-                .unwrap(),
-            equality_delete_table_name.clone(),
-        )];
+    /// Test building the plan with no delete files
+    #[tokio::test]
+    async fn test_merge_on_read_plan_no_deletes() {
+        let ctx = SessionContext::new();
+        register_empty_table(&ctx, DATA_FILE_TABLE, &data_fields(&[]));
 
-        let builder = SqlBuilder::new(
-            &project_names,
-            Some(POSITION_DELETE_TABLE.to_owned()),
-            Some(DATA_FILE_TABLE.to_owned()),
+        let plan = MergeOnReadPlanBuilder::new(
+            vec!["id".to_owned(), "name".to_owned()],
+            DATA_FILE_TABLE.to_owned(),
+            &[],
+        )
+        .build(&ctx)
+        .await
+        .unwrap();
+
+        assert!(joins(&plan).is_empty());
+        assert_eq!(
+            plan.schema()
+                .fields()
+                .iter()
+                .map(|f| f.name().as_str())
+                .collect::<Vec<_>>(),
+            vec!["id", "name"]
+        );
+    }
+
+    /// Test building the plan with several equality delete tables
+    #[tokio::test]
+    async fn test_merge_on_read_plan_with_equality_deletes() {
+        let ctx = SessionContext::new();
+        register_empty_table(&ctx, DATA_FILE_TABLE, &data_fields(&[SYS_HIDDEN_SEQ_NUM]));
+        register_empty_table(
+            &ctx,
+            "test_1",
+            &[
+                ("id", DataType::Int64),
+                (SYS_HIDDEN_SEQ_NUM, DataType::Int64),
+            ],
+        );
+        register_empty_table(
+            &ctx,
+            "test_2",
+            &[
+                ("id", DataType::Int64),
+                ("name", DataType::Utf8),
+                (SYS_HIDDEN_SEQ_NUM, DataType::Int64),
+            ],
+        );
+        let equality_delete_metadatas = vec![
+            equality_delete_metadata("test_1", &["id"]),
+            equality_delete_metadata("test_2", &["id", "name"]),
+        ];
+
+        let plan = MergeOnReadPlanBuilder::new(
+            vec!["id".to_owned(), "name".to_owned()],
+            DATA_FILE_TABLE.to_owned(),
             &equality_delete_metadatas,
-            false,
+        )
+        .build(&ctx)
+        .await
+        .unwrap();
+
+        // the outermost join is the last equality delete table
+        let joins = joins(&plan);
+        assert_eq!(joins.len(), 2);
+        assert!(
+            joins
+                .iter()
+                .all(|join| join.join_type == JoinType::LeftAnti)
+        );
+        assert_eq!(
+            join_keys(joins[0]),
+            vec![
+                (format!("{DATA_FILE_TABLE}.id"), "test_2.id".to_owned()),
+                (format!("{DATA_FILE_TABLE}.name"), "test_2.name".to_owned()),
+            ]
+        );
+        assert_eq!(
+            joins[0].filter.as_ref().unwrap().to_string(),
+            format!("{DATA_FILE_TABLE}.{SYS_HIDDEN_SEQ_NUM} < test_2.{SYS_HIDDEN_SEQ_NUM}")
+        );
+        assert_eq!(
+            join_keys(joins[1]),
+            vec![(format!("{DATA_FILE_TABLE}.id"), "test_1.id".to_owned())]
+        );
+        assert_eq!(
+            joins[1].filter.as_ref().unwrap().to_string(),
+            format!("{DATA_FILE_TABLE}.{SYS_HIDDEN_SEQ_NUM} < test_1.{SYS_HIDDEN_SEQ_NUM}")
         );
-        let sql = builder.build_merge_on_read_sql().unwrap();
-        assert!(sql.contains(&format!(
-            "{DATA_FILE_TABLE}.{SYS_HIDDEN_SEQ_NUM} < {equality_delete_table_name}.{SYS_HIDDEN_SEQ_NUM}",
-        )));
     }
 
-    /// Test building SQL with both position AND equality delete files
+    /// Column names with spaces, reserved words and mixed case are used as is
+    #[tokio::test]
+    async fn test_merge_on_read_plan_with_awkward_column_names() {
+        let ctx = SessionContext::new();
+        let data_schema = arrow_schema(&[
+            ("user id", DataType::Int64),
+            ("select", DataType::Utf8),
+            ("MixedCase", DataType::Int64),
+            (SYS_HIDDEN_SEQ_NUM, DataType::Int64),
+        ]);
+        let data = RecordBatch::try_new(
+            data_schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+                Arc::new(Int64Array::from(vec![10, 20, 30])),
+                Arc::new(Int64Array::from(vec![1, 1, 3])),
+            ],
+        )
+        .unwrap();
+        ctx.register_table(
+            TableReference::bare(DATA_FILE_TABLE),
+            Arc::new(MemTable::try_new(data_schema, vec![vec![data]]).unwrap()),
+        )
+        .unwrap();
+        let delete_schema = arrow_schema(&[
+            ("user id", DataType::Int64),
+            (SYS_HIDDEN_SEQ_NUM, DataType::Int64),
+        ]);
+        // deletes rows 1 and 3, but row 3 was written after the delete
+        let deletes = RecordBatch::try_new(
+            delete_schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 3])),
+                Arc::new(Int64Array::from(vec![2, 2])),
+            ],
+        )
+        .unwrap();
+        ctx.register_table(
+            TableReference::bare("equality_delete_table_0"),
+            Arc::new(MemTable::try_new(delete_schema, vec![vec![deletes]]).unwrap()),
+        )
+        .unwrap();
+
+        let plan = MergeOnReadPlanBuilder::new(
+            vec![
+                "user id".to_owned(),
+                "select".to_owned(),
+                "MixedCase".to_owned(),
+            ],
+            DATA_FILE_TABLE.to_owned(),
+            &[equality_delete_metadata(
+                "equality_delete_table_0",
+                &["user id"],
+            )],
+        )
+        .build(&ctx)
+        .await
+        .unwrap();
+        let batches = ctx
+            .execute_logical_plan(plan)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+
+        let mut ids = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column_by_name("user id")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, vec![2, 3]);
+        assert_eq!(batches[0].schema().field(2).name(), "MixedCase");
+    }
+
+    /// Null keys of an equality delete match null values of the data
+    #[tokio::test]
+    async fn test_merge_on_read_plan_null_equality() {
+        let ctx = SessionContext::new();
+        let data_schema = arrow_schema(&[
+            ("id", DataType::Int64),
+            (SYS_HIDDEN_SEQ_NUM, DataType::Int64),
+        ]);
+        let data = RecordBatch::try_new(
+            data_schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![Some(1), None, Some(2)])),
+                Arc::new(Int64Array::from(vec![1, 1, 1])),
+            ],
+        )
+        .unwrap();
+        ctx.register_table(
+            TableReference::bare(DATA_FILE_TABLE),
+            Arc::new(MemTable::try_new(data_schema.clone(), vec![vec![data]]).unwrap()),
+        )
+        .unwrap();
+        let deletes = RecordBatch::try_new(
+            data_schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![None, Some(2)])),
+                Arc::new(Int64Array::from(vec![2, 2])),
+            ],
+        )
+        .unwrap();
+        ctx.register_table(
+            TableReference::bare("equality_delete_table_0"),
+            Arc::new(MemTable::try_new(data_schema, vec![vec![deletes]]).unwrap()),
+        )
+        .unwrap();
+
+        let plan = MergeOnReadPlanBuilder::new(
+            vec!["id".to_owned()],
+            DATA_FILE_TABLE.to_owned(),
+            &[equality_delete_metadata("equality_delete_table_0", &["id"])],
+        )
+        .build(&ctx)
+        .await
+        .unwrap();
+        assert!(joins(&plan)[0].null_equals_null);
+        let batches = ctx
+            .execute_logical_plan(plan)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+
+        let ids = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .iter()
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![Some(1)]);
+    }
+
+    /// Equality delete files sharing a set of equality ids are read as one table
     #[test]
-    fn test_build_merge_on_read_sql_with_both_deletes() {
-        let project_names = vec!["id".to_owned(), "name".to_owned()];
-        let equality_delete_table_name = "test".to_owned();
-        let equality_delete_metadatas = vec![EqualityDeleteMetadata::new(
+    fn test_group_equality_delete_files() {
+        let schema = Arc::new(
             Schema::builder()
-                .with_fields(vec![Arc::new(NestedField::new(
-                    1,
-                    "id",
-                    Type::Primitive(PrimitiveType::Int),
-                    true,
-                ))])
+                .with_fields(vec![
+                    Arc::new(NestedField::optional(
+                        1,
+                        "id",
+                        Type::Primitive(PrimitiveType::Long),
+                    )),
+                    Arc::new(NestedField::optional(
+                        2,
+                        "name",
+                        Type::Primitive(PrimitiveType::String),
+                    )),
+                ])
                 .build()
                 .unwrap(),
-            equality_delete_table_name.clone(),
-        )];
+        );
+        let equality_delete_task = |path: &str, equality_ids: Vec<i32>| FileScanTask {
+            start: 0,
+            length: 100,
+            record_count: None,
+            data_file_path: path.to_owned(),
+            data_file_content: iceberg::spec::DataContentType::EqualityDeletes,
+            data_file_format: iceberg::spec::DataFileFormat::Parquet,
+            schema: schema.clone(),
+            project_field_ids: equality_ids.clone(),
+            predicate: None,
+            deletes: vec![],
+            sequence_number: 2,
+            equality_ids,
+            file_size_in_bytes: 100,
+        };
 
-        let builder = SqlBuilder::new(
-            &project_names,
-            Some(POSITION_DELETE_TABLE.to_owned()),
-            Some(DATA_FILE_TABLE.to_owned()),
-            &equality_delete_metadatas,
-            true,
+        let task_ctx = DataFusionTaskContext::builder()
+            .unwrap()
+            .with_schema(schema.clone())
+            .with_equality_delete_files(vec![
+                equality_delete_task("a.parquet", vec![1]),
+                equality_delete_task("b.parquet", vec![2, 1]),
+                equality_delete_task("c.parquet", vec![1]),
+                equality_delete_task("d.parquet", vec![1, 2, 2]),
+            ])
+            .build_merge_on_read()
+            .unwrap();
+
+        assert_eq!(task_ctx.merge_on_read_groups.len(), 1);
+        let metadatas = &task_ctx.merge_on_read_groups[0].equality_delete_metadatas;
+        assert_eq!(metadatas.len(), 2);
+        assert_eq!(
+            metadatas[0].equality_delete_table_name,
+            format!("{EQUALITY_DELETE_TABLE}_0")
         );
-        let sql = builder.build_merge_on_read_sql().unwrap();
-        assert!(sql.contains(&format!(
-            "LEFT ANTI JOIN {POSITION_DELETE_TABLE} ON {DATA_FILE_TABLE}"
-        )));
-        assert!(sql.contains(&format!(
-            "LEFT ANTI JOIN {equality_delete_table_name} ON {DATA_FILE_TABLE}",
-        )));
-        assert!(sql.contains(&format!(
-            "{POSITION_DELETE_TABLE} ON {DATA_FILE_TABLE}.{SYS_HIDDEN_FILE_PATH} = {POSITION_DELETE_TABLE}.{SYS_HIDDEN_FILE_PATH} AND {DATA_FILE_TABLE}.{SYS_HIDDEN_POS} = {POSITION_DELETE_TABLE}.{SYS_HIDDEN_POS}",
-        )));
-        assert!(sql.contains(&format!(
-            "{equality_delete_table_name} ON {DATA_FILE_TABLE}.id = {equality_delete_table_name}.id",
-        )));
-        assert!(sql.contains(&format!(
-            "{DATA_FILE_TABLE}.{SYS_HIDDEN_SEQ_NUM} < {equality_delete_table_name}.{SYS_HIDDEN_SEQ_NUM}",
-        )));
+        assert_eq!(metadatas[0].equality_delete_join_names(), vec!["id"]);
+        assert_eq!(metadatas[0].file_scan_tasks.len(), 2);
+        assert_eq!(
+            metadatas[1].equality_delete_join_names(),
+            vec!["id", "name"]
+        );
+        assert!(
+            metadatas[1]
+                .file_scan_tasks
+                .iter()
+                .all(|task| task.project_field_ids == vec![1, 2])
+        );
+        // the sequence number columns of the data and delete tables share their id
+        let seq_num_id = |schema: &Schema| schema.field_by_name(SYS_HIDDEN_SEQ_NUM).unwrap().id;
+        assert!(metadatas.iter().all(|metadata| {
+            seq_num_id(&metadata.equality_delete_schema)
+                == seq_num_id(task_ctx.data_file_schema.as_ref().unwrap())
+        }));
     }
 
-    /// Test building SQL with multiple equality delete files
+    /// Deletes are matched with the data files of their partition, global deletes with every
+    /// partition, deletion vectors with their data file, and deletes older than the data files are
+    /// dropped
     #[test]
-    fn test_build_merge_on_read_sql_with_multiple_equality_deletes_schema() {
-        let project_names = vec!["id".to_owned(), "name".to_owned()];
+    fn test_match_deletes_by_partition() {
+        use iceberg::spec::{DataContentType, DataFileFormat, Literal};
 
-        let equality_delete_table_name_1 = "test_1".to_owned();
-        let equality_delete_table_name_2 = "test_2".to_owned();
-        let equality_delete_metadatas = vec![
-            EqualityDeleteMetadata::new(
-                Schema::builder()
-                    .with_fields(vec![Arc::new(NestedField::new(
-                        1,
-                        "id",
-                        Type::Primitive(PrimitiveType::Int),
-                        true,
-                    ))])
-                    .build()
-                    .unwrap(),
-                equality_delete_table_name_1.clone(),
-            ),
-            EqualityDeleteMetadata::new(
-                Schema::builder()
-                    .with_fields(vec![Arc::new(NestedField::new(
+        let schema = Arc::new(
+            Schema::builder()
+                .with_fields(vec![
+                    Arc::new(NestedField::optional(
                         1,
                         "id",
-                        Type::Primitive(PrimitiveType::Int),
-                        true,
-                    ))])
-                    .build()
-                    .unwrap(),
-                equality_delete_table_name_2.clone(),
-            ),
-        ];
-
-        let builder = SqlBuilder::new(
-            &project_names,
-            Some(POSITION_DELETE_TABLE.to_owned()),
-            Some(DATA_FILE_TABLE.to_owned()),
-            &equality_delete_metadatas,
-            false,
+                        Type::Primitive(PrimitiveType::Long),
+                    )),
+                    Arc::new(NestedField::optional(
+                        2,
+                        "p",
+                        Type::Primitive(PrimitiveType::Long),
+                    )),
+                ])
+                .build()
+                .unwrap(),
         );
-        let sql = builder.build_merge_on_read_sql().unwrap();
-
-        assert!(sql.contains(
-            &("LEFT ANTI JOIN ".to_owned()
-                + &equality_delete_table_name_1
-                + " ON "
-                + DATA_FILE_TABLE)
-        ));
-        assert!(sql.contains(
-            &("LEFT ANTI JOIN ".to_owned()
-                + &equality_delete_table_name_2
-                + " ON "
-                + DATA_FILE_TABLE)
-        ));
-        assert!(sql.contains(
-            &(equality_delete_table_name_1.clone()
-                + " ON "
-                + DATA_FILE_TABLE
-                + ".id = "
-                + &equality_delete_table_name_1
-                + ".id")
-        ));
-        assert!(sql.contains(
-            &(equality_delete_table_name_2.clone()
-                + " ON "
-                + DATA_FILE_TABLE
-                + ".id = "
-                + &equality_delete_table_name_2
-                + ".id")
-        ));
-
-        // Check that the sequence number comparison is present for both equality delete tables
-        assert!(sql.contains(
-            &(DATA_FILE_TABLE.to_owned()
-                + "."
-                + SYS_HIDDEN_SEQ_NUM
-                + " < "
-                + &equality_delete_table_name_1
-                + "."
-                + SYS_HIDDEN_SEQ_NUM)
-        ));
-        assert!(sql.contains(
-            &(DATA_FILE_TABLE.to_owned()
-                + "."
-                + SYS_HIDDEN_SEQ_NUM
-                + " < "
-                + &equality_delete_table_name_2
-                + "."
-                + SYS_HIDDEN_SEQ_NUM)
-        ));
+        let task = |path: &str, content: DataContentType, sequence_number| {
+            let equality_ids = if content == DataContentType::EqualityDeletes {
+                vec![1]
+            } else {
+                vec![]
+            };
+            FileScanTask {
+                start: 0,
+                length: 100,
+                record_count: None,
+                data_file_path: path.to_owned(),
+                data_file_content: content,
+                data_file_format: DataFileFormat::Parquet,
+                schema: schema.clone(),
+                project_field_ids: vec![],
+                predicate: None,
+                deletes: vec![],
+                sequence_number,
+                equality_ids,
+                file_size_in_bytes: 100,
+            }
+        };
+        let metadata = |path: &str, partition: Option<i64>| {
+            (
+                path.to_owned(),
+                DataFileMetadata {
+                    partition_spec_id: if partition.is_some() { 1 } else { 0 },
+                    partition: Some(Struct::from_iter(partition.map(|p| Some(Literal::long(p))))),
+                    sort_order_id: None,
+                },
+            )
+        };
+        let builder = || {
+            DataFusionTaskContext::builder()
+                .unwrap()
+                .with_schema(schema.clone())
+                .with_datafile(vec![
+                    task("a.parquet", DataContentType::Data, 1),
+                    task("b.parquet", DataContentType::Data, 1),
+                    task("c.parquet", DataContentType::Data, 5),
+                ])
+                .with_equality_delete_files(vec![
+                    task("a-deletes.parquet", DataContentType::EqualityDeletes, 3),
+                    task("b-deletes.parquet", DataContentType::EqualityDeletes, 3),
+                    task(
+                        "global-deletes.parquet",
+                        DataContentType::EqualityDeletes,
+                        3,
+                    ),
+                    task("old-deletes.parquet", DataContentType::EqualityDeletes, 1),
+                ])
+                .with_position_delete_files(vec![
+                    task("old-positions.parquet", DataContentType::PositionDeletes, 0),
+                    task("b-positions.parquet", DataContentType::PositionDeletes, 1),
+                ])
+                .with_deletion_vectors(vec![DeletionVector {
+                    referenced_data_file: "a.parquet".to_owned(),
+                    puffin_file_path: "a-deletes.puffin".to_owned(),
+                    content_offset: 4,
+                    content_size_in_bytes: 10,
+                    cardinality: 1,
+                }])
+                .with_data_file_metadata(HashMap::from([
+                    metadata("a.parquet", Some(1)),
+                    metadata("b.parquet", Some(2)),
+                    metadata("c.parquet", Some(2)),
+                ]))
+        };
+        let delete_file_metadata = HashMap::from([
+            metadata("a-deletes.parquet", Some(1)),
+            metadata("b-deletes.parquet", Some(2)),
+            metadata("global-deletes.parquet", None),
+            metadata("old-deletes.parquet", Some(1)),
+            metadata("old-positions.parquet", Some(2)),
+            metadata("b-positions.parquet", Some(2)),
+        ]);
+
+        let task_ctx = builder()
+            .with_delete_file_metadata(delete_file_metadata)
+            .build_merge_on_read()
+            .unwrap();
+        assert_eq!(task_ctx.equality_delete_files.as_ref().unwrap().len(), 3);
+        let groups = &task_ctx.merge_on_read_groups;
+        assert_eq!(groups.len(), 2);
+        let group_files = |group: &MergeOnReadGroup| {
+            let data_files = group
+                .data_files
+                .iter()
+                .map(|task| task.data_file_path.as_str())
+                .collect::<Vec<_>>();
+            let mut delete_files = group
+                .equality_delete_metadatas
+                .iter()
+                .flat_map(|metadata| &metadata.file_scan_tasks)
+                .map(|task| task.data_file_path.as_str())
+                .collect::<Vec<_>>();
+            delete_files.extend(
+                group
+                    .position_delete_files
+                    .iter()
+                    .map(|task| task.data_file_path.as_str()),
+            );
+            delete_files.extend(
+                group
+                    .deletion_vectors
+                    .iter()
+                    .map(|deletion_vector| deletion_vector.puffin_file_path.as_str()),
+            );
+            delete_files.sort();
+            (data_files, delete_files)
+        };
+        assert_eq!(
+            group_files(&groups[0]),
+            (
+                vec!["a.parquet"],
+                vec![
+                    "a-deletes.parquet",
+                    "a-deletes.puffin",
+                    "global-deletes.parquet"
+                ]
+            )
+        );
+        assert_eq!(
+            group_files(&groups[1]),
+            (
+                vec!["b.parquet", "c.parquet"],
+                vec![
+                    "b-deletes.parquet",
+                    "b-positions.parquet",
+                    "global-deletes.parquet"
+                ]
+            )
+        );
+        assert_eq!(
+            groups[1].data_file_table_name,
+            format!("{DATA_FILE_TABLE}_1")
+        );
+        assert_eq!(groups[1].data_size, 200);
+
+        // without the partitions of the delete files, all files are merged by a single plan
+        let task_ctx = builder().build_merge_on_read().unwrap();
+        assert_eq!(task_ctx.merge_on_read_groups.len(), 1);
+        let group = &task_ctx.merge_on_read_groups[0];
+        assert_eq!(group.data_file_table_name, DATA_FILE_TABLE);
+        assert_eq!(group.position_delete_files.len(), 1);
+        assert_eq!(group.deletion_vectors.len(), 1);
+
+        // deletes without data files are also merged by a single plan, of no rows
+        let task_ctx = builder()
+            .with_datafile(vec![])
+            .with_data_file_metadata(HashMap::new())
+            .with_delete_file_metadata(HashMap::from([metadata("b-positions.parquet", Some(2))]))
+            .with_equality_delete_files(vec![])
+            .with_position_delete_files(vec![task(
+                "b-positions.parquet",
+                DataContentType::PositionDeletes,
+                1,
+            )])
+            .build_merge_on_read()
+            .unwrap();
+        assert_eq!(task_ctx.merge_on_read_groups.len(), 1);
+        assert!(task_ctx.merge_on_read_groups[0].data_files.is_empty());
     }
 
     #[test]
@@ -834,19 +1449,12 @@ mod tests {
             .build()
             .unwrap();
 
-        let mut highest_field_id = schema.highest_field_id();
-
-        let builder = DataFusionTaskContextBuilder {
-            schema: Arc::new(schema),
-            data_files: vec![],
-            position_delete_files: vec![],
-            equality_delete_files: vec![],
-        };
+        let builder = DataFusionTaskContext::builder()
+            .unwrap()
+            .with_schema(Arc::new(schema));
 
         let equality_ids = vec![1, 2];
-        let equality_delete_schema = builder
-            .build_equality_delete_schema(&equality_ids, &mut highest_field_id)
-            .unwrap();
+        let equality_delete_schema = builder.build_equality_delete_schema(&equality_ids).unwrap();
 
         assert_eq!(equality_delete_schema.as_struct().fields().len(), 3);
         assert_eq!(equality_delete_schema.as_struct().fields()[0].name, "id");
@@ -855,7 +1463,7 @@ mod tests {
             equality_delete_schema.as_struct().fields()[2].name,
             "sys_hidden_seq_num"
         );
-        assert_eq!(highest_field_id, 3);
+        assert_eq!(equality_delete_schema.as_struct().fields()[2].id, 3);
     }
 
     #[test]
@@ -883,12 +1491,6 @@ mod tests {
                 Type::Primitive(PrimitiveType::Long),
                 true,
             )),
-            Arc::new(NestedField::new(
-                4,
-                "sys_hidden_file_path",
-                Type::Primitive(PrimitiveType::String),
-                true,
-            )),
         ];
         let schema = Schema::builder().with_fields(fields).build().unwrap();
 
@@ -0,0 +1,292 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Writers of position delete and equality delete files, used to rewrite the deletes of
+//! merge-on-read tables, built on iceberg's delete file writers.
+
+use std::sync::Arc;
+
+use datafusion::parquet::file::properties::WriterProperties;
+use iceberg::io::FileIO;
+use iceberg::spec::{DataFile, NestedField, PrimitiveType, Schema, Struct, Type};
+use iceberg::writer::base_writer::equality_delete_writer::{
+    EqualityDeleteFileWriter, EqualityDeleteFileWriterBuilder, EqualityDeleteWriterConfig,
+};
+use iceberg::writer::base_writer::sort_position_delete_writer::{
+    PositionDeleteInput, SortPositionDeleteWriterBuilder,
+};
+use iceberg::writer::file_writer::ParquetWriterBuilder;
+use iceberg::writer::file_writer::location_generator::{
+    DefaultFileNameGenerator, DefaultLocationGenerator,
+};
+use iceberg::writer::{IcebergWriter, IcebergWriterBuilder};
+
+use super::position_delete::{FILE_PATH_FIELD_ID, POS_FIELD_ID, PositionDeleteIndex};
+use crate::error::{CompactionError, Result};
+
+/// Number of positions the position delete writer sorts in memory before writing them.
+const POSITION_DELETE_CACHE_SIZE: usize = 8192;
+
+type DeleteParquetWriterBuilder =
+    ParquetWriterBuilder<DefaultLocationGenerator, DefaultFileNameGenerator>;
+
+/// Writer of the equality delete files of a single partition.
+pub(crate) type EqualityDeleteWriter = EqualityDeleteFileWriter<DeleteParquetWriterBuilder>;
+
+/// Schema of the position delete files written, without the optional `row` column.
+pub(crate) fn position_delete_schema() -> Result<Schema> {
+    Ok(Schema::builder()
+        .with_fields(vec![
+            Arc::new(NestedField::required(
+                FILE_PATH_FIELD_ID,
+                "file_path",
+                Type::Primitive(PrimitiveType::String),
+            )),
+            Arc::new(NestedField::required(
+                POS_FIELD_ID,
+                "pos",
+                Type::Primitive(PrimitiveType::Long),
+            )),
+        ])
+        .build()?)
+}
+
+/// Schema of the equality delete files on `equality_ids`, the fields of `schema` with these ids.
+pub(crate) fn equality_delete_schema(schema: &Schema, equality_ids: &[i32]) -> Result<Schema> {
+    let fields = equality_ids
+        .iter()
+        .map(|id| {
+            schema.field_by_id(*id).cloned().ok_or_else(|| {
+                CompactionError::InvalidInput(format!("equality id {} not found in the schema", id))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Schema::builder().with_fields(fields).build()?)
+}
+
+fn parquet_writer_builder(
+    file_io: FileIO,
+    dir_path: String,
+    file_name_generator: DefaultFileNameGenerator,
+    schema: Schema,
+) -> DeleteParquetWriterBuilder {
+    ParquetWriterBuilder::new(
+        WriterProperties::default(),
+        Arc::new(schema),
+        file_io,
+        DefaultLocationGenerator { dir_path },
+        file_name_generator,
+    )
+}
+
+/// Writes the positions of `index` into position delete files of a single partition, sorted by
+/// data file path and position as the spec requires.
+pub(crate) async fn write_position_deletes(
+    file_io: FileIO,
+    dir_path: String,
+    file_name_generator: DefaultFileNameGenerator,
+    index: &PositionDeleteIndex,
+    partition: Struct,
+    partition_spec_id: i32,
+) -> Result<Vec<DataFile>> {
+    let mut writer = SortPositionDeleteWriterBuilder::new(
+        parquet_writer_builder(
+            file_io,
+            dir_path,
+            file_name_generator,
+            position_delete_schema()?,
+        ),
+        POSITION_DELETE_CACHE_SIZE,
+        Some(partition),
+        Some(partition_spec_id),
+    )
+    .build()
+    .await?;
+    for (file_path, deletes) in index.iter() {
+        for pos in deletes.iter() {
+            writer
+                .write(PositionDeleteInput {
+                    path: file_path.to_owned(),
+                    offset: pos as i64,
+                })
+                .await?;
+        }
+    }
+    Ok(writer.close().await?)
+}
+
+/// Creates a writer of equality delete files on `equality_ids` for a single partition, whose
+/// batches hold the columns of [`equality_delete_schema`] in order.
+pub(crate) async fn equality_delete_writer(
+    file_io: FileIO,
+    dir_path: String,
+    file_name_generator: DefaultFileNameGenerator,
+    schema: &Schema,
+    equality_ids: Vec<i32>,
+    partition: Struct,
+    partition_spec_id: i32,
+) -> Result<EqualityDeleteWriter> {
+    let delete_schema = equality_delete_schema(schema, &equality_ids)?;
+    // the batches already hold only the equality columns, so the writer projects them as is
+    let config = EqualityDeleteWriterConfig::new(
+        equality_ids,
+        Arc::new(delete_schema.clone()),
+        Some(partition),
+        partition_spec_id,
+    )?;
+    Ok(EqualityDeleteFileWriterBuilder::new(
+        parquet_writer_builder(file_io, dir_path, file_name_generator, delete_schema),
+        config,
+    )
+    .build()
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{AsArray, Int32Array, RecordBatch};
+    use datafusion::arrow::datatypes::{Int32Type, Int64Type};
+    use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use iceberg::io::FileIOBuilder;
+    use iceberg::spec::{DataContentType, DataFileFormat, Literal};
+    use roaring::RoaringTreemap;
+
+    use super::*;
+
+    fn file_name_generator() -> DefaultFileNameGenerator {
+        DefaultFileNameGenerator::new("10".to_owned(), None, DataFileFormat::Parquet)
+    }
+
+    async fn read_batches(file_io: &FileIO, data_file: &DataFile) -> Vec<RecordBatch> {
+        let bytes = file_io
+            .new_input(data_file.file_path())
+            .unwrap()
+            .read()
+            .await
+            .unwrap();
+        ParquetRecordBatchReaderBuilder::try_new(bytes)
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_write_position_deletes() {
+        let file_io = FileIOBuilder::new("memory").build().unwrap();
+        let index = PositionDeleteIndex::from_iter([
+            ("b.parquet".to_owned(), RoaringTreemap::from_iter([7, 1])),
+            ("a.parquet".to_owned(), RoaringTreemap::from_iter([3])),
+        ]);
+        let partition = Struct::from_iter([Some(Literal::int(4))]);
+
+        let delete_files = write_position_deletes(
+            file_io.clone(),
+            "memory:///deletes".to_owned(),
+            file_name_generator(),
+            &index,
+            partition.clone(),
+            2,
+        )
+        .await
+        .unwrap();
+        assert_eq!(delete_files.len(), 1);
+        assert_eq!(
+            delete_files[0].content_type(),
+            DataContentType::PositionDeletes
+        );
+        assert_eq!(delete_files[0].record_count(), 3);
+        assert_eq!(delete_files[0].partition(), &partition);
+
+        let batches = read_batches(&file_io, &delete_files[0]).await;
+        assert_eq!(batches.len(), 1);
+        assert_eq!(
+            batches[0]
+                .column(0)
+                .as_string::<i32>()
+                .iter()
+                .flatten()
+                .collect::<Vec<_>>(),
+            vec!["a.parquet", "b.parquet", "b.parquet"]
+        );
+        assert_eq!(
+            batches[0].column(1).as_primitive::<Int64Type>().values(),
+            &[3, 1, 7]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_equality_deletes() {
+        let file_io = FileIOBuilder::new("memory").build().unwrap();
+        let schema = Schema::builder()
+            .with_fields(vec![
+                Arc::new(NestedField::required(
+                    1,
+                    "id",
+                    Type::Primitive(PrimitiveType::Int),
+                )),
+                Arc::new(NestedField::optional(
+                    2,
+                    "name",
+                    Type::Primitive(PrimitiveType::String),
+                )),
+            ])
+            .build()
+            .unwrap();
+        let mut writer = equality_delete_writer(
+            file_io.clone(),
+            "memory:///deletes".to_owned(),
+            file_name_generator(),
+            &schema,
+            vec![1],
+            Struct::empty(),
+            0,
+        )
+        .await
+        .unwrap();
+        // batches computed by DataFusion carry no field ids
+        let batch = RecordBatch::try_new(
+            Arc::new(datafusion::arrow::datatypes::Schema::new(vec![
+                datafusion::arrow::datatypes::Field::new(
+                    "id",
+                    datafusion::arrow::datatypes::DataType::Int32,
+                    false,
+                ),
+            ])),
+            vec![Arc::new(Int32Array::from(vec![3, 5]))],
+        )
+        .unwrap();
+        writer.write(batch).await.unwrap();
+
+        let delete_files = writer.close().await.unwrap();
+        assert_eq!(delete_files.len(), 1);
+        assert_eq!(
+            delete_files[0].content_type(),
+            DataContentType::EqualityDeletes
+        );
+        assert_eq!(delete_files[0].equality_ids(), &[1]);
+        assert_eq!(delete_files[0].record_count(), 2);
+
+        let batches = read_batches(&file_io, &delete_files[0]).await;
+        assert_eq!(batches[0].num_columns(), 1);
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Int32Type>().values(),
+            &[3, 5]
+        );
+        assert!(equality_delete_schema(&schema, &[3]).is_err());
+    }
+}
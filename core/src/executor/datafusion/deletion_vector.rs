@@ -0,0 +1,275 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Deletion vectors of Iceberg v3 tables, stored as `deletion-vector-v1` blobs of Puffin files.
+
+use datafusion::error::{DataFusionError, Result as DFResult};
+use iceberg::io::{FileIO, FileRead};
+use iceberg::scan::FileScanTask;
+use iceberg_datafusion::to_datafusion_error;
+use roaring::RoaringTreemap;
+use serde_json::json;
+
+use super::position_delete::{POS_FIELD_ID, PositionDeleteIndex};
+use crate::error::{CompactionError, Result};
+use crate::executor::DeletionVector;
+
+const PUFFIN_MAGIC: [u8; 4] = [0x50, 0x46, 0x41, 0x31];
+const DELETION_VECTOR_MAGIC: [u8; 4] = [0xD1, 0xD3, 0x39, 0x64];
+const DELETION_VECTOR_BLOB_TYPE: &str = "deletion-vector-v1";
+/// Length prefix and CRC around the magic and the bitmap of a blob
+const BLOB_FRAMING_SIZE: u64 = 8;
+
+/// Serializes deleted positions into a `deletion-vector-v1` blob: the big-endian length of the
+/// magic and bitmap, the magic, the portable 64-bit roaring bitmap and the big-endian CRC-32 of
+/// the magic and bitmap.
+pub(crate) fn serialize_deletion_vector(positions: &RoaringTreemap) -> Vec<u8> {
+    let vector_size = DELETION_VECTOR_MAGIC.len() + positions.serialized_size();
+    let mut blob = Vec::with_capacity(vector_size + BLOB_FRAMING_SIZE as usize);
+    blob.extend_from_slice(&(vector_size as u32).to_be_bytes());
+    blob.extend_from_slice(&DELETION_VECTOR_MAGIC);
+    positions
+        .serialize_into(&mut blob)
+        .expect("writing to a Vec cannot fail");
+    let crc = crc32fast::hash(&blob[4..]);
+    blob.extend_from_slice(&crc.to_be_bytes());
+    blob
+}
+
+/// Reads the deleted positions of a `deletion-vector-v1` blob, checking its length, magic and
+/// checksum.
+pub(crate) fn deserialize_deletion_vector(blob: &[u8]) -> DFResult<RoaringTreemap> {
+    let invalid = |reason: &str| {
+        DataFusionError::Execution(format!("invalid deletion vector blob: {}", reason))
+    };
+    if (blob.len() as u64) < BLOB_FRAMING_SIZE + DELETION_VECTOR_MAGIC.len() as u64 {
+        return Err(invalid("blob is too short"));
+    }
+    let vector_size = u32::from_be_bytes(blob[..4].try_into().unwrap()) as usize;
+    if vector_size + BLOB_FRAMING_SIZE as usize != blob.len() {
+        return Err(invalid("length does not match the blob size"));
+    }
+    let vector = &blob[4..4 + vector_size];
+    let crc = u32::from_be_bytes(blob[4 + vector_size..].try_into().unwrap());
+    if crc32fast::hash(vector) != crc {
+        return Err(invalid("checksum mismatch"));
+    }
+    if vector[..4] != DELETION_VECTOR_MAGIC {
+        return Err(invalid("unexpected magic"));
+    }
+    RoaringTreemap::deserialize_from(&vector[4..]).map_err(|e| invalid(&e.to_string()))
+}
+
+/// Reads the blob of a deletion vector from its Puffin file.
+pub(crate) async fn read_deletion_vector(
+    file_io: &FileIO,
+    deletion_vector: &DeletionVector,
+) -> DFResult<RoaringTreemap> {
+    let reader = file_io
+        .new_input(&deletion_vector.puffin_file_path)
+        .map_err(to_datafusion_error)?
+        .reader()
+        .await
+        .map_err(to_datafusion_error)?;
+    let start = deletion_vector.content_offset;
+    let end = start
+        .checked_add(deletion_vector.content_size_in_bytes)
+        .ok_or_else(|| {
+            DataFusionError::External(Box::new(CompactionError::InvalidInput(format!(
+                "deletion vector of {} at offset {} of size {} overflows",
+                deletion_vector.referenced_data_file, start, deletion_vector.content_size_in_bytes
+            ))))
+        })?;
+    let blob = reader.read(start..end).await.map_err(to_datafusion_error)?;
+    let positions = deserialize_deletion_vector(&blob)?;
+    if positions.len() != deletion_vector.cardinality {
+        return Err(DataFusionError::Execution(format!(
+            "deletion vector of {} holds {} positions, expected {}",
+            deletion_vector.referenced_data_file,
+            positions.len(),
+            deletion_vector.cardinality
+        )));
+    }
+    Ok(positions)
+}
+
+/// Writes a Puffin file with one deletion vector per data file, returning the references to
+/// record in the table metadata.
+pub(crate) async fn write_deletion_vectors<'a>(
+    file_io: &FileIO,
+    puffin_file_path: &str,
+    deletes: impl IntoIterator<Item = (&'a str, &'a RoaringTreemap)>,
+) -> DFResult<Vec<DeletionVector>> {
+    let mut file = PUFFIN_MAGIC.to_vec();
+    let mut deletion_vectors = vec![];
+    let mut blobs_metadata = vec![];
+    for (data_file_path, positions) in deletes {
+        let blob = serialize_deletion_vector(positions);
+        let deletion_vector = DeletionVector {
+            referenced_data_file: data_file_path.to_owned(),
+            puffin_file_path: puffin_file_path.to_owned(),
+            content_offset: file.len() as u64,
+            content_size_in_bytes: blob.len() as u64,
+            cardinality: positions.len(),
+        };
+        file.extend_from_slice(&blob);
+        blobs_metadata.push(json!({
+            "type": DELETION_VECTOR_BLOB_TYPE,
+            "fields": [POS_FIELD_ID],
+            "snapshot-id": -1,
+            "sequence-number": -1,
+            "offset": deletion_vector.content_offset,
+            "length": deletion_vector.content_size_in_bytes,
+            "properties": {
+                "referenced-data-file": deletion_vector.referenced_data_file,
+                "cardinality": deletion_vector.cardinality.to_string(),
+            },
+        }));
+        deletion_vectors.push(deletion_vector);
+    }
+
+    // footer: magic, JSON payload, payload size, flags, magic
+    let payload = serde_json::to_vec(&json!({ "blobs": blobs_metadata }))
+        .map_err(|e| DataFusionError::External(Box::new(e)))?;
+    file.extend_from_slice(&PUFFIN_MAGIC);
+    file.extend_from_slice(&payload);
+    file.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    file.extend_from_slice(&[0; 4]);
+    file.extend_from_slice(&PUFFIN_MAGIC);
+
+    file_io
+        .new_output(puffin_file_path)
+        .map_err(to_datafusion_error)?
+        .write(file.into())
+        .await
+        .map_err(to_datafusion_error)?;
+    Ok(deletion_vectors)
+}
+
+/// Converts position delete files and deletion vectors into a single Puffin file holding one
+/// deletion vector per data file, e.g. to move the deletes of a table upgraded to v3.
+///
+/// The positions of a data file found in several inputs are merged into its deletion vector, as
+/// a data file can be referenced by only one.
+pub(crate) async fn convert_to_deletion_vectors(
+    file_io: &FileIO,
+    position_delete_files: Vec<FileScanTask>,
+    deletion_vectors: Vec<DeletionVector>,
+    puffin_file_path: &str,
+) -> Result<Vec<DeletionVector>> {
+    let index = PositionDeleteIndex::load(file_io, position_delete_files, deletion_vectors).await?;
+    Ok(write_deletion_vectors(file_io, puffin_file_path, index.iter()).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg::io::FileIOBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_deletion_vector_round_trip() {
+        let positions = RoaringTreemap::from_iter([0, 7, 1 << 20, (1 << 33) + 5]);
+        let blob = serialize_deletion_vector(&positions);
+        assert_eq!(
+            u32::from_be_bytes(blob[..4].try_into().unwrap()) as usize,
+            blob.len() - BLOB_FRAMING_SIZE as usize
+        );
+        assert_eq!(blob[4..8], DELETION_VECTOR_MAGIC);
+        assert_eq!(deserialize_deletion_vector(&blob).unwrap(), positions);
+
+        let mut corrupted = blob.clone();
+        corrupted[10] ^= 1;
+        assert!(deserialize_deletion_vector(&corrupted).is_err());
+        assert!(deserialize_deletion_vector(&blob[..blob.len() - 1]).is_err());
+        assert!(deserialize_deletion_vector(&[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_and_read_deletion_vectors() {
+        let file_io = FileIOBuilder::new("memory").build().unwrap();
+        let path = "memory:///deletes.puffin";
+        let a = RoaringTreemap::from_iter([1, 2, 3]);
+        let b = RoaringTreemap::from_iter([10]);
+
+        let deletion_vectors =
+            write_deletion_vectors(&file_io, path, [("a.parquet", &a), ("b.parquet", &b)])
+                .await
+                .unwrap();
+        assert_eq!(deletion_vectors.len(), 2);
+        assert_eq!(
+            deletion_vectors[0].content_offset,
+            PUFFIN_MAGIC.len() as u64
+        );
+        assert_eq!(
+            deletion_vectors[1].content_offset,
+            deletion_vectors[0].content_offset + deletion_vectors[0].content_size_in_bytes
+        );
+        assert_eq!(deletion_vectors[1].cardinality, 1);
+
+        let file = file_io.new_input(path).unwrap().read().await.unwrap();
+        assert_eq!(file[..4], PUFFIN_MAGIC);
+        assert_eq!(file[file.len() - 4..], PUFFIN_MAGIC);
+
+        assert_eq!(
+            read_deletion_vector(&file_io, &deletion_vectors[0])
+                .await
+                .unwrap(),
+            a
+        );
+        assert_eq!(
+            read_deletion_vector(&file_io, &deletion_vectors[1])
+                .await
+                .unwrap(),
+            b
+        );
+
+        // converting deletion vectors merges them by data file
+        let merged_path = "memory:///merged.puffin";
+        let mut duplicate = deletion_vectors[0].clone();
+        duplicate.referenced_data_file = "b.parquet".to_owned();
+        let merged = convert_to_deletion_vectors(
+            &file_io,
+            vec![],
+            vec![
+                deletion_vectors[0].clone(),
+                deletion_vectors[1].clone(),
+                duplicate,
+            ],
+            merged_path,
+        )
+        .await
+        .unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[1].referenced_data_file, "b.parquet");
+        assert_eq!(
+            read_deletion_vector(&file_io, &merged[1]).await.unwrap(),
+            RoaringTreemap::from_iter([1, 2, 3, 10])
+        );
+
+        let overflowing = DeletionVector {
+            content_offset: u64::MAX,
+            ..deletion_vectors[0].clone()
+        };
+        let error = read_deletion_vector(&file_io, &overflowing)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            CompactionError::from(error),
+            CompactionError::InvalidInput(_)
+        ));
+    }
+}
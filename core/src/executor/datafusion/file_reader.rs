@@ -0,0 +1,901 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::Cursor;
+use std::ops::Range;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    Array, ArrayRef, AsArray, RecordBatch, RecordBatchOptions, StructArray, new_null_array,
+};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{
+    DataType, Field, Fields, Schema as ArrowSchema, SchemaRef as ArrowSchemaRef,
+};
+use datafusion::common::apache_avro::schema::RecordSchema;
+use datafusion::common::apache_avro::{Reader as AvroFileReader, Schema as AvroSchema};
+use datafusion::datasource::avro_to_arrow::ReaderBuilder as AvroReaderBuilder;
+use datafusion::error::{DataFusionError, Result as DFResult};
+use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchStreamBuilder;
+use datafusion::parquet::arrow::async_reader::AsyncFileReader;
+use datafusion::parquet::arrow::{PARQUET_FIELD_ID_META_KEY, ProjectionMask};
+use datafusion::parquet::errors::ParquetError;
+use datafusion::parquet::file::metadata::{
+    ParquetMetaData, ParquetMetaDataReader, RowGroupMetaData,
+};
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{FutureExt, StreamExt};
+use iceberg::arrow::{ArrowReader, ArrowReaderBuilder, schema_to_arrow_schema};
+use iceberg::io::{FileIO, FileRead};
+use iceberg::scan::FileScanTask;
+use iceberg::spec::{DataContentType, DataFileFormat, NestedFieldRef, Schema, Type};
+use iceberg_datafusion::to_datafusion_error;
+
+use super::iceberg_file_task_scan::literal_to_array;
+
+const DEFAULT_BATCH_SIZE: usize = 1024;
+/// Attribute of the fields of an Avro schema holding their iceberg field id.
+const AVRO_FIELD_ID_KEY: &str = "field-id";
+
+/// Builds the Arrow reader used for Parquet files, with row group and page pruning enabled.
+pub(crate) fn build_arrow_reader(file_io: &FileIO) -> ArrowReader {
+    ArrowReaderBuilder::new(file_io.clone())
+        .with_row_group_filtering_enabled(true)
+        .with_row_selection_enabled(true)
+        .build()
+}
+
+/// Record batches read for a file scan task.
+pub(crate) struct TaskBatches {
+    /// Position in the data file of the first row read, non-zero for splits of a file
+    pub(crate) first_row_position: i64,
+    pub(crate) batches: BoxStream<'static, DFResult<RecordBatch>>,
+}
+
+/// Reads the record batches of a file scan task, dispatching on the format of the file.
+///
+/// Parquet files go through iceberg's Arrow reader, except for splits made by
+/// [`split_file_scan_task`], which only read their own row groups, and for tasks reading fields
+/// with an initial default, which must know the fields of the file. ORC and Avro files are
+/// loaded into memory as a whole and decoded. The field ids of Avro files are taken from their
+/// Avro schema, ORC files are matched by name as orc-rust does not expose the `iceberg.id`
+/// attributes of their types. Batches of data and equality delete files are
+/// conformed to the fields the task reads from the current schema, so that all formats and all
+/// schema versions of the table produce the same schema.
+pub(crate) async fn read_file_scan_task(
+    file_io: &FileIO,
+    reader: &ArrowReader,
+    task: FileScanTask,
+) -> DFResult<TaskBatches> {
+    // position delete files have their own schema
+    let is_position_delete = task.data_file_content == DataContentType::PositionDeletes;
+    if task.data_file_format == DataFileFormat::Parquet
+        && (is_split(&task)
+            || !is_position_delete && has_initial_default(task.schema.as_struct().fields()))
+    {
+        return read_parquet_range(file_io, task).await;
+    }
+    let target_schema = if is_position_delete {
+        None
+    } else {
+        Some(read_arrow_schema(&task)?)
+    };
+    let schema = task.schema.clone();
+    let batches = match task.data_file_format {
+        DataFileFormat::Parquet => {
+            let task_stream = futures::stream::iter(vec![Ok(task)]).boxed();
+            let batch_stream = reader
+                .clone()
+                .read(task_stream)
+                .await
+                .map_err(to_datafusion_error)?;
+            batch_stream
+                .map(move |batch| {
+                    conform_batch(
+                        batch.map_err(to_datafusion_error)?,
+                        target_schema.as_ref(),
+                        &schema,
+                    )
+                })
+                .boxed()
+        }
+        DataFileFormat::Orc => {
+            let bytes = read_file(file_io, &task.data_file_path).await?;
+            let reader = orc_rust::ArrowReaderBuilder::try_new(bytes)
+                .map_err(|e| DataFusionError::External(Box::new(e)))?
+                .with_batch_size(DEFAULT_BATCH_SIZE)
+                .build();
+            futures::stream::iter(
+                reader.map(move |batch| conform_batch(batch?, target_schema.as_ref(), &schema)),
+            )
+            .boxed()
+        }
+        DataFileFormat::Avro => {
+            let bytes = read_file(file_io, &task.data_file_path).await?;
+            let avro_schema = AvroFileReader::new(Cursor::new(&bytes[..]))
+                .map_err(|e| DataFusionError::External(Box::new(e)))?
+                .writer_schema()
+                .clone();
+            let reader = AvroReaderBuilder::new()
+                .read_schema()
+                .with_batch_size(DEFAULT_BATCH_SIZE)
+                .build(Cursor::new(bytes))?;
+            futures::stream::iter(reader.map(move |batch| {
+                let batch = with_avro_field_ids(batch?, &avro_schema)?;
+                conform_batch(batch, target_schema.as_ref(), &schema)
+            }))
+            .boxed()
+        }
+    };
+    Ok(TaskBatches {
+        first_row_position: 0,
+        batches,
+    })
+}
+
+/// Splits a Parquet data file larger than `target_split_size` bytes into tasks covering
+/// consecutive row groups.
+///
+/// A row group belongs to the split whose byte range contains the start of the row group, so
+/// the splits of a file read every row group exactly once. Other tasks are returned unchanged.
+pub(crate) async fn split_file_scan_task(
+    file_io: &FileIO,
+    task: FileScanTask,
+    target_split_size: u64,
+) -> DFResult<Vec<FileScanTask>> {
+    if task.data_file_content != DataContentType::Data
+        || task.data_file_format != DataFileFormat::Parquet
+        || task.length <= target_split_size
+    {
+        return Ok(vec![task]);
+    }
+    let mut reader = ParquetFileReader::open(file_io, &task.data_file_path).await?;
+    let metadata = reader.get_metadata().await?;
+    let row_groups = metadata
+        .row_groups()
+        .iter()
+        .map(|row_group| {
+            (
+                row_group_offset(row_group),
+                row_group.compressed_size() as u64,
+                row_group.num_rows() as u64,
+            )
+        })
+        .collect::<Vec<_>>();
+    let ranges = split_row_groups(&row_groups, reader.file_size, target_split_size);
+    if ranges.len() <= 1 {
+        return Ok(vec![task]);
+    }
+    Ok(ranges
+        .into_iter()
+        .map(|(range, record_count)| FileScanTask {
+            start: range.start,
+            length: range.end - range.start,
+            record_count: Some(record_count),
+            file_size_in_bytes: reader.file_size,
+            ..task.clone()
+        })
+        .collect())
+}
+
+/// Groups row groups, given as `(offset, compressed size, number of rows)`, into byte ranges
+/// of at least `target_split_size` bytes. Returns each range with its number of rows; the
+/// ranges cover the file from the first row group to the end.
+fn split_row_groups(
+    row_groups: &[(u64, u64, u64)],
+    file_size: u64,
+    target_split_size: u64,
+) -> Vec<(Range<u64>, u64)> {
+    let mut splits: Vec<(Range<u64>, u64)> = vec![];
+    let mut split_size = 0;
+    for &(offset, compressed_size, num_rows) in row_groups {
+        if splits.is_empty() || split_size >= target_split_size {
+            if let Some((range, _)) = splits.last_mut() {
+                range.end = offset;
+            }
+            splits.push((offset..file_size, 0));
+            split_size = 0;
+        }
+        if let Some((_, record_count)) = splits.last_mut() {
+            *record_count += num_rows;
+        }
+        split_size += compressed_size;
+    }
+    splits
+}
+
+/// Offset of the first page of a row group, which decides the split it belongs to.
+pub(crate) fn row_group_offset(row_group: &RowGroupMetaData) -> u64 {
+    row_group.column(0).byte_range().0
+}
+
+/// Reads the footer of a Parquet file.
+pub(crate) async fn read_parquet_metadata(
+    file_io: &FileIO,
+    path: &str,
+) -> DFResult<Arc<ParquetMetaData>> {
+    ParquetFileReader::open(file_io, path)
+        .await?
+        .get_metadata()
+        .await
+        .map_err(DataFusionError::from)
+}
+
+/// Whether `task` covers only part of its file.
+fn is_split(task: &FileScanTask) -> bool {
+    task.start > 0 || task.length < task.file_size_in_bytes
+}
+
+/// Reads the row groups of a Parquet file, or of a split made by [`split_file_scan_task`], with
+/// parquet's reader.
+///
+/// Row groups are selected by their offset and columns by field id. The predicate of the task
+/// is not applied, DataFusion still filters the rows.
+async fn read_parquet_range(file_io: &FileIO, task: FileScanTask) -> DFResult<TaskBatches> {
+    let reader = ParquetFileReader::open(file_io, &task.data_file_path).await?;
+    let builder = ParquetRecordBatchStreamBuilder::new(reader).await?;
+    let split = if is_split(&task) {
+        task.start..task.start.saturating_add(task.length)
+    } else {
+        0..u64::MAX
+    };
+    let mut first_row_position = 0;
+    let mut row_groups = vec![];
+    for (idx, row_group) in builder.metadata().row_groups().iter().enumerate() {
+        let offset = row_group_offset(row_group);
+        if split.contains(&offset) {
+            row_groups.push(idx);
+        } else if offset < split.start {
+            first_row_position += row_group.num_rows();
+        }
+    }
+
+    let target_schema = read_arrow_schema(&task)?;
+    let projection = if task.project_field_ids.is_empty() {
+        ProjectionMask::all()
+    } else {
+        let field_ids = task
+            .project_field_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        let roots = builder
+            .schema()
+            .fields()
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| {
+                field
+                    .metadata()
+                    .get(PARQUET_FIELD_ID_META_KEY)
+                    .is_some_and(|id| field_ids.contains(id))
+            })
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+        ProjectionMask::roots(builder.parquet_schema(), roots)
+    };
+    let stream = builder
+        .with_row_groups(row_groups)
+        .with_projection(projection)
+        .with_batch_size(DEFAULT_BATCH_SIZE)
+        .build()?;
+    Ok(TaskBatches {
+        first_row_position,
+        batches: stream
+            .map(move |batch| conform_batch_to_schema(batch?, &target_schema, &task.schema))
+            .boxed(),
+    })
+}
+
+/// Adapts an iceberg [`FileRead`] to parquet's [`AsyncFileReader`].
+struct ParquetFileReader {
+    reader: Box<dyn FileRead>,
+    file_size: u64,
+}
+
+impl ParquetFileReader {
+    async fn open(file_io: &FileIO, path: &str) -> DFResult<Self> {
+        let input = file_io.new_input(path).map_err(to_datafusion_error)?;
+        let file_size = input.metadata().await.map_err(to_datafusion_error)?.size;
+        let reader = input.reader().await.map_err(to_datafusion_error)?;
+        Ok(Self {
+            reader: Box::new(reader),
+            file_size,
+        })
+    }
+}
+
+impl AsyncFileReader for ParquetFileReader {
+    fn get_bytes(
+        &mut self,
+        range: Range<usize>,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<bytes::Bytes>> {
+        async move {
+            self.reader
+                .read(range.start as u64..range.end as u64)
+                .await
+                .map_err(|e| ParquetError::External(Box::new(e)))
+        }
+        .boxed()
+    }
+
+    fn get_metadata(
+        &mut self,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Arc<ParquetMetaData>>> {
+        async move {
+            let file_size = self.file_size as usize;
+            let metadata = ParquetMetaDataReader::new()
+                .load_and_finish(self, file_size)
+                .await?;
+            Ok(Arc::new(metadata))
+        }
+        .boxed()
+    }
+}
+
+async fn read_file(file_io: &FileIO, path: &str) -> DFResult<bytes::Bytes> {
+    file_io
+        .new_input(path)
+        .map_err(to_datafusion_error)?
+        .read()
+        .await
+        .map_err(to_datafusion_error)
+}
+
+/// Attaches the field ids of the Avro schema of a file onto the fields of `batch`, read from
+/// it, which the Avro reader leaves out of the Arrow schema. Its columns are then matched by
+/// field id like those of Parquet files.
+fn with_avro_field_ids(batch: RecordBatch, avro_schema: &AvroSchema) -> DFResult<RecordBatch> {
+    let Some(record) = avro_record(avro_schema) else {
+        return Ok(batch);
+    };
+    let (fields, columns) = avro_field_ids(batch.schema().fields(), batch.columns(), record)?;
+    Ok(RecordBatch::try_new_with_options(
+        Arc::new(ArrowSchema::new_with_metadata(
+            fields,
+            batch.schema().metadata().clone(),
+        )),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+    )?)
+}
+
+fn avro_field_ids(
+    fields: &Fields,
+    columns: &[ArrayRef],
+    record: &RecordSchema,
+) -> DFResult<(Fields, Vec<ArrayRef>)> {
+    let mut fields_with_ids = Vec::with_capacity(fields.len());
+    let mut columns_with_ids = Vec::with_capacity(columns.len());
+    for (field, column) in fields.iter().zip(columns) {
+        let Some(avro_field) = record.fields.iter().find(|f| f.name == *field.name()) else {
+            fields_with_ids.push(field.clone());
+            columns_with_ids.push(column.clone());
+            continue;
+        };
+        let nested = match (field.data_type(), avro_record(&avro_field.schema)) {
+            (DataType::Struct(children), Some(nested)) => {
+                let column = column.as_struct();
+                let (children, child_columns) = avro_field_ids(children, column.columns(), nested)?;
+                let column =
+                    StructArray::try_new(children.clone(), child_columns, column.nulls().cloned())?;
+                Some((DataType::Struct(children), Arc::new(column) as ArrayRef))
+            }
+            _ => None,
+        };
+        let (data_type, column) =
+            nested.unwrap_or_else(|| (field.data_type().clone(), column.clone()));
+        let mut metadata = field.metadata().clone();
+        if let Some(field_id) = avro_field
+            .custom_attributes
+            .get(AVRO_FIELD_ID_KEY)
+            .and_then(|field_id| field_id.as_i64())
+        {
+            metadata.insert(PARQUET_FIELD_ID_META_KEY.to_owned(), field_id.to_string());
+        }
+        fields_with_ids.push(Arc::new(
+            field
+                .as_ref()
+                .clone()
+                .with_data_type(data_type)
+                .with_metadata(metadata),
+        ));
+        columns_with_ids.push(column);
+    }
+    Ok((Fields::from(fields_with_ids), columns_with_ids))
+}
+
+/// Record of an Avro schema, looking into the union of an optional record.
+fn avro_record(avro_schema: &AvroSchema) -> Option<&RecordSchema> {
+    match avro_schema {
+        AvroSchema::Record(record) => Some(record),
+        AvroSchema::Union(union) => union.variants().iter().find_map(avro_record),
+        _ => None,
+    }
+}
+
+/// Arrow schema of the fields projected by `task`, or of its whole schema if the task has no
+/// projection.
+fn read_arrow_schema(task: &FileScanTask) -> DFResult<ArrowSchemaRef> {
+    if task.project_field_ids.is_empty() {
+        return Ok(Arc::new(
+            schema_to_arrow_schema(&task.schema).map_err(to_datafusion_error)?,
+        ));
+    }
+    let fields = task
+        .project_field_ids
+        .iter()
+        .map(|id| {
+            task.schema.field_by_id(*id).cloned().ok_or_else(|| {
+                DataFusionError::Plan(format!(
+                    "projected field id {} not found in schema of {}",
+                    id, task.data_file_path
+                ))
+            })
+        })
+        .collect::<DFResult<Vec<_>>>()?;
+    let schema = Schema::builder()
+        .with_fields(fields)
+        .build()
+        .map_err(to_datafusion_error)?;
+    Ok(Arc::new(
+        schema_to_arrow_schema(&schema).map_err(to_datafusion_error)?,
+    ))
+}
+
+fn conform_batch(
+    batch: RecordBatch,
+    target_schema: Option<&ArrowSchemaRef>,
+    schema: &Schema,
+) -> DFResult<RecordBatch> {
+    match target_schema {
+        Some(target_schema) => conform_batch_to_schema(batch, target_schema, schema),
+        None => Ok(batch),
+    }
+}
+
+/// Reorders, casts and fills the columns of `batch` so that it matches `target_schema`, the
+/// columns of `schema` the task reads.
+///
+/// Columns are matched by field id when the file carries field ids, so renamed columns are
+/// found and a dropped column is not mistaken for a new one of the same name. Files without
+/// field ids are matched by name. Fields missing from the file take their initial default from
+/// `schema`, or null if they have none. Promoted types are cast, and nested structs are
+/// conformed the same way.
+pub(crate) fn conform_batch_to_schema(
+    batch: RecordBatch,
+    target_schema: &ArrowSchemaRef,
+    schema: &Schema,
+) -> DFResult<RecordBatch> {
+    let columns = conform_columns(
+        batch.schema().fields(),
+        batch.columns(),
+        target_schema.fields(),
+        batch.num_rows(),
+        schema,
+    )?;
+    Ok(RecordBatch::try_new_with_options(
+        target_schema.clone(),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+    )?)
+}
+
+fn conform_columns(
+    source_fields: &Fields,
+    source_columns: &[ArrayRef],
+    target_fields: &Fields,
+    num_rows: usize,
+    schema: &Schema,
+) -> DFResult<Vec<ArrayRef>> {
+    let by_id = source_fields
+        .iter()
+        .any(|field| field.metadata().contains_key(PARQUET_FIELD_ID_META_KEY));
+    target_fields
+        .iter()
+        .map(|field| match find_column(source_fields, field, by_id) {
+            Some(idx) => conform_column(&source_columns[idx], field, schema),
+            None => missing_column(field, num_rows, schema),
+        })
+        .collect()
+}
+
+fn conform_column(column: &ArrayRef, field: &Field, schema: &Schema) -> DFResult<ArrayRef> {
+    match (column.data_type(), field.data_type()) {
+        (source, target) if source == target => Ok(column.clone()),
+        (DataType::Struct(source_fields), DataType::Struct(target_fields)) => {
+            let column = column.as_struct();
+            let columns = conform_columns(
+                source_fields,
+                column.columns(),
+                target_fields,
+                column.len(),
+                schema,
+            )?;
+            Ok(Arc::new(StructArray::try_new(
+                target_fields.clone(),
+                columns,
+                column.nulls().cloned(),
+            )?))
+        }
+        _ => Ok(cast(column, field.data_type())?),
+    }
+}
+
+/// Column of a field the file does not have, filled with its initial default or with nulls.
+fn missing_column(field: &Field, num_rows: usize, schema: &Schema) -> DFResult<ArrayRef> {
+    let initial_default = field
+        .metadata()
+        .get(PARQUET_FIELD_ID_META_KEY)
+        .and_then(|id| id.parse::<i32>().ok())
+        .and_then(|id| schema.field_by_id(id))
+        .and_then(|field| field.initial_default.clone());
+    match initial_default {
+        Some(initial_default) => {
+            literal_to_array(Some(initial_default), field.data_type(), num_rows)
+        }
+        None if field.is_nullable() => Ok(new_null_array(field.data_type(), num_rows)),
+        None => Err(DataFusionError::Execution(format!(
+            "required column {} is missing from the data file",
+            field.name()
+        ))),
+    }
+}
+
+fn find_column(fields: &Fields, field: &Field, by_id: bool) -> Option<usize> {
+    if by_id {
+        let field_id = field.metadata().get(PARQUET_FIELD_ID_META_KEY)?;
+        fields
+            .iter()
+            .position(|f| f.metadata().get(PARQUET_FIELD_ID_META_KEY) == Some(field_id))
+    } else {
+        fields.iter().position(|f| f.name() == field.name())
+    }
+}
+
+/// Whether a field of `fields`, or of their nested structs, has an initial default.
+fn has_initial_default(fields: &[NestedFieldRef]) -> bool {
+    fields.iter().any(|field| {
+        field.initial_default.is_some()
+            || matches!(&*field.field_type, Type::Struct(nested) if has_initial_default(nested.fields()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int32Array, Int64Array, StringArray};
+    use datafusion::arrow::datatypes::Int64Type;
+    use iceberg::spec::{Literal, NestedField, PrimitiveType};
+    use std::collections::HashMap;
+
+    fn field_with_id(name: &str, data_type: DataType, nullable: bool, id: i32) -> Field {
+        Field::new(name, data_type, nullable).with_metadata(HashMap::from([(
+            PARQUET_FIELD_ID_META_KEY.to_owned(),
+            id.to_string(),
+        )]))
+    }
+
+    fn empty_schema() -> Schema {
+        Schema::builder().build().unwrap()
+    }
+
+    #[test]
+    fn test_conform_batch_to_schema() {
+        // ORC files are read without field ids, in file order
+        let source = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("name", DataType::Utf8, true),
+                Field::new("id", DataType::Int32, false),
+            ])),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(Int32Array::from(vec![1, 2])),
+            ],
+        )
+        .unwrap();
+        let target = Arc::new(ArrowSchema::new(vec![
+            field_with_id("id", DataType::Int64, false, 1),
+            field_with_id("name", DataType::Utf8, true, 2),
+            field_with_id("added", DataType::Utf8, true, 3),
+        ]));
+
+        let batch = conform_batch_to_schema(source, &target, &empty_schema()).unwrap();
+        assert_eq!(batch.schema(), target);
+        assert_eq!(
+            batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .values(),
+            &[1, 2]
+        );
+        assert_eq!(batch.column(2).null_count(), 2);
+    }
+
+    #[test]
+    fn test_conform_batch_to_schema_by_field_id() {
+        let source = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![field_with_id(
+                "old_name",
+                DataType::Int64,
+                false,
+                1,
+            )])),
+            vec![Arc::new(Int64Array::from(vec![7]))],
+        )
+        .unwrap();
+        let target = Arc::new(ArrowSchema::new(vec![field_with_id(
+            "new_name",
+            DataType::Int64,
+            false,
+            1,
+        )]));
+
+        let batch = conform_batch_to_schema(source, &target, &empty_schema()).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(batch.schema().field(0).name(), "new_name");
+    }
+
+    #[test]
+    fn test_conform_batch_missing_required_column() {
+        let source = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "id",
+                DataType::Int64,
+                false,
+            )])),
+            vec![Arc::new(Int64Array::from(vec![1]))],
+        )
+        .unwrap();
+        let target = Arc::new(ArrowSchema::new(vec![Field::new(
+            "name",
+            DataType::Utf8,
+            false,
+        )]));
+
+        assert!(conform_batch_to_schema(source, &target, &empty_schema()).is_err());
+    }
+
+    #[test]
+    fn test_conform_batch_does_not_match_dropped_column_by_name() {
+        // `name` was dropped and a new column with the same name added
+        let source = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                field_with_id("id", DataType::Int64, false, 1),
+                field_with_id("name", DataType::Utf8, true, 2),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![1])),
+                Arc::new(StringArray::from(vec!["old"])),
+            ],
+        )
+        .unwrap();
+        let target = Arc::new(ArrowSchema::new(vec![
+            field_with_id("id", DataType::Int64, false, 1),
+            field_with_id("name", DataType::Utf8, true, 3),
+        ]));
+
+        let batch = conform_batch_to_schema(source, &target, &empty_schema()).unwrap();
+        assert_eq!(batch.column(1).null_count(), 1);
+    }
+
+    #[test]
+    fn test_conform_batch_fills_initial_default() {
+        let schema = Schema::builder()
+            .with_fields(vec![
+                Arc::new(NestedField::required(
+                    1,
+                    "id",
+                    Type::Primitive(PrimitiveType::Long),
+                )),
+                Arc::new(
+                    NestedField::required(2, "added", Type::Primitive(PrimitiveType::Long))
+                        .with_initial_default(Literal::long(42)),
+                ),
+            ])
+            .build()
+            .unwrap();
+        // written before `added`, with `id` as an int since promoted to long
+        let source = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![field_with_id(
+                "id",
+                DataType::Int32,
+                false,
+                1,
+            )])),
+            vec![Arc::new(Int32Array::from(vec![1, 2]))],
+        )
+        .unwrap();
+        let target = Arc::new(schema_to_arrow_schema(&schema).unwrap());
+
+        let batch = conform_batch_to_schema(source, &target, &schema).unwrap();
+        assert_eq!(batch.schema(), target);
+        assert_eq!(
+            batch.column(0).as_primitive::<Int64Type>().values(),
+            &[1, 2]
+        );
+        assert_eq!(
+            batch.column(1).as_primitive::<Int64Type>().values(),
+            &[42, 42]
+        );
+        assert!(has_initial_default(schema.as_struct().fields()));
+        assert!(!has_initial_default(empty_schema().as_struct().fields()));
+    }
+
+    #[test]
+    fn test_conform_batch_nested_struct() {
+        let source_fields = Fields::from(vec![field_with_id("a", DataType::Int32, true, 2)]);
+        let source = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![field_with_id(
+                "s",
+                DataType::Struct(source_fields.clone()),
+                true,
+                1,
+            )])),
+            vec![Arc::new(StructArray::new(
+                source_fields,
+                vec![Arc::new(Int32Array::from(vec![Some(1), None]))],
+                None,
+            ))],
+        )
+        .unwrap();
+        let target_fields = Fields::from(vec![
+            field_with_id("a", DataType::Int64, true, 2),
+            field_with_id("b", DataType::Utf8, true, 3),
+        ]);
+        let target = Arc::new(ArrowSchema::new(vec![field_with_id(
+            "s",
+            DataType::Struct(target_fields),
+            true,
+            1,
+        )]));
+
+        let batch = conform_batch_to_schema(source, &target, &empty_schema()).unwrap();
+        assert_eq!(batch.schema(), target);
+        let column = batch.column(0).as_struct();
+        assert_eq!(column.column(0).as_primitive::<Int64Type>().value(0), 1);
+        assert_eq!(column.column(1).null_count(), 2);
+    }
+
+    #[test]
+    fn test_with_avro_field_ids() {
+        let avro_schema = AvroSchema::parse_str(
+            r#"{
+                "type": "record",
+                "name": "table",
+                "fields": [
+                    {"name": "old_name", "type": "long", "field-id": 1},
+                    {"name": "dropped", "type": ["null", "string"], "field-id": 2},
+                    {"name": "nested", "type": ["null", {
+                        "type": "record",
+                        "name": "r4",
+                        "fields": [{"name": "a", "type": "int", "field-id": 5}]
+                    }], "field-id": 4}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let nested_fields = Fields::from(vec![Field::new("a", DataType::Int32, false)]);
+        let source = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("old_name", DataType::Int64, false),
+                Field::new("dropped", DataType::Utf8, true),
+                Field::new("nested", DataType::Struct(nested_fields.clone()), true),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(StructArray::new(
+                    nested_fields,
+                    vec![Arc::new(Int32Array::from(vec![3, 4]))],
+                    None,
+                )),
+            ],
+        )
+        .unwrap();
+        // the column is renamed, and `dropped` is dropped and re-added with a new id
+        let target = Arc::new(ArrowSchema::new(vec![
+            field_with_id("id", DataType::Int64, false, 1),
+            field_with_id("dropped", DataType::Utf8, true, 3),
+            field_with_id(
+                "nested",
+                DataType::Struct(Fields::from(vec![field_with_id(
+                    "a",
+                    DataType::Int64,
+                    true,
+                    5,
+                )])),
+                true,
+            ),
+        ]));
+
+        let source = with_avro_field_ids(source, &avro_schema).unwrap();
+        let batch = conform_batch_to_schema(source, &target, &empty_schema()).unwrap();
+        assert_eq!(batch.schema(), target);
+        assert_eq!(
+            batch.column(0).as_primitive::<Int64Type>().values(),
+            &[1, 2]
+        );
+        assert_eq!(batch.column(1).null_count(), 2);
+        assert_eq!(
+            batch
+                .column(2)
+                .as_struct()
+                .column(0)
+                .as_primitive::<Int64Type>()
+                .values(),
+            &[3, 4]
+        );
+    }
+
+    #[test]
+    fn test_split_row_groups() {
+        let row_groups = [(4, 100, 10), (104, 100, 20), (204, 50, 30), (254, 100, 40)];
+
+        let splits = split_row_groups(&row_groups, 400, 150);
+        assert_eq!(splits, vec![(4..204, 30), (204..400, 70)]);
+
+        // every row group is a split once it alone reaches the target size
+        let splits = split_row_groups(&row_groups, 400, 1);
+        assert_eq!(
+            splits,
+            vec![(4..104, 10), (104..204, 20), (204..254, 30), (254..400, 40)]
+        );
+
+        let splits = split_row_groups(&row_groups, 400, 1000);
+        assert_eq!(splits, vec![(4..400, 100)]);
+
+        assert!(split_row_groups(&[], 8, 1).is_empty());
+    }
+
+    #[test]
+    fn test_is_split() {
+        let schema = Arc::new(Schema::builder().build().unwrap());
+        let task = FileScanTask {
+            start: 0,
+            length: 400,
+            record_count: None,
+            data_file_path: "a.parquet".to_owned(),
+            data_file_content: DataContentType::Data,
+            data_file_format: DataFileFormat::Parquet,
+            schema,
+            project_field_ids: vec![],
+            predicate: None,
+            deletes: vec![],
+            sequence_number: 0,
+            equality_ids: vec![],
+            file_size_in_bytes: 400,
+        };
+        assert!(!is_split(&task));
+        // the size of the file is unknown for tasks decoded from a request
+        assert!(!is_split(&FileScanTask {
+            file_size_in_bytes: 0,
+            ..task.clone()
+        }));
+        assert!(is_split(&FileScanTask {
+            length: 200,
+            ..task.clone()
+        }));
+        assert!(is_split(&FileScanTask {
+            start: 204,
+            length: 196,
+            ..task
+        }));
+    }
+}
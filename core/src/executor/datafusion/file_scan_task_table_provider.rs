@@ -24,10 +24,13 @@ use datafusion::datasource::{TableProvider, TableType};
 use datafusion::error::Result as DFResult;
 use datafusion::logical_expr::{Expr, TableProviderFilterPushDown};
 use datafusion::physical_plan::ExecutionPlan;
+use futures::future::try_join_all;
 use iceberg::io::FileIO;
 use iceberg::scan::FileScanTask;
 
-use super::iceberg_file_task_scan::IcebergFileTaskScan;
+use super::file_reader::split_file_scan_task;
+use super::iceberg_file_task_scan::{DataFileLayout, IcebergFileTaskScan};
+use super::position_delete::PositionDeleteIndex;
 
 /// A table provider for iceberg file scan tasks
 #[derive(Debug, Clone)]
@@ -36,25 +39,35 @@ pub struct IcebergFileScanTaskTableProvider {
     schema: ArrowSchemaRef,
     file_io: FileIO,
     need_seq_num: bool,
-    need_file_path_and_pos: bool,
+    position_deletes: Option<Arc<PositionDeleteIndex>>,
     batch_parallelism: usize,
+    file_prefetch_depth: usize,
+    target_split_size: u64,
+    layout: DataFileLayout,
 }
 impl IcebergFileScanTaskTableProvider {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         file_scan_tasks: Vec<FileScanTask>,
         schema: ArrowSchemaRef,
         file_io: FileIO,
         need_seq_num: bool,
-        need_file_path_and_pos: bool,
+        position_deletes: Option<Arc<PositionDeleteIndex>>,
         batch_parallelism: usize,
+        file_prefetch_depth: usize,
+        target_split_size: u64,
+        layout: DataFileLayout,
     ) -> Self {
         Self {
             file_scan_tasks,
             schema,
             file_io,
             need_seq_num,
-            need_file_path_and_pos,
+            position_deletes,
             batch_parallelism,
+            file_prefetch_depth,
+            target_split_size,
+            layout,
         }
     }
 }
@@ -75,7 +88,9 @@ impl TableProvider for IcebergFileScanTaskTableProvider {
     /// Scans the iceberg file scan tasks
     ///
     /// This function creates an execution plan for scanning the iceberg file scan tasks.
-    /// It uses the IcebergFileTaskScan struct to create the execution plan.    
+    /// It uses the IcebergFileTaskScan struct to create the execution plan.
+    /// Large Parquet data files are split at row group boundaries first, so that a single file
+    /// can be spread over several partitions.
     async fn scan(
         &self,
         _state: &dyn Session,
@@ -83,16 +98,26 @@ impl TableProvider for IcebergFileScanTaskTableProvider {
         filters: &[Expr],
         _limit: Option<usize>,
     ) -> DFResult<Arc<dyn ExecutionPlan>> {
+        let file_scan_tasks =
+            try_join_all(self.file_scan_tasks.iter().map(|task| {
+                split_file_scan_task(&self.file_io, task.clone(), self.target_split_size)
+            }))
+            .await?
+            .into_iter()
+            .flatten()
+            .collect();
         Ok(Arc::new(IcebergFileTaskScan::new(
-            self.file_scan_tasks.clone(),
+            file_scan_tasks,
             self.schema.clone(),
             projection,
             filters,
             &self.file_io,
             self.need_seq_num,
-            self.need_file_path_and_pos,
+            self.position_deletes.clone(),
             self.batch_parallelism,
-        )))
+            self.file_prefetch_depth,
+            &self.layout,
+        )?))
     }
 
     fn supports_filters_pushdown(
@@ -15,30 +15,50 @@
  */
 
 use std::any::Any;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::vec;
 
 use async_stream::try_stream;
-use datafusion::arrow::array::{Int64Array, RecordBatch, StringArray};
-use datafusion::arrow::datatypes::{Field, Schema, SchemaRef as ArrowSchemaRef};
-use datafusion::error::Result as DFResult;
+use datafusion::arrow::array::{ArrayRef, Int64Array, RecordBatch, RecordBatchOptions};
+use datafusion::arrow::compute::{SortOptions, cast};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef as ArrowSchemaRef};
+use datafusion::common::ScalarValue;
+use datafusion::common::hash_utils::create_hashes;
+use datafusion::common::runtime::SpawnedTask;
+use datafusion::error::{DataFusionError, Result as DFResult};
 use datafusion::execution::{SendableRecordBatchStream, TaskContext};
-use datafusion::physical_expr::EquivalenceProperties;
+use datafusion::parquet::arrow::PARQUET_FIELD_ID_META_KEY;
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_expr::{
+    EquivalenceProperties, LexOrdering, PhysicalExpr, PhysicalSortExpr,
+};
 use datafusion::physical_plan::execution_plan::{Boundedness, EmissionType};
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::physical_plan::{DisplayAs, ExecutionPlan, Partitioning, PlanProperties};
 use datafusion::prelude::Expr;
 use futures::{Stream, StreamExt, TryStreamExt};
-use iceberg::arrow::ArrowReaderBuilder;
-use iceberg::expr::Predicate;
+use iceberg::arrow::ArrowReader;
+use iceberg::expr::{Bind, Predicate};
 use iceberg::io::FileIO;
 use iceberg::scan::FileScanTask;
+use iceberg::spec::{
+    DataContentType, DataFileFormat, Literal, NullOrder, PartitionSpec, PrimitiveLiteral,
+    SortDirection, SortOrderRef, Transform,
+};
 use iceberg_datafusion::physical_plan::expr_to_predicate::convert_filters_to_predicate;
-use iceberg_datafusion::to_datafusion_error;
+use tokio::sync::{mpsc, oneshot};
 
-use super::datafusion_processor::SYS_HIDDEN_SEQ_NUM;
+use crate::executor::DataFileMetadata;
+
+use super::datafusion_processor::{SYS_HIDDEN_COLS, SYS_HIDDEN_SEQ_NUM};
+use super::file_reader::{build_arrow_reader, read_file_scan_task};
+use super::partition_key::partition_field_expr;
+use super::position_delete::{PositionDeleteIndex, remove_deleted_rows};
+
+/// Number of batches buffered per prefetched file.
+const PREFETCH_BATCHES: usize = 2;
 
 /// An execution plan for scanning iceberg file scan tasks
 #[derive(Debug)]
@@ -46,10 +66,14 @@ pub(crate) struct IcebergFileTaskScan {
     file_scan_tasks_group: Vec<Vec<FileScanTask>>,
     plan_properties: PlanProperties,
     projection: Option<Vec<String>>,
+    /// Field ids read from the files, `None` to read all fields of the task
+    project_field_ids: Option<Vec<i32>>,
     predicates: Option<Predicate>,
     file_io: FileIO,
     need_seq_num: bool,
-    need_file_path_and_pos: bool,
+    /// Deleted rows of the data files, dropped while scanning
+    position_deletes: Option<Arc<PositionDeleteIndex>>,
+    file_prefetch_depth: usize,
 }
 
 impl IcebergFileTaskScan {
@@ -61,44 +85,267 @@ impl IcebergFileTaskScan {
         filters: &[Expr],
         file_io: &FileIO,
         need_seq_num: bool,
-        need_file_path_and_pos: bool,
+        position_deletes: Option<Arc<PositionDeleteIndex>>,
         batch_parallelism: usize,
-    ) -> Self {
+        file_prefetch_depth: usize,
+        layout: &DataFileLayout,
+    ) -> DFResult<Self> {
+        if batch_parallelism == 0 {
+            return Err(DataFusionError::Configuration(
+                "batch_parallelism must be greater than 0".to_owned(),
+            ));
+        }
         let output_schema = match projection {
             None => schema.clone(),
-            Some(projection) => Arc::new(schema.project(projection).unwrap()),
+            Some(projection) => Arc::new(schema.project(projection)?),
         };
-        let file_scan_tasks_group = split_n_vecs(file_scan_tasks, batch_parallelism);
+        let (file_scan_tasks_group, partitioning) =
+            match layout.group_by_partition(&file_scan_tasks, &output_schema, batch_parallelism)? {
+                Some((groups, partition_exprs)) => {
+                    let partitioning = Partitioning::Hash(partition_exprs, groups.len());
+                    (groups, partitioning)
+                }
+                None => {
+                    let groups = split_n_vecs(file_scan_tasks, batch_parallelism);
+                    let partitioning = Partitioning::UnknownPartitioning(groups.len());
+                    (groups, partitioning)
+                }
+            };
+        let ordering = layout.output_ordering(&file_scan_tasks_group, &output_schema);
         let plan_properties =
-            Self::compute_properties(output_schema.clone(), file_scan_tasks_group.len());
+            Self::compute_properties(output_schema.clone(), partitioning, ordering);
+        let project_field_ids = get_project_field_ids(&schema, projection)?;
         let projection = get_column_names(schema.clone(), projection);
         let predicates = convert_filters_to_predicate(filters);
 
-        Self {
+        Ok(Self {
             file_scan_tasks_group,
             plan_properties,
             projection,
+            project_field_ids,
             predicates,
             file_io: file_io.clone(),
             need_seq_num,
-            need_file_path_and_pos,
-        }
+            position_deletes,
+            file_prefetch_depth,
+        })
     }
 
     /// Computes [`PlanProperties`] used in query optimization.
-    fn compute_properties(schema: ArrowSchemaRef, partitioning_size: usize) -> PlanProperties {
-        // TODO:
-        // This is more or less a placeholder, to be replaced
-        // once we support output-partitioning
+    fn compute_properties(
+        schema: ArrowSchemaRef,
+        partitioning: Partitioning,
+        ordering: Option<LexOrdering>,
+    ) -> PlanProperties {
+        let eq_properties = match ordering {
+            Some(ordering) => EquivalenceProperties::new_with_orderings(schema, &[ordering]),
+            None => EquivalenceProperties::new(schema),
+        };
         PlanProperties::new(
-            EquivalenceProperties::new(schema),
-            Partitioning::UnknownPartitioning(partitioning_size),
+            eq_properties,
+            partitioning,
             EmissionType::Incremental,
             Boundedness::Bounded,
         )
     }
 }
 
+/// Seeds of the hash `RepartitionExec` routes rows with. Scan groups must be assigned with the
+/// same hash for DataFusion to rely on the declared hash partitioning.
+const REPARTITION_HASH_SEEDS: (u64, u64, u64, u64) = (0, 0, 0, 0);
+
+/// Partition values and sort order of the data files, from which the scan declares its output
+/// partitioning and ordering.
+#[derive(Debug, Clone, Default)]
+pub struct DataFileLayout {
+    pub partition_spec: Option<Arc<PartitionSpec>>,
+    pub sort_order: Option<SortOrderRef>,
+    pub data_file_metadata: HashMap<String, DataFileMetadata>,
+}
+
+impl DataFileLayout {
+    /// Groups the tasks by the hash of their partition value, the same way `RepartitionExec`
+    /// hash partitions rows on the partition key expressions.
+    ///
+    /// The source columns of the partition fields must be columns of the scan, so that the
+    /// partition value of a row can be computed from it. Returns `None` if any task cannot be
+    /// placed, including files written with another partition spec, whose partition values do
+    /// not follow the fields of the spec.
+    ///
+    /// Also returns `None` once a file is split into several tasks: the splits of a file share
+    /// its partition value, so grouping them would leave a large file to a single scan partition.
+    /// The splits are then spread over all the scan partitions, and the rows of a partition are
+    /// routed to a single writer by a `RepartitionExec` instead.
+    fn group_by_partition(
+        &self,
+        file_scan_tasks: &[FileScanTask],
+        output_schema: &ArrowSchemaRef,
+        split_num: usize,
+    ) -> DFResult<Option<(Vec<Vec<FileScanTask>>, Vec<Arc<dyn PhysicalExpr>>)>> {
+        let Some(partition_spec) = &self.partition_spec else {
+            return Ok(None);
+        };
+        let mut data_file_paths = HashSet::with_capacity(file_scan_tasks.len());
+        if !file_scan_tasks
+            .iter()
+            .all(|task| data_file_paths.insert(task.data_file_path.as_str()))
+        {
+            return Ok(None);
+        }
+        // (index in the partition value, expression computing it, its type)
+        let mut key_exprs = vec![];
+        for (idx, field) in partition_spec.fields().iter().enumerate() {
+            if field.transform == Transform::Void {
+                continue;
+            }
+            let Some(column) = column_by_field_id(output_schema, field.source_id) else {
+                return Ok(None);
+            };
+            let expr = partition_field_expr(field.transform, column.name(), output_schema)?;
+            let data_type = expr.data_type(output_schema)?;
+            key_exprs.push((idx, expr, data_type));
+        }
+        if key_exprs.is_empty() {
+            return Ok(None);
+        }
+
+        let random_state = ahash::RandomState::with_seeds(
+            REPARTITION_HASH_SEEDS.0,
+            REPARTITION_HASH_SEEDS.1,
+            REPARTITION_HASH_SEEDS.2,
+            REPARTITION_HASH_SEEDS.3,
+        );
+        let mut groups = vec![vec![]; split_num];
+        for task in file_scan_tasks {
+            let Some(partition) = self
+                .data_file_metadata
+                .get(&task.data_file_path)
+                .filter(|metadata| metadata.partition_spec_id == partition_spec.spec_id())
+                .and_then(|metadata| metadata.partition.clone())
+            else {
+                return Ok(None);
+            };
+            let partition = partition.into_iter().collect::<Vec<_>>();
+            let key = key_exprs
+                .iter()
+                .map(|(idx, _, data_type)| {
+                    let literal = partition.get(*idx).cloned().flatten();
+                    literal_to_array(literal, data_type, 1)
+                })
+                .collect::<DFResult<Vec<_>>>()?;
+            let mut hashes = vec![0u64];
+            create_hashes(&key, &random_state, &mut hashes)?;
+            groups[(hashes[0] % split_num as u64) as usize].push(task.clone());
+        }
+        let exprs = key_exprs.into_iter().map(|(_, expr, _)| expr).collect();
+        Ok(Some((groups, exprs)))
+    }
+
+    /// Ordering of every scan partition, known when each partition reads at most one file
+    /// written with the sort order of the table.
+    ///
+    /// The ordering covers the leading sort fields that are identity transforms of columns of
+    /// the scan.
+    fn output_ordering(
+        &self,
+        file_scan_tasks_group: &[Vec<FileScanTask>],
+        output_schema: &ArrowSchemaRef,
+    ) -> Option<LexOrdering> {
+        let sort_order = self.sort_order.as_ref()?;
+        let sorted_files = file_scan_tasks_group.iter().all(|tasks| {
+            tasks.len() <= 1
+                && tasks.iter().all(|task| {
+                    self.data_file_metadata
+                        .get(&task.data_file_path)
+                        .and_then(|metadata| metadata.sort_order_id)
+                        .is_some_and(|id| i64::from(id) == sort_order.order_id)
+                })
+        });
+        if !sorted_files {
+            return None;
+        }
+        let sort_exprs = sort_order
+            .fields
+            .iter()
+            .map_while(|field| {
+                if field.transform != Transform::Identity {
+                    return None;
+                }
+                let column = column_by_field_id(output_schema, field.source_id)?;
+                Some(PhysicalSortExpr::new(
+                    Arc::new(column),
+                    SortOptions {
+                        descending: field.direction == SortDirection::Descending,
+                        nulls_first: field.null_order == NullOrder::First,
+                    },
+                ))
+            })
+            .collect::<Vec<_>>();
+        if sort_exprs.is_empty() {
+            None
+        } else {
+            Some(LexOrdering::new(sort_exprs))
+        }
+    }
+}
+
+/// Finds the column of `schema` carrying the iceberg field id `field_id`.
+fn column_by_field_id(schema: &ArrowSchemaRef, field_id: i32) -> Option<Column> {
+    let field_id = field_id.to_string();
+    schema
+        .fields()
+        .iter()
+        .position(|field| field.metadata().get(PARQUET_FIELD_ID_META_KEY) == Some(&field_id))
+        .map(|idx| Column::new(schema.field(idx).name(), idx))
+}
+
+/// Builds an array of `data_type` repeating a partition or default value `num_rows` times.
+pub(crate) fn literal_to_array(
+    literal: Option<Literal>,
+    data_type: &DataType,
+    num_rows: usize,
+) -> DFResult<ArrayRef> {
+    let value = match literal {
+        None => ScalarValue::Null,
+        Some(Literal::Primitive(literal)) => match (literal, data_type) {
+            (PrimitiveLiteral::Boolean(v), _) => ScalarValue::Boolean(Some(v)),
+            (PrimitiveLiteral::Int(v), _) => ScalarValue::Int32(Some(v)),
+            (PrimitiveLiteral::Long(v), _) => ScalarValue::Int64(Some(v)),
+            (PrimitiveLiteral::Float(v), _) => ScalarValue::Float32(Some(v.0)),
+            (PrimitiveLiteral::Double(v), _) => ScalarValue::Float64(Some(v.0)),
+            (PrimitiveLiteral::String(v), _) => ScalarValue::Utf8(Some(v)),
+            (PrimitiveLiteral::Binary(v), DataType::FixedSizeBinary(size)) => {
+                ScalarValue::FixedSizeBinary(*size, Some(v))
+            }
+            (PrimitiveLiteral::Binary(v), _) => ScalarValue::Binary(Some(v)),
+            (PrimitiveLiteral::Int128(v), DataType::Decimal128(precision, scale)) => {
+                ScalarValue::Decimal128(Some(v), *precision, *scale)
+            }
+            (PrimitiveLiteral::UInt128(v), _) => {
+                ScalarValue::FixedSizeBinary(16, Some(v.to_be_bytes().to_vec()))
+            }
+            (literal, _) => {
+                return Err(DataFusionError::Internal(format!(
+                    "value {:?} cannot be read as {}",
+                    literal, data_type
+                )));
+            }
+        },
+        Some(literal) => {
+            return Err(DataFusionError::Internal(format!(
+                "value {:?} is not a primitive",
+                literal
+            )));
+        }
+    };
+    let array = value.to_array_of_size(num_rows)?;
+    if array.data_type() == data_type {
+        Ok(array)
+    } else {
+        Ok(cast(&array, data_type)?)
+    }
+}
+
 /// Uniformly distribute scan tasks to compute nodes.
 /// It's deterministic so that it can best utilize the data locality.
 ///
@@ -158,7 +405,7 @@ fn split_n_vecs(file_scan_tasks: Vec<FileScanTask>, split_num: usize) -> Vec<Vec
 
     for file_task in file_scan_tasks {
         let mut group = heap.peek_mut().unwrap();
-        group.0.total_length += file_task.length;
+        group.0.total_length = group.0.total_length.saturating_add(file_task.length);
         group.0.tasks.push(file_task);
     }
 
@@ -201,8 +448,12 @@ impl ExecutionPlan for IcebergFileTaskScan {
         let fut = get_batch_stream(
             self.file_io.clone(),
             self.file_scan_tasks_group[partition].clone(),
+            self.schema(),
+            self.project_field_ids.clone(),
+            self.predicates.clone(),
             self.need_seq_num,
-            self.need_file_path_and_pos,
+            self.position_deletes.clone(),
+            self.file_prefetch_depth,
         );
         let stream = futures::stream::once(fut).try_flatten();
 
@@ -214,44 +465,83 @@ impl ExecutionPlan for IcebergFileTaskScan {
 }
 
 /// Gets a stream of record batches from a list of file scan tasks
+///
+/// The projection and predicate of the scan are pushed down into each task, and the batches are
+/// reordered to match `output_schema`. Up to `file_prefetch_depth` files after the current one
+/// are opened and buffered concurrently, while batches are still emitted in task order. ORC and
+/// Avro files are loaded into memory as a whole, so only one of them is read ahead at a time.
+///
+/// The row groups of a file are read one after another; large Parquet files are read
+/// concurrently only once they are split across partitions by `target_split_size`.
+#[allow(clippy::too_many_arguments)]
 async fn get_batch_stream(
     file_io: FileIO,
     file_scan_tasks: Vec<FileScanTask>,
+    output_schema: ArrowSchemaRef,
+    project_field_ids: Option<Vec<i32>>,
+    predicate: Option<Predicate>,
     need_seq_num: bool,
-    need_file_path_and_pos: bool,
+    position_deletes: Option<Arc<PositionDeleteIndex>>,
+    file_prefetch_depth: usize,
 ) -> DFResult<Pin<Box<dyn Stream<Item = DFResult<RecordBatch>> + Send>>> {
+    // one reader shared by all files of the partition
+    let reader = build_arrow_reader(&file_io);
     let stream = try_stream! {
-        for task in file_scan_tasks {
-            let file_path = task.data_file_path.clone();
-            let data_file_content = task.data_file_content;
-            let sequence_number = task.sequence_number;
-            let task_stream = futures::stream::iter(vec![Ok(task)]).boxed();
-            let arrow_reader_builder = ArrowReaderBuilder::new(file_io.clone());
-            let mut batch_stream = arrow_reader_builder.build()
-                .read(task_stream)
-                .await
-                .map_err(to_datafusion_error)?;
-            let mut index_start = 0;
-            while let Some(batch) = batch_stream.next().await {
-                let mut batch = batch.map_err(to_datafusion_error)?;
-                let batch = match data_file_content {
-                    iceberg::spec::DataContentType::Data => {
+        let mut tasks = file_scan_tasks.into_iter().peekable();
+        let mut prefetched_files: VecDeque<PrefetchedFile> =
+            VecDeque::with_capacity(file_prefetch_depth + 1);
+        loop {
+            while prefetched_files.len() <= file_prefetch_depth {
+                let Some(task) = tasks.next_if(|task| {
+                    !is_loaded_whole(task) || !prefetched_files.iter().any(|file| file.loaded_whole)
+                }) else {
+                    break;
+                };
+                // positions are counted over all rows of a data file, so rows of a file with
+                // position deletes must not be pruned
+                let has_position_deletes = position_deletes
+                    .as_ref()
+                    .is_some_and(|index| index.get(&task.data_file_path).is_some());
+                let predicate = predicate.as_ref().filter(|_| !has_position_deletes);
+                let task = push_down_into_task(task, project_field_ids.as_deref(), predicate);
+                prefetched_files.push_back(PrefetchedFile::spawn(
+                    file_io.clone(),
+                    reader.clone(),
+                    task,
+                ));
+            }
+            let Some(mut file) = prefetched_files.pop_front() else {
+                break;
+            };
+            let deletes = position_deletes
+                .as_ref()
+                .and_then(|index| index.get(&file.data_file_path));
+            let sequence_number = file.sequence_number;
+            // splits of a file start at the position of their first row, if the file
+            // cannot be read the error comes through `batches`
+            let mut index_start = file.first_row_position.await.unwrap_or(0);
+            while let Some(batch) = file.batches.recv().await {
+                let mut batch = batch?;
+                let batch = match file.data_file_content {
+                    DataContentType::Data => {
+                        // drop the rows deleted by position
+                        if let Some(deletes) = deletes {
+                            let num_rows = batch.num_rows() as i64;
+                            batch = remove_deleted_rows(deletes, batch, index_start)?;
+                            index_start += num_rows;
+                        }
                         // add sequence number if needed
                         if need_seq_num {
                             batch = add_seq_num_into_batch(batch, sequence_number)?;
                         }
-                        // add file path and position if needed
-                        if need_file_path_and_pos {
-                            batch = add_file_path_pos_into_batch(batch, &file_path, index_start)?;
-                            index_start += batch.num_rows() as i64;
-                        }
-                        batch
+                        project_batch(batch, &output_schema)?
                     }
-                    iceberg::spec::DataContentType::PositionDeletes => {
+                    DataContentType::PositionDeletes => {
                         batch
                     },
-                    iceberg::spec::DataContentType::EqualityDeletes => {
-                        add_seq_num_into_batch(batch, sequence_number)?
+                    DataContentType::EqualityDeletes => {
+                        let batch = add_seq_num_into_batch(batch, sequence_number)?;
+                        project_batch(batch, &output_schema)?
                     },
                 };
                 yield batch;
@@ -261,6 +551,101 @@ async fn get_batch_stream(
     Ok(Box::pin(stream))
 }
 
+/// A file read ahead on a spawned task, which buffers up to [`PREFETCH_BATCHES`] batches.
+///
+/// The reading task is aborted when the file is dropped.
+struct PrefetchedFile {
+    data_file_path: String,
+    data_file_content: DataContentType,
+    sequence_number: i64,
+    /// Whether the file is loaded into memory as a whole rather than read in ranges
+    loaded_whole: bool,
+    first_row_position: oneshot::Receiver<i64>,
+    batches: mpsc::Receiver<DFResult<RecordBatch>>,
+    _reader_task: SpawnedTask<()>,
+}
+
+impl PrefetchedFile {
+    fn spawn(file_io: FileIO, reader: ArrowReader, task: FileScanTask) -> Self {
+        let data_file_path = task.data_file_path.clone();
+        let data_file_content = task.data_file_content;
+        let sequence_number = task.sequence_number;
+        let loaded_whole = is_loaded_whole(&task);
+        let (position_tx, position_rx) = oneshot::channel();
+        let (tx, rx) = mpsc::channel(PREFETCH_BATCHES);
+        let reader_task = SpawnedTask::spawn(async move {
+            let mut task_batches = match read_file_scan_task(&file_io, &reader, task).await {
+                Ok(task_batches) => task_batches,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
+            let _ = position_tx.send(task_batches.first_row_position);
+            while let Some(batch) = task_batches.batches.next().await {
+                // the receiver is gone once the scan is dropped
+                if tx.send(batch).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            data_file_path,
+            data_file_content,
+            sequence_number,
+            loaded_whole,
+            first_row_position: position_rx,
+            batches: rx,
+            _reader_task: reader_task,
+        }
+    }
+}
+
+/// Whether the file of `task` is loaded into memory as a whole by [`read_file_scan_task`].
+fn is_loaded_whole(task: &FileScanTask) -> bool {
+    task.data_file_format != DataFileFormat::Parquet
+}
+
+/// Sets the field ids and the predicate a file scan task is read with.
+///
+/// Position delete files are always read in full. A predicate that cannot be bound to the task
+/// schema, e.g. one on a hidden column, is dropped and left to DataFusion.
+fn push_down_into_task(
+    mut task: FileScanTask,
+    project_field_ids: Option<&[i32]>,
+    predicate: Option<&Predicate>,
+) -> FileScanTask {
+    if task.data_file_content == DataContentType::PositionDeletes {
+        return task;
+    }
+    if let Some(project_field_ids) = project_field_ids {
+        task.project_field_ids = project_field_ids.to_vec();
+    }
+    task.predicate = predicate.and_then(|predicate| predicate.bind(task.schema.clone(), true).ok());
+    task
+}
+
+/// Selects the columns of `schema` from `batch` by name, in the order of `schema`.
+fn project_batch(batch: RecordBatch, schema: &ArrowSchemaRef) -> DFResult<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            batch.column_by_name(field.name()).cloned().ok_or_else(|| {
+                DataFusionError::Internal(format!(
+                    "column {} is missing from the scanned batch",
+                    field.name()
+                ))
+            })
+        })
+        .collect::<DFResult<Vec<_>>>()?;
+    Ok(RecordBatch::try_new_with_options(
+        schema.clone(),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+    )?)
+}
+
 /// Adds a sequence number column to a record batch
 fn add_seq_num_into_batch(batch: RecordBatch, seq_num: i64) -> DFResult<RecordBatch> {
     let schema = batch.schema();
@@ -279,40 +664,6 @@ fn add_seq_num_into_batch(batch: RecordBatch, seq_num: i64) -> DFResult<RecordBa
         .map_err(|e| datafusion::error::DataFusionError::ArrowError(e, None))
 }
 
-/// Adds a file path and position column to a record batch
-fn add_file_path_pos_into_batch(
-    batch: RecordBatch,
-    file_path: &str,
-    index_start: i64,
-) -> DFResult<RecordBatch> {
-    let schema = batch.schema();
-    let file_path_field = Arc::new(Field::new(
-        "file_path",
-        datafusion::arrow::datatypes::DataType::Utf8,
-        false,
-    ));
-    let pos_field = Arc::new(Field::new(
-        "pos",
-        datafusion::arrow::datatypes::DataType::Int64,
-        false,
-    ));
-    let mut new_fields = schema.fields().to_vec();
-    new_fields.push(file_path_field);
-    new_fields.push(pos_field);
-    let new_schema = Arc::new(Schema::new(new_fields));
-
-    let mut columns = batch.columns().to_vec();
-    columns.push(Arc::new(StringArray::from(vec![
-        file_path;
-        batch.num_rows()
-    ])));
-    columns.push(Arc::new(Int64Array::from_iter(
-        (index_start..(index_start + batch.num_rows() as i64)).collect::<Vec<i64>>(),
-    )));
-    RecordBatch::try_new(new_schema, columns)
-        .map_err(|e| datafusion::error::DataFusionError::ArrowError(e, None))
-}
-
 impl DisplayAs for IcebergFileTaskScan {
     fn fmt_as(
         &self,
@@ -332,6 +683,32 @@ impl DisplayAs for IcebergFileTaskScan {
     }
 }
 
+/// Gets the iceberg field ids of the projected columns, skipping the hidden columns that are
+/// added after reading.
+fn get_project_field_ids(
+    schema: &ArrowSchemaRef,
+    projection: Option<&Vec<usize>>,
+) -> DFResult<Option<Vec<i32>>> {
+    let Some(projection) = projection else {
+        return Ok(None);
+    };
+    projection
+        .iter()
+        .map(|idx| schema.field(*idx))
+        .filter(|field| !SYS_HIDDEN_COLS.contains(&field.name().as_str()))
+        .map(|field| {
+            field
+                .metadata()
+                .get(PARQUET_FIELD_ID_META_KEY)
+                .and_then(|id| id.parse::<i32>().ok())
+                .ok_or_else(|| {
+                    DataFusionError::Internal(format!("column {} has no field id", field.name()))
+                })
+        })
+        .collect::<DFResult<Vec<_>>>()
+        .map(Some)
+}
+
 pub fn get_column_names(
     schema: ArrowSchemaRef,
     projection: Option<&Vec<usize>>,
@@ -462,4 +839,404 @@ mod tests {
             assert_eq!(groups, groups_2);
         }
     }
+
+    #[test]
+    fn test_new_rejects_invalid_input() {
+        let file_io = iceberg::io::FileIOBuilder::new("memory").build().unwrap();
+        let schema = Arc::new(datafusion::arrow::datatypes::Schema::new(vec![Field::new(
+            "id",
+            datafusion::arrow::datatypes::DataType::Int64,
+            false,
+        )]));
+        let file_scan_tasks = vec![create_file_scan_task(100, 1)];
+
+        let out_of_range = IcebergFileTaskScan::new(
+            file_scan_tasks.clone(),
+            schema.clone(),
+            Some(&vec![1]),
+            &[],
+            &file_io,
+            false,
+            None,
+            1,
+            0,
+            &DataFileLayout::default(),
+        );
+        assert!(out_of_range.is_err());
+
+        let zero_parallelism = IcebergFileTaskScan::new(
+            file_scan_tasks,
+            schema,
+            None,
+            &[],
+            &file_io,
+            false,
+            None,
+            0,
+            0,
+            &DataFileLayout::default(),
+        );
+        assert!(zero_parallelism.is_err());
+    }
+
+    #[test]
+    fn test_get_project_field_ids_skips_hidden_columns() {
+        let field_with_id = |name: &str, id: &str| {
+            Field::new(name, datafusion::arrow::datatypes::DataType::Int64, false).with_metadata(
+                std::collections::HashMap::from([(
+                    PARQUET_FIELD_ID_META_KEY.to_owned(),
+                    id.to_owned(),
+                )]),
+            )
+        };
+        let schema = Arc::new(datafusion::arrow::datatypes::Schema::new(vec![
+            field_with_id("a", "1"),
+            field_with_id("b", "2"),
+            Field::new(
+                SYS_HIDDEN_SEQ_NUM,
+                datafusion::arrow::datatypes::DataType::Int64,
+                false,
+            ),
+        ]));
+
+        assert_eq!(get_project_field_ids(&schema, None).unwrap(), None);
+        assert_eq!(
+            get_project_field_ids(&schema, Some(&vec![2, 1])).unwrap(),
+            Some(vec![2])
+        );
+    }
+
+    #[test]
+    fn test_push_down_into_task() {
+        let project_field_ids = [1, 2];
+        let task = push_down_into_task(
+            create_file_scan_task(100, 1),
+            Some(&project_field_ids),
+            None,
+        );
+        assert_eq!(task.project_field_ids, vec![1, 2]);
+
+        let mut position_delete_task = create_file_scan_task(100, 2);
+        position_delete_task.data_file_content = DataContentType::PositionDeletes;
+        let task = push_down_into_task(position_delete_task, Some(&project_field_ids), None);
+        assert!(task.project_field_ids.is_empty());
+    }
+
+    fn partitioned_layout(
+        values: &[i64],
+        transform: Transform,
+    ) -> (Vec<FileScanTask>, DataFileLayout, ArrowSchemaRef) {
+        use iceberg::spec::{Datum, NestedField, PrimitiveType, Struct, Type};
+        use iceberg::transform::create_transform_function;
+
+        let schema = Arc::new(
+            Schema::builder()
+                .with_fields(vec![Arc::new(NestedField::required(
+                    1,
+                    "p",
+                    Type::Primitive(PrimitiveType::Long),
+                ))])
+                .build()
+                .unwrap(),
+        );
+        let partition_spec = PartitionSpec::builder(schema.clone())
+            .add_partition_field("p", "p_part", transform)
+            .unwrap()
+            .build()
+            .unwrap();
+        let function = create_transform_function(&transform).unwrap();
+        let tasks = values
+            .iter()
+            .map(|value| create_file_scan_task(100, *value as u64))
+            .collect::<Vec<_>>();
+        let data_file_metadata = tasks
+            .iter()
+            .zip(values)
+            .map(|(task, value)| {
+                let partition = function
+                    .transform_literal(&Datum::long(*value))
+                    .unwrap()
+                    .map(|datum| Literal::Primitive(datum.literal().clone()));
+                (
+                    task.data_file_path.clone(),
+                    DataFileMetadata {
+                        partition_spec_id: 0,
+                        partition: Some(Struct::from_iter([partition])),
+                        sort_order_id: Some(1),
+                    },
+                )
+            })
+            .collect();
+        let layout = DataFileLayout {
+            partition_spec: Some(Arc::new(partition_spec)),
+            sort_order: None,
+            data_file_metadata,
+        };
+        let arrow_schema = Arc::new(iceberg::arrow::schema_to_arrow_schema(&schema).unwrap());
+        (tasks, layout, arrow_schema)
+    }
+
+    #[tokio::test]
+    async fn test_group_by_partition_matches_repartition() {
+        use datafusion::physical_plan::collect_partitioned;
+        use datafusion::physical_plan::memory::MemoryExec;
+        use datafusion::physical_plan::repartition::RepartitionExec;
+
+        let values = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 13, 21];
+        for transform in [
+            Transform::Identity,
+            Transform::Bucket(4),
+            Transform::Truncate(5),
+        ] {
+            let (tasks, layout, schema) = partitioned_layout(&values, transform);
+            let (groups, exprs) = layout
+                .group_by_partition(&tasks, &schema, 3)
+                .unwrap()
+                .unwrap();
+            assert_eq!(groups.len(), 3);
+            assert_eq!(exprs.len(), 1);
+
+            // rows holding the values must be routed to the groups of their files by the
+            // declared partitioning
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int64Array::from(values.to_vec()))],
+            )
+            .unwrap();
+            let input = MemoryExec::try_new(&[vec![batch]], schema.clone(), None).unwrap();
+            let plan =
+                RepartitionExec::try_new(Arc::new(input), Partitioning::Hash(exprs, 3)).unwrap();
+            let partitions = collect_partitioned(Arc::new(plan), Arc::new(TaskContext::default()))
+                .await
+                .unwrap();
+            for (group, batches) in groups.iter().zip(partitions) {
+                let mut rows = batches
+                    .iter()
+                    .flat_map(|batch| {
+                        batch
+                            .column(0)
+                            .as_any()
+                            .downcast_ref::<Int64Array>()
+                            .unwrap()
+                            .values()
+                            .to_vec()
+                    })
+                    .collect::<Vec<_>>();
+                rows.sort();
+                let mut expected = values
+                    .iter()
+                    .copied()
+                    .filter(|value| {
+                        group
+                            .iter()
+                            .any(|task| task.data_file_path == format!("test_{}.parquet", value))
+                    })
+                    .collect::<Vec<_>>();
+                expected.sort();
+                assert_eq!(rows, expected, "{}", transform);
+            }
+        }
+
+        let (tasks, layout, schema) = partitioned_layout(&values, Transform::Identity);
+        // the splits of a file are spread over all the scan partitions
+        let mut split_tasks = tasks.clone();
+        split_tasks.push(tasks[0].clone());
+        assert!(
+            layout
+                .group_by_partition(&split_tasks, &schema, 3)
+                .unwrap()
+                .is_none()
+        );
+
+        // files of an older partition spec cannot be placed
+        let mut layout = layout;
+        layout
+            .data_file_metadata
+            .values_mut()
+            .next()
+            .unwrap()
+            .partition_spec_id = 1;
+        assert!(
+            layout
+                .group_by_partition(&tasks, &schema, 3)
+                .unwrap()
+                .is_none()
+        );
+
+        // files without partition values cannot be placed
+        layout.data_file_metadata.clear();
+        assert!(
+            layout
+                .group_by_partition(&tasks, &schema, 3)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_output_ordering() {
+        use iceberg::spec::{SortField, SortOrder};
+
+        let (tasks, mut layout, schema) = partitioned_layout(&[1, 2], Transform::Identity);
+        layout.sort_order = Some(Arc::new(SortOrder {
+            order_id: 1,
+            fields: vec![SortField {
+                source_id: 1,
+                transform: Transform::Identity,
+                direction: SortDirection::Descending,
+                null_order: NullOrder::First,
+            }],
+        }));
+
+        let one_file_per_group = vec![vec![tasks[0].clone()], vec![tasks[1].clone()], vec![]];
+        let ordering = layout
+            .output_ordering(&one_file_per_group, &schema)
+            .unwrap();
+        assert_eq!(ordering.len(), 1);
+        assert!(ordering[0].options.descending);
+        assert!(ordering[0].options.nulls_first);
+
+        // files of a group are read one after the other, so the group is not sorted
+        assert!(layout.output_ordering(&[tasks.clone()], &schema).is_none());
+
+        layout.sort_order = None;
+        assert!(
+            layout
+                .output_ordering(&one_file_per_group, &schema)
+                .is_none()
+        );
+    }
+
+    async fn write_parquet_file(file_io: &FileIO, path: &str, ids: Vec<i64>) -> FileScanTask {
+        use datafusion::parquet::arrow::ArrowWriter;
+        use iceberg::arrow::schema_to_arrow_schema;
+        use iceberg::spec::{NestedField, PrimitiveType, Type};
+
+        let schema = Arc::new(
+            Schema::builder()
+                .with_fields(vec![Arc::new(NestedField::required(
+                    1,
+                    "id",
+                    Type::Primitive(PrimitiveType::Long),
+                ))])
+                .build()
+                .unwrap(),
+        );
+        let arrow_schema = Arc::new(schema_to_arrow_schema(&schema).unwrap());
+        let batch =
+            RecordBatch::try_new(arrow_schema.clone(), vec![Arc::new(Int64Array::from(ids))])
+                .unwrap();
+        let mut writer = ArrowWriter::try_new(vec![], arrow_schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        let bytes = writer.into_inner().unwrap();
+        let file_size_in_bytes = bytes.len() as u64;
+        file_io
+            .new_output(path)
+            .unwrap()
+            .write(bytes.into())
+            .await
+            .unwrap();
+        FileScanTask {
+            length: file_size_in_bytes,
+            record_count: None,
+            data_file_path: path.to_owned(),
+            schema,
+            project_field_ids: vec![1],
+            file_size_in_bytes,
+            ..create_file_scan_task(0, 0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_batch_stream_prefetches_in_order() {
+        use datafusion::arrow::datatypes::Schema as ArrowSchema;
+        use iceberg::io::FileIOBuilder;
+
+        let file_io = FileIOBuilder::new("memory").build().unwrap();
+        let mut tasks = vec![];
+        for i in 0..4 {
+            let path = format!("memory:///{}.parquet", i);
+            tasks.push(write_parquet_file(&file_io, &path, vec![i * 2, i * 2 + 1]).await);
+        }
+        let output_schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "id",
+            DataType::Int64,
+            false,
+        )]));
+
+        for file_prefetch_depth in [0, 1, 8] {
+            let stream = get_batch_stream(
+                file_io.clone(),
+                tasks.clone(),
+                output_schema.clone(),
+                None,
+                None,
+                false,
+                None,
+                file_prefetch_depth,
+            )
+            .await
+            .unwrap();
+            let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+            let ids = batches
+                .iter()
+                .flat_map(|batch| {
+                    let ids = batch.column(0).as_any().downcast_ref::<Int64Array>();
+                    ids.unwrap().values().to_vec()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(ids, (0..8).collect::<Vec<_>>());
+        }
+
+        // the batches of the files before a missing one are emitted before its error
+        let missing = FileScanTask {
+            data_file_path: "memory:///missing.parquet".to_owned(),
+            ..tasks[0].clone()
+        };
+        tasks.insert(1, missing);
+        let mut stream = get_batch_stream(
+            file_io.clone(),
+            tasks,
+            output_schema,
+            None,
+            None,
+            false,
+            None,
+            2,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap().num_rows(), 2);
+        assert!(stream.next().await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_prefetched_file() {
+        use iceberg::io::FileIOBuilder;
+
+        let file_io = FileIOBuilder::new("memory").build().unwrap();
+        let task = write_parquet_file(&file_io, "memory:///a.parquet", vec![1, 2, 3]).await;
+        let reader = build_arrow_reader(&file_io);
+
+        let mut file = PrefetchedFile::spawn(file_io.clone(), reader.clone(), task.clone());
+        assert!(!file.loaded_whole);
+        assert_eq!((&mut file.first_row_position).await.unwrap(), 0);
+        assert_eq!(file.batches.recv().await.unwrap().unwrap().num_rows(), 3);
+        assert!(file.batches.recv().await.is_none());
+
+        // a file that cannot be read sends its error instead of batches
+        let missing = FileScanTask {
+            data_file_path: "memory:///missing.parquet".to_owned(),
+            ..task.clone()
+        };
+        let mut file = PrefetchedFile::spawn(file_io.clone(), reader, missing);
+        assert!(file.batches.recv().await.unwrap().is_err());
+        assert!(file.first_row_position.await.is_err());
+
+        let orc = FileScanTask {
+            data_file_format: DataFileFormat::Orc,
+            ..task
+        };
+        assert!(is_loaded_whole(&orc));
+    }
 }
@@ -16,41 +16,109 @@ use crate::error::Result;
  */
 
 use ::datafusion::{
+    execution::{
+        disk_manager::DiskManagerConfig,
+        memory_pool::FairSpillPool,
+        runtime_env::{RuntimeEnv, RuntimeEnvBuilder},
+    },
     parquet::file::properties::WriterProperties,
     prelude::{SessionConfig, SessionContext},
 };
 use async_trait::async_trait;
-use datafusion_processor::{DataFusionTaskContext, DatafusionProcessor};
+use datafusion_processor::{
+    DataFusionTaskContext, DatafusionProcessor, DatafusionTableRegister, canonical_equality_ids,
+};
+use delete_file_writer::{equality_delete_schema, equality_delete_writer, write_position_deletes};
+use deletion_vector::convert_to_deletion_vectors;
 use futures::{StreamExt, future::try_join_all};
 use iceberg::{
     io::FileIO,
-    spec::{DataFile, PartitionSpec, Schema},
+    scan::FileScanTask,
+    spec::{DataFile, DataFileFormat, PartitionSpec, Schema, Struct},
     writer::{
         IcebergWriter, IcebergWriterBuilder,
         base_writer::data_file_writer::DataFileWriterBuilder,
         file_writer::{
-            ParquetWriterBuilder,
+            FileWriterBuilder, ParquetWriterBuilder,
             location_generator::{DefaultFileNameGenerator, DefaultLocationGenerator},
         },
         function_writer::fanout_partition_writer::FanoutPartitionWriterBuilder,
     },
 };
+use orc_writer::OrcWriterBuilder;
+use position_delete::PositionDeleteIndex;
 use sqlx::types::Uuid;
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, LazyLock, Mutex},
+};
 use tokio::task::JoinHandle;
 
-use crate::CompactionError;
+use crate::{CompactionError, ExecutorConfig};
 
-use super::{CompactionExecutor, InputFileScanTasks, RewriteFilesStat};
+use super::{CompactionExecutor, DataFileMetadata, InputFileScanTasks, RewriteFilesStat};
 pub mod datafusion_processor;
-use super::{RewriteFilesRequest, RewriteFilesResponse};
+pub mod delete_file_writer;
+pub mod deletion_vector;
+use super::{RewriteDeleteFilesRequest, RewriteFilesRequest, RewriteFilesResponse};
+pub mod file_reader;
 pub mod file_scan_task_table_provider;
 pub mod iceberg_file_task_scan;
+pub mod orc_writer;
+pub mod partition_key;
+pub mod position_delete;
 
 const DEFAULT_PREFIX: &str = "10";
+const DEFAULT_FILE_PREFETCH_DEPTH: usize = 2;
+const DEFAULT_TARGET_SPLIT_SIZE: u64 = 128 * 1024 * 1024;
+/// Table property selecting the file format of written data files.
+const WRITE_FORMAT_DEFAULT: &str = "write.format.default";
+
+/// Memory limit and spill directory of a runtime environment.
+type RuntimeEnvKey = (Option<usize>, Option<String>);
+
+/// Runtime environments of the process by memory limit and spill directory, so that concurrent
+/// rewrites draw from one memory pool rather than each from its own. Both are only taken from
+/// the [`ExecutorConfig`] of the process, never from a request, so there are few of them.
+static RUNTIME_ENVS: LazyLock<Mutex<HashMap<RuntimeEnvKey, Arc<RuntimeEnv>>>> =
+    LazyLock::new(Default::default);
+
+/// Returns the runtime environment shared by the rewrites run with `memory_limit` and
+/// `spill_dir`, creating it on first use.
+fn shared_runtime_env(
+    memory_limit: Option<usize>,
+    spill_dir: Option<&str>,
+) -> Result<Arc<RuntimeEnv>> {
+    let mut runtime_envs = RUNTIME_ENVS.lock().unwrap();
+    let key = (memory_limit, spill_dir.map(str::to_owned));
+    if let Some(runtime_env) = runtime_envs.get(&key) {
+        return Ok(runtime_env.clone());
+    }
+    let mut runtime_env_builder = RuntimeEnvBuilder::new();
+    if let Some(memory_limit) = memory_limit {
+        runtime_env_builder =
+            runtime_env_builder.with_memory_pool(Arc::new(FairSpillPool::new(memory_limit)));
+    }
+    if let Some(spill_dir) = spill_dir {
+        runtime_env_builder =
+            runtime_env_builder.with_disk_manager(DiskManagerConfig::NewSpecified(vec![
+                PathBuf::from(spill_dir),
+            ]));
+    }
+    let runtime_env = runtime_env_builder.build_arc()?;
+    runtime_envs.insert(key, runtime_env.clone());
+    Ok(runtime_env)
+}
 
 #[derive(Default)]
-pub struct DataFusionExecutor {}
+pub struct DataFusionExecutor {
+    /// Memory limit in bytes shared by the rewrites of the process, unlimited if not set
+    memory_limit: Option<usize>,
+    /// Directory operators spill to, the temporary directory of the OS if not set
+    spill_dir: Option<String>,
+}
 
 #[async_trait]
 impl CompactionExecutor for DataFusionExecutor {
@@ -62,37 +130,65 @@ impl CompactionExecutor for DataFusionExecutor {
             config,
             dir_path,
             partition_spec,
+            table_properties,
+            sort_order,
         } = request;
+        config.validate()?;
+        let write_format = Self::write_format(&table_properties)?;
+        Self::check_partition_specs(
+            &input_file_scan_tasks.data_file_metadata,
+            &partition_spec,
+            config.rewrite_to_current_spec.unwrap_or(false),
+        )?;
         let batch_parallelism = config.batch_parallelism.unwrap_or(4);
         let target_partitions = config.target_partitions.unwrap_or(4);
+        let file_prefetch_depth = config
+            .file_prefetch_depth
+            .unwrap_or(DEFAULT_FILE_PREFETCH_DEPTH);
+        let target_split_size = config
+            .target_split_size
+            .unwrap_or(DEFAULT_TARGET_SPLIT_SIZE);
         let data_file_prefix = config
             .data_file_prefix
             .clone()
             .unwrap_or(DEFAULT_PREFIX.to_owned());
-        let mut session_config = SessionConfig::new();
-        session_config = session_config.with_target_partitions(target_partitions);
-        let ctx = Arc::new(SessionContext::new_with_config(session_config));
+        let ctx = Arc::new(self.session_context(target_partitions)?);
 
         let mut stat = RewriteFilesStat::default();
         let rewritten_files_count = input_file_scan_tasks.input_files_count();
+        let data_sequence_number = input_file_scan_tasks
+            .data_files
+            .iter()
+            .map(|task| task.sequence_number)
+            .max();
 
         let InputFileScanTasks {
             data_files,
             position_delete_files,
             equality_delete_files,
+            deletion_vectors,
+            data_file_metadata,
+            delete_file_metadata,
         } = input_file_scan_tasks;
 
         let datafusion_task_ctx = DataFusionTaskContext::builder()?
             .with_schema(schema)
+            .with_partition_spec(partition_spec.clone())
+            .with_sort_order(sort_order)
+            .with_data_file_metadata(data_file_metadata)
+            .with_delete_file_metadata(delete_file_metadata)
             .with_datafile(data_files)
             .with_position_delete_files(position_delete_files)
             .with_equality_delete_files(equality_delete_files)
+            .with_deletion_vectors(deletion_vectors)
             .build_merge_on_read()?;
         let (batchs, input_schema) = DatafusionProcessor::new(
             ctx,
             datafusion_task_ctx,
             batch_parallelism,
             target_partitions,
+            file_prefetch_depth,
+            target_split_size,
             file_io.clone(),
         )
         .execute()
@@ -115,6 +211,7 @@ impl CompactionExecutor for DataFusionExecutor {
                     schema,
                     file_io,
                     partition_spec,
+                    write_format,
                 )
                 .await?;
                 while let Some(b) = batch.as_mut().next().await {
@@ -128,7 +225,13 @@ impl CompactionExecutor for DataFusionExecutor {
         // collect all data files from all partitions
         let output_data_files: Vec<DataFile> = try_join_all(futures)
             .await
-            .map_err(|e| CompactionError::Execution(e.to_string()))?
+            .map_err(|e| {
+                if e.is_cancelled() {
+                    CompactionError::Cancelled(e.to_string())
+                } else {
+                    CompactionError::Execution(e.to_string())
+                }
+            })?
             .into_iter()
             .map(|res| res.map(|v| v.into_iter()))
             .collect::<Result<Vec<_>>>()
@@ -143,36 +246,323 @@ impl CompactionExecutor for DataFusionExecutor {
 
         Ok(RewriteFilesResponse {
             data_files: output_data_files,
+            delete_files: vec![],
+            deletion_vectors: vec![],
+            delete_file_sequence_numbers: HashMap::new(),
+            data_sequence_number,
+            stat,
+        })
+    }
+
+    async fn rewrite_delete_files(
+        &self,
+        request: RewriteDeleteFilesRequest,
+    ) -> Result<RewriteFilesResponse> {
+        let RewriteDeleteFilesRequest {
+            file_io,
+            schema,
+            position_delete_files,
+            equality_delete_files,
+            deletion_vectors,
+            write_deletion_vectors,
+            delete_file_metadata,
+            config,
+            dir_path,
+        } = request;
+        config.validate()?;
+        if !deletion_vectors.is_empty() && !write_deletion_vectors {
+            return Err(CompactionError::InvalidInput(
+                "deletion vectors can only be rewritten into deletion vectors".to_owned(),
+            ));
+        }
+        let batch_parallelism = config.batch_parallelism.unwrap_or(4);
+        let target_partitions = config.target_partitions.unwrap_or(4);
+        let file_prefetch_depth = config
+            .file_prefetch_depth
+            .unwrap_or(DEFAULT_FILE_PREFETCH_DEPTH);
+        let target_split_size = config
+            .target_split_size
+            .unwrap_or(DEFAULT_TARGET_SPLIT_SIZE);
+        let data_file_prefix = config
+            .data_file_prefix
+            .clone()
+            .unwrap_or(DEFAULT_PREFIX.to_owned());
+        let mut stat = RewriteFilesStat {
+            rewritten_files_count: (position_delete_files.len()
+                + equality_delete_files.len()
+                + deletion_vectors.len()) as u32,
+            ..Default::default()
+        };
+        let mut output_delete_files = vec![];
+        let mut output_deletion_vectors = vec![];
+
+        if write_deletion_vectors {
+            // a data file can be referenced by a single deletion vector, so all its position
+            // deletes are merged into it
+            let puffin_file_path = format!(
+                "{}/{}-{}.puffin",
+                dir_path,
+                data_file_prefix,
+                Uuid::now_v7()
+            );
+            output_deletion_vectors = convert_to_deletion_vectors(
+                &file_io,
+                position_delete_files,
+                deletion_vectors,
+                &puffin_file_path,
+            )
+            .await?;
+        } else {
+            // position deletes of any sequence number can be merged, as a position delete only
+            // applies to the data files committed before it
+            for ((partition_spec_id, partition, ()), tasks) in
+                Self::group_delete_files(position_delete_files, &delete_file_metadata, |_| ())?
+            {
+                let index = PositionDeleteIndex::load(&file_io, tasks, vec![]).await?;
+                output_delete_files.extend(
+                    write_position_deletes(
+                        file_io.clone(),
+                        dir_path.clone(),
+                        Self::delete_file_name_generator(&data_file_prefix),
+                        &index,
+                        partition,
+                        partition_spec_id,
+                    )
+                    .await?,
+                );
+            }
+        }
+
+        let ctx = Arc::new(self.session_context(target_partitions)?);
+        let table_register = DatafusionTableRegister::new(
+            file_io.clone(),
+            ctx.clone(),
+            file_prefetch_depth,
+            target_split_size,
+        );
+        let mut delete_file_sequence_numbers = HashMap::new();
+        for (
+            table_idx,
+            ((partition_spec_id, partition, (equality_ids, sequence_number)), mut tasks),
+        ) in Self::group_delete_files(equality_delete_files, &delete_file_metadata, |task| {
+            (
+                canonical_equality_ids(&task.equality_ids),
+                task.sequence_number,
+            )
+        })?
+        .into_iter()
+        .enumerate()
+        {
+            for task in &mut tasks {
+                task.project_field_ids = equality_ids.clone();
+            }
+            let table_name = format!("equality_delete_table_{}", table_idx);
+            table_register.register_delete_table_provider(
+                &equality_delete_schema(&schema, &equality_ids)?,
+                tasks,
+                &table_name,
+                batch_parallelism,
+            )?;
+            let mut writer = equality_delete_writer(
+                file_io.clone(),
+                dir_path.clone(),
+                Self::delete_file_name_generator(&data_file_prefix),
+                &schema,
+                equality_ids,
+                partition,
+                partition_spec_id,
+            )
+            .await?;
+            let mut stream = ctx
+                .table(table_name.as_str())
+                .await?
+                .distinct()?
+                .execute_stream()
+                .await?;
+            while let Some(batch) = stream.next().await {
+                writer.write(batch?).await?;
+            }
+            // an equality delete only applies to data files older than it, so the merged file
+            // must keep the sequence number of its inputs
+            for delete_file in writer.close().await? {
+                delete_file_sequence_numbers
+                    .insert(delete_file.file_path().to_owned(), sequence_number);
+                output_delete_files.push(delete_file);
+            }
+        }
+
+        stat.added_files_count = (output_delete_files.len() + output_deletion_vectors.len()) as u32;
+        stat.rewritten_bytes = output_delete_files
+            .iter()
+            .map(|f| f.file_size_in_bytes())
+            .chain(
+                output_deletion_vectors
+                    .iter()
+                    .map(|deletion_vector| deletion_vector.content_size_in_bytes),
+            )
+            .sum();
+
+        Ok(RewriteFilesResponse {
+            data_files: vec![],
+            delete_files: output_delete_files,
+            deletion_vectors: output_deletion_vectors,
+            delete_file_sequence_numbers,
+            data_sequence_number: None,
             stat,
         })
     }
 }
 
 impl DataFusionExecutor {
+    /// Creates an executor running with the memory limit and spill directory of `config`.
+    pub fn from_config(config: &ExecutorConfig) -> Self {
+        Self {
+            memory_limit: config.memory_limit,
+            spill_dir: config.spill_dir.clone(),
+        }
+    }
+
+    /// Builds the session a rewrite runs in, on the runtime environment shared by the rewrites of
+    /// the process.
+    ///
+    /// With a memory limit, hash joins are replaced by sort merge joins, as hash joins cannot
+    /// spill and would fail once the limit is reached rather than spill to disk.
+    fn session_context(&self, target_partitions: usize) -> Result<SessionContext> {
+        let mut session_config = SessionConfig::new().with_target_partitions(target_partitions);
+        if self.memory_limit.is_some() {
+            session_config =
+                session_config.set_bool("datafusion.optimizer.prefer_hash_join", false);
+        }
+        let runtime_env = shared_runtime_env(self.memory_limit, self.spill_dir.as_deref())?;
+        Ok(SessionContext::new_with_config_rt(
+            session_config,
+            runtime_env,
+        ))
+    }
+
+    /// Resolves the output file format from the table properties, Parquet by default.
+    pub(crate) fn write_format(
+        table_properties: &HashMap<String, String>,
+    ) -> Result<DataFileFormat> {
+        let write_format = match table_properties.get(WRITE_FORMAT_DEFAULT) {
+            Some(format) => DataFileFormat::from_str(format).map_err(|e| {
+                CompactionError::InvalidInput(format!("Invalid {}: {}", WRITE_FORMAT_DEFAULT, e))
+            })?,
+            None => DataFileFormat::Parquet,
+        };
+        match write_format {
+            DataFileFormat::Parquet | DataFileFormat::Orc => Ok(write_format),
+            DataFileFormat::Avro => Err(CompactionError::InvalidInput(
+                "Writing Avro data files is not supported".to_owned(),
+            )),
+        }
+    }
+
+    /// Rejects data files written with a partition spec other than the one of the request, unless
+    /// they are to be rewritten into it.
+    fn check_partition_specs(
+        data_file_metadata: &HashMap<String, DataFileMetadata>,
+        partition_spec: &PartitionSpec,
+        rewrite_to_current_spec: bool,
+    ) -> Result<()> {
+        if rewrite_to_current_spec {
+            return Ok(());
+        }
+        match data_file_metadata
+            .iter()
+            .find(|(_, metadata)| metadata.partition_spec_id != partition_spec.spec_id())
+        {
+            Some((data_file_path, metadata)) => Err(CompactionError::InvalidInput(format!(
+                "data file {} was written with partition spec {} instead of {}, set rewrite_to_current_spec to rewrite it",
+                data_file_path,
+                metadata.partition_spec_id,
+                partition_spec.spec_id()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Groups delete files by partition spec, partition and `key`, as the files of a group can be
+    /// merged into one.
+    #[allow(clippy::type_complexity)]
+    fn group_delete_files<K: Eq + std::hash::Hash>(
+        delete_files: Vec<FileScanTask>,
+        delete_file_metadata: &HashMap<String, DataFileMetadata>,
+        key: impl Fn(&FileScanTask) -> K,
+    ) -> Result<HashMap<(i32, Struct, K), Vec<FileScanTask>>> {
+        let mut groups: HashMap<(i32, Struct, K), Vec<FileScanTask>> = HashMap::new();
+        for task in delete_files {
+            let metadata = delete_file_metadata
+                .get(&task.data_file_path)
+                .ok_or_else(|| {
+                    CompactionError::InvalidInput(format!(
+                        "no metadata for delete file {}",
+                        task.data_file_path
+                    ))
+                })?;
+            let partition = metadata.partition.clone().unwrap_or_else(Struct::empty);
+            groups
+                .entry((metadata.partition_spec_id, partition, key(&task)))
+                .or_default()
+                .push(task);
+        }
+        Ok(groups)
+    }
+
+    fn delete_file_name_generator(data_file_prefix: &str) -> DefaultFileNameGenerator {
+        DefaultFileNameGenerator::new(
+            data_file_prefix.to_owned(),
+            Some(Uuid::now_v7().to_string()),
+            DataFileFormat::Parquet,
+        )
+    }
+
     async fn build_iceberg_writer(
         data_file_prefix: String,
         dir_path: String,
         schema: Arc<Schema>,
         file_io: FileIO,
         partition_spec: Arc<PartitionSpec>,
+        write_format: DataFileFormat,
     ) -> Result<Box<dyn IcebergWriter>> {
         let location_generator = DefaultLocationGenerator { dir_path };
         let unique_uuid_suffix = Uuid::now_v7();
         let file_name_generator = DefaultFileNameGenerator::new(
             data_file_prefix,
             Some(unique_uuid_suffix.to_string()),
-            iceberg::spec::DataFileFormat::Parquet,
+            write_format,
         );
 
-        let parquet_writer_builder = ParquetWriterBuilder::new(
-            WriterProperties::default(),
-            schema.clone(),
-            file_io,
-            location_generator,
-            file_name_generator,
-        );
+        match write_format {
+            DataFileFormat::Orc => {
+                let orc_writer_builder = OrcWriterBuilder::new(
+                    schema.clone(),
+                    file_io,
+                    location_generator,
+                    file_name_generator,
+                );
+                Self::build_data_file_writer(orc_writer_builder, schema, partition_spec).await
+            }
+            _ => {
+                let parquet_writer_builder = ParquetWriterBuilder::new(
+                    WriterProperties::default(),
+                    schema.clone(),
+                    file_io,
+                    location_generator,
+                    file_name_generator,
+                );
+                Self::build_data_file_writer(parquet_writer_builder, schema, partition_spec).await
+            }
+        }
+    }
+
+    async fn build_data_file_writer<B: FileWriterBuilder>(
+        file_writer_builder: B,
+        schema: Arc<Schema>,
+        partition_spec: Arc<PartitionSpec>,
+    ) -> Result<Box<dyn IcebergWriter>> {
         let data_file_builder =
-            DataFileWriterBuilder::new(parquet_writer_builder, None, partition_spec.spec_id());
+            DataFileWriterBuilder::new(file_writer_builder, None, partition_spec.spec_id());
         let iceberg_output_writer = if partition_spec.fields().is_empty() {
             Box::new(data_file_builder.build().await?) as Box<dyn IcebergWriter>
         } else {
@@ -189,3 +579,447 @@ impl DataFusionExecutor {
         Ok(iceberg_output_writer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_format() {
+        let properties =
+            |format: &str| HashMap::from([(WRITE_FORMAT_DEFAULT.to_owned(), format.to_owned())]);
+        assert_eq!(
+            DataFusionExecutor::write_format(&HashMap::new()).unwrap(),
+            DataFileFormat::Parquet
+        );
+        assert_eq!(
+            DataFusionExecutor::write_format(&properties("orc")).unwrap(),
+            DataFileFormat::Orc
+        );
+        assert_eq!(
+            DataFusionExecutor::write_format(&properties("PARQUET")).unwrap(),
+            DataFileFormat::Parquet
+        );
+        assert!(DataFusionExecutor::write_format(&properties("avro")).is_err());
+        assert!(DataFusionExecutor::write_format(&properties("csv")).is_err());
+    }
+
+    #[test]
+    fn test_group_delete_files() {
+        let schema = Arc::new(Schema::builder().build().unwrap());
+        let task = |path: &str, sequence_number| FileScanTask {
+            start: 0,
+            length: 100,
+            record_count: None,
+            data_file_path: path.to_owned(),
+            data_file_content: iceberg::spec::DataContentType::EqualityDeletes,
+            data_file_format: DataFileFormat::Parquet,
+            schema: schema.clone(),
+            project_field_ids: vec![],
+            predicate: None,
+            deletes: vec![],
+            sequence_number,
+            equality_ids: vec![1],
+            file_size_in_bytes: 100,
+        };
+        let metadata = |partition_spec_id| DataFileMetadata {
+            partition_spec_id,
+            partition: None,
+            sort_order_id: None,
+        };
+        let delete_file_metadata = HashMap::from([
+            ("a.parquet".to_owned(), metadata(0)),
+            ("b.parquet".to_owned(), metadata(0)),
+            ("c.parquet".to_owned(), metadata(1)),
+            ("d.parquet".to_owned(), metadata(0)),
+        ]);
+        let tasks = vec![
+            task("a.parquet", 1),
+            task("b.parquet", 1),
+            task("c.parquet", 1),
+            task("d.parquet", 2),
+        ];
+
+        let groups =
+            DataFusionExecutor::group_delete_files(tasks.clone(), &delete_file_metadata, |task| {
+                task.sequence_number
+            })
+            .unwrap();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[&(0, Struct::empty(), 1)].len(), 2);
+        assert_eq!(groups[&(1, Struct::empty(), 1)].len(), 1);
+        assert_eq!(groups[&(0, Struct::empty(), 2)].len(), 1);
+
+        let groups =
+            DataFusionExecutor::group_delete_files(tasks.clone(), &delete_file_metadata, |_| ())
+                .unwrap();
+        assert_eq!(groups.len(), 2);
+
+        assert!(
+            DataFusionExecutor::group_delete_files(
+                vec![task("e.parquet", 1)],
+                &delete_file_metadata,
+                |_| ()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_partition_specs() {
+        let metadata = |partition_spec_id| DataFileMetadata {
+            partition_spec_id,
+            partition: None,
+            sort_order_id: None,
+        };
+        let data_file_metadata = HashMap::from([
+            ("a.parquet".to_owned(), metadata(0)),
+            ("b.parquet".to_owned(), metadata(1)),
+        ]);
+        let unpartitioned = PartitionSpec::unpartition_spec();
+        assert!(
+            DataFusionExecutor::check_partition_specs(&data_file_metadata, &unpartitioned, false)
+                .is_err()
+        );
+        assert!(
+            DataFusionExecutor::check_partition_specs(&data_file_metadata, &unpartitioned, true)
+                .is_ok()
+        );
+        assert!(
+            DataFusionExecutor::check_partition_specs(
+                &HashMap::from([("a.parquet".to_owned(), metadata(0))]),
+                &unpartitioned,
+                false
+            )
+            .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_context_spills() {
+        use ::datafusion::arrow::array::{Int64Array, RecordBatch};
+        use ::datafusion::arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+        use ::datafusion::datasource::MemTable;
+        use ::datafusion::physical_plan::{ExecutionPlan, collect};
+
+        let executor = DataFusionExecutor::from_config(&ExecutorConfig {
+            memory_limit: Some(2 * 1024 * 1024),
+            spill_dir: Some(std::env::temp_dir().to_str().unwrap().to_owned()),
+            ..Default::default()
+        });
+        let ctx = executor.session_context(1).unwrap();
+        // the rewrites of the process share one memory pool
+        let other = executor.session_context(4).unwrap();
+        assert!(Arc::ptr_eq(&ctx.runtime_env(), &other.runtime_env()));
+        assert!(!ctx.copied_config().options().optimizer.prefer_hash_join);
+
+        // sort more rows than fit in the pool, keeping little memory aside to merge the spills
+        let ctx = SessionContext::new_with_config_rt(
+            ctx.copied_config().set_usize(
+                "datafusion.execution.sort_spill_reservation_bytes",
+                256 * 1024,
+            ),
+            ctx.runtime_env(),
+        );
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "id",
+            DataType::Int64,
+            false,
+        )]));
+        let batches = (0..50)
+            .map(|i| {
+                let ids = (0..8192).map(|j| -(i * 8192 + j)).collect::<Vec<i64>>();
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(ids))]).unwrap()
+            })
+            .collect();
+        ctx.register_table(
+            "t",
+            Arc::new(MemTable::try_new(schema, vec![batches]).unwrap()),
+        )
+        .unwrap();
+        let plan = ctx
+            .sql("SELECT id FROM t ORDER BY id")
+            .await
+            .unwrap()
+            .create_physical_plan()
+            .await
+            .unwrap();
+        let sorted = collect(plan.clone(), ctx.task_ctx()).await.unwrap();
+
+        fn spill_count(plan: &Arc<dyn ExecutionPlan>) -> usize {
+            let spill_count = plan.metrics().and_then(|metrics| metrics.spill_count());
+            spill_count.unwrap_or(0) + plan.children().into_iter().map(spill_count).sum::<usize>()
+        }
+        assert!(spill_count(&plan) > 0);
+        let ids = sorted
+            .iter()
+            .flat_map(|batch| {
+                let ids = batch.column(0).as_any().downcast_ref::<Int64Array>();
+                ids.unwrap().values().to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 50 * 8192);
+        assert!(ids.is_sorted());
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_deletion_vectors() {
+        let file_io = iceberg::io::FileIOBuilder::new("memory").build().unwrap();
+        let positions = roaring::RoaringTreemap::from_iter([1, 2, 7]);
+        let deletion_vectors = deletion_vector::write_deletion_vectors(
+            &file_io,
+            "memory:///deletes.puffin",
+            [("memory:///data.parquet", &positions)],
+        )
+        .await
+        .unwrap();
+        let request = |write_deletion_vectors| RewriteDeleteFilesRequest {
+            file_io: file_io.clone(),
+            schema: Arc::new(Schema::builder().build().unwrap()),
+            position_delete_files: vec![],
+            equality_delete_files: vec![],
+            deletion_vectors: deletion_vectors.clone(),
+            write_deletion_vectors,
+            delete_file_metadata: HashMap::new(),
+            config: Arc::new(crate::CompactionConfig::default()),
+            dir_path: "memory:///output".to_owned(),
+        };
+
+        let response = DataFusionExecutor::default()
+            .rewrite_delete_files(request(true))
+            .await
+            .unwrap();
+        assert!(response.delete_files.is_empty());
+        assert_eq!(response.deletion_vectors.len(), 1);
+        assert_eq!(
+            response.deletion_vectors[0].referenced_data_file,
+            "memory:///data.parquet"
+        );
+        assert_eq!(
+            deletion_vector::read_deletion_vector(&file_io, &response.deletion_vectors[0])
+                .await
+                .unwrap(),
+            positions
+        );
+
+        // a v2 table has no deletion vectors to merge them with position delete files
+        let result = DataFusionExecutor::default()
+            .rewrite_delete_files(request(false))
+            .await;
+        assert!(matches!(result, Err(CompactionError::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_equality_delete_files() {
+        use ::datafusion::arrow::array::{Int32Array, RecordBatch};
+        use iceberg::spec::{NestedField, PrimitiveType, Type};
+
+        let file_io = iceberg::io::FileIOBuilder::new("memory").build().unwrap();
+        let schema = Arc::new(
+            Schema::builder()
+                .with_fields(vec![
+                    Arc::new(NestedField::required(
+                        1,
+                        "id",
+                        Type::Primitive(PrimitiveType::Int),
+                    )),
+                    Arc::new(NestedField::optional(
+                        2,
+                        "name",
+                        Type::Primitive(PrimitiveType::String),
+                    )),
+                ])
+                .build()
+                .unwrap(),
+        );
+        let write_deletes = |ids: Vec<i32>, sequence_number: i64| {
+            let file_io = file_io.clone();
+            let schema = schema.clone();
+            async move {
+                let mut writer = equality_delete_writer(
+                    file_io,
+                    "memory:///deletes".to_owned(),
+                    DataFusionExecutor::delete_file_name_generator("10"),
+                    &schema,
+                    vec![1],
+                    Struct::empty(),
+                    0,
+                )
+                .await
+                .unwrap();
+                let batch = RecordBatch::try_new(
+                    Arc::new(
+                        iceberg::arrow::schema_to_arrow_schema(
+                            &equality_delete_schema(&schema, &[1]).unwrap(),
+                        )
+                        .unwrap(),
+                    ),
+                    vec![Arc::new(Int32Array::from(ids))],
+                )
+                .unwrap();
+                writer.write(batch).await.unwrap();
+                let delete_file = writer.close().await.unwrap().remove(0);
+                FileScanTask {
+                    start: 0,
+                    length: delete_file.file_size_in_bytes(),
+                    record_count: Some(delete_file.record_count()),
+                    data_file_path: delete_file.file_path().to_owned(),
+                    data_file_content: iceberg::spec::DataContentType::EqualityDeletes,
+                    data_file_format: DataFileFormat::Parquet,
+                    schema: schema.clone(),
+                    project_field_ids: vec![1],
+                    predicate: None,
+                    deletes: vec![],
+                    sequence_number,
+                    equality_ids: vec![1],
+                    file_size_in_bytes: delete_file.file_size_in_bytes(),
+                }
+            }
+        };
+        let equality_delete_files = vec![
+            write_deletes(vec![1, 2], 3).await,
+            write_deletes(vec![2, 3], 3).await,
+            write_deletes(vec![2], 5).await,
+        ];
+        let delete_file_metadata = equality_delete_files
+            .iter()
+            .map(|task| {
+                (
+                    task.data_file_path.clone(),
+                    DataFileMetadata {
+                        partition_spec_id: 0,
+                        partition: Some(Struct::empty()),
+                        sort_order_id: None,
+                    },
+                )
+            })
+            .collect();
+
+        let response = DataFusionExecutor::default()
+            .rewrite_delete_files(RewriteDeleteFilesRequest {
+                file_io: file_io.clone(),
+                schema,
+                position_delete_files: vec![],
+                equality_delete_files,
+                deletion_vectors: vec![],
+                write_deletion_vectors: false,
+                delete_file_metadata,
+                config: Arc::new(crate::CompactionConfig::default()),
+                dir_path: "memory:///output".to_owned(),
+            })
+            .await
+            .unwrap();
+
+        // the files of sequence number 3 are merged and deduplicated, the newer one is kept apart
+        let mut delete_files = response
+            .delete_files
+            .iter()
+            .map(|delete_file| {
+                (
+                    response.delete_file_sequence_numbers[delete_file.file_path()],
+                    delete_file.record_count(),
+                )
+            })
+            .collect::<Vec<_>>();
+        delete_files.sort_unstable();
+        assert_eq!(delete_files, vec![(3, 3), (5, 1)]);
+        assert!(response.delete_files.iter().all(|delete_file| {
+            delete_file.content_type() == iceberg::spec::DataContentType::EqualityDeletes
+                && delete_file.equality_ids() == [1]
+        }));
+        assert_eq!(response.stat.rewritten_files_count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_files_data_sequence_number() {
+        use ::datafusion::arrow::array::{Int32Array, RecordBatch};
+        use ::datafusion::parquet::arrow::ArrowWriter;
+        use iceberg::spec::{NestedField, PrimitiveType, Type};
+
+        let file_io = iceberg::io::FileIOBuilder::new("memory").build().unwrap();
+        let schema = Arc::new(
+            Schema::builder()
+                .with_fields(vec![Arc::new(NestedField::required(
+                    1,
+                    "id",
+                    Type::Primitive(PrimitiveType::Int),
+                ))])
+                .build()
+                .unwrap(),
+        );
+        let write_data_file = |path: &'static str, ids: Vec<i32>, sequence_number: i64| {
+            let file_io = file_io.clone();
+            let schema = schema.clone();
+            async move {
+                let arrow_schema =
+                    Arc::new(iceberg::arrow::schema_to_arrow_schema(&schema).unwrap());
+                let batch = RecordBatch::try_new(
+                    arrow_schema.clone(),
+                    vec![Arc::new(Int32Array::from(ids))],
+                )
+                .unwrap();
+                let mut writer = ArrowWriter::try_new(vec![], arrow_schema, None).unwrap();
+                writer.write(&batch).unwrap();
+                let bytes = writer.into_inner().unwrap();
+                let file_size_in_bytes = bytes.len() as u64;
+                file_io
+                    .new_output(path)
+                    .unwrap()
+                    .write(bytes.into())
+                    .await
+                    .unwrap();
+                FileScanTask {
+                    start: 0,
+                    length: file_size_in_bytes,
+                    record_count: Some(batch.num_rows() as u64),
+                    data_file_path: path.to_owned(),
+                    data_file_content: iceberg::spec::DataContentType::Data,
+                    data_file_format: DataFileFormat::Parquet,
+                    schema,
+                    project_field_ids: vec![1],
+                    predicate: None,
+                    deletes: vec![],
+                    sequence_number,
+                    equality_ids: vec![],
+                    file_size_in_bytes,
+                }
+            }
+        };
+        let data_files = vec![
+            write_data_file("memory:///data/a.parquet", vec![1, 2], 5).await,
+            write_data_file("memory:///data/b.parquet", vec![3], 3).await,
+        ];
+
+        let response = DataFusionExecutor::default()
+            .rewrite_files(RewriteFilesRequest {
+                file_io: file_io.clone(),
+                schema,
+                input_file_scan_tasks: InputFileScanTasks {
+                    data_files,
+                    position_delete_files: vec![],
+                    equality_delete_files: vec![],
+                    deletion_vectors: vec![],
+                    data_file_metadata: HashMap::new(),
+                    delete_file_metadata: HashMap::new(),
+                },
+                config: Arc::new(crate::CompactionConfig::default()),
+                dir_path: "memory:///output".to_owned(),
+                partition_spec: Arc::new(PartitionSpec::unpartition_spec()),
+                table_properties: HashMap::new(),
+                sort_order: None,
+            })
+            .await
+            .unwrap();
+
+        // the output takes the newest sequence number of the rewritten data files
+        assert_eq!(response.data_sequence_number, Some(5));
+        assert_eq!(
+            response
+                .data_files
+                .iter()
+                .map(|data_file| data_file.record_count())
+                .sum::<u64>(),
+            3
+        );
+    }
+}
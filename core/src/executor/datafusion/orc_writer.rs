@@ -0,0 +1,392 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::io::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use datafusion::arrow::array::{ArrayRef, RecordBatch};
+use datafusion::common::ScalarValue;
+use datafusion::functions_aggregate::min_max::{MaxAccumulator, MinAccumulator};
+use datafusion::logical_expr::Accumulator;
+use datafusion::parquet::arrow::PARQUET_FIELD_ID_META_KEY;
+use iceberg::arrow::schema_to_arrow_schema;
+use iceberg::io::{FileIO, FileWrite, OutputFile};
+use iceberg::spec::{
+    DataContentType, DataFileBuilder, DataFileFormat, Datum, PrimitiveType, Schema, Type,
+};
+use iceberg::writer::file_writer::location_generator::{FileNameGenerator, LocationGenerator};
+use iceberg::writer::file_writer::{FileWriter, FileWriterBuilder};
+use iceberg::{Error, ErrorKind};
+
+use crate::executor::bounds::truncate_bound;
+
+/// Builds [`OrcWriter`]s, the ORC counterpart of iceberg's `ParquetWriterBuilder`.
+#[derive(Clone)]
+pub(crate) struct OrcWriterBuilder<T: LocationGenerator, F: FileNameGenerator> {
+    schema: Arc<Schema>,
+    file_io: FileIO,
+    location_generator: T,
+    file_name_generator: F,
+}
+
+impl<T: LocationGenerator, F: FileNameGenerator> OrcWriterBuilder<T, F> {
+    pub(crate) fn new(
+        schema: Arc<Schema>,
+        file_io: FileIO,
+        location_generator: T,
+        file_name_generator: F,
+    ) -> Self {
+        Self {
+            schema,
+            file_io,
+            location_generator,
+            file_name_generator,
+        }
+    }
+}
+
+impl<T: LocationGenerator, F: FileNameGenerator> FileWriterBuilder for OrcWriterBuilder<T, F> {
+    type R = OrcWriter;
+
+    async fn build(self) -> iceberg::Result<Self::R> {
+        let arrow_schema = Arc::new(schema_to_arrow_schema(&self.schema)?);
+        let output_file = self.file_io.new_output(
+            self.location_generator
+                .generate_location(&self.file_name_generator.generate_file_name()),
+        )?;
+        let buffer = SharedBuffer::default();
+        let writer = orc_rust::ArrowWriterBuilder::new(buffer.clone(), arrow_schema)
+            .try_build()
+            .map_err(orc_error)?;
+        Ok(OrcWriter {
+            writer,
+            buffer,
+            output_file,
+            output: None,
+            file_size_in_bytes: 0,
+            record_count: 0,
+            metrics: HashMap::new(),
+            schema: self.schema,
+        })
+    }
+}
+
+/// Writes a single ORC data file.
+///
+/// The ORC writer needs a blocking `Write`, so each stripe is encoded into memory and uploaded
+/// through `FileIO` once the writer has flushed it. The output file is only created once rows
+/// are written.
+pub(crate) struct OrcWriter {
+    writer: orc_rust::ArrowWriter<SharedBuffer>,
+    buffer: SharedBuffer,
+    output_file: OutputFile,
+    output: Option<Box<dyn FileWrite>>,
+    file_size_in_bytes: u64,
+    record_count: u64,
+    /// Metrics of the top-level primitive columns, by field id
+    metrics: HashMap<i32, ColumnMetrics>,
+    schema: Arc<Schema>,
+}
+
+impl OrcWriter {
+    /// Uploads the bytes flushed by the ORC writer since the last upload.
+    async fn upload(&mut self) -> iceberg::Result<()> {
+        let bytes = self.buffer.take();
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        if self.output.is_none() {
+            self.output = Some(self.output_file.writer().await?);
+        }
+        self.file_size_in_bytes += bytes.len() as u64;
+        self.output.as_mut().unwrap().write(bytes.into()).await
+    }
+
+    fn update_metrics(&mut self, batch: &RecordBatch) -> iceberg::Result<()> {
+        for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+            let Some(field_id) = field
+                .metadata()
+                .get(PARQUET_FIELD_ID_META_KEY)
+                .and_then(|id| id.parse::<i32>().ok())
+            else {
+                continue;
+            };
+            let Some(Type::Primitive(primitive_type)) = self
+                .schema
+                .field_by_id(field_id)
+                .map(|field| &*field.field_type)
+            else {
+                continue;
+            };
+            let metrics = match self.metrics.entry(field_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(ColumnMetrics::try_new(primitive_type.clone(), column)?)
+                }
+            };
+            metrics.update(column)?;
+        }
+        Ok(())
+    }
+}
+
+impl FileWriter for OrcWriter {
+    async fn write(&mut self, batch: &RecordBatch) -> iceberg::Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        self.writer.write(batch).map_err(orc_error)?;
+        self.record_count += batch.num_rows() as u64;
+        self.update_metrics(batch)?;
+        self.upload().await
+    }
+
+    async fn close(mut self) -> iceberg::Result<Vec<DataFileBuilder>> {
+        self.writer.close().map_err(orc_error)?;
+        if self.record_count == 0 {
+            return Ok(vec![]);
+        }
+        self.upload().await?;
+        if let Some(mut output) = self.output.take() {
+            output.close().await?;
+        }
+
+        let mut value_counts = HashMap::new();
+        let mut null_value_counts = HashMap::new();
+        let mut lower_bounds = HashMap::new();
+        let mut upper_bounds = HashMap::new();
+        for (field_id, metrics) in self.metrics {
+            value_counts.insert(field_id, metrics.value_count);
+            null_value_counts.insert(field_id, metrics.null_value_count);
+            let (lower_bound, upper_bound) = metrics.bounds()?;
+            if let Some(lower_bound) = lower_bound {
+                lower_bounds.insert(field_id, lower_bound);
+            }
+            if let Some(upper_bound) = upper_bound {
+                upper_bounds.insert(field_id, upper_bound);
+            }
+        }
+        let mut builder = DataFileBuilder::default();
+        builder
+            .content(DataContentType::Data)
+            .file_path(self.output_file.location().to_owned())
+            .file_format(DataFileFormat::Orc)
+            .record_count(self.record_count)
+            .file_size_in_bytes(self.file_size_in_bytes)
+            .value_counts(value_counts)
+            .null_value_counts(null_value_counts)
+            .lower_bounds(lower_bounds)
+            .upper_bounds(upper_bounds);
+        Ok(vec![builder])
+    }
+}
+
+/// Value counts and bounds of a primitive column, accumulated over the written batches.
+struct ColumnMetrics {
+    primitive_type: PrimitiveType,
+    value_count: u64,
+    null_value_count: u64,
+    min: MinAccumulator,
+    max: MaxAccumulator,
+}
+
+impl ColumnMetrics {
+    fn try_new(primitive_type: PrimitiveType, column: &ArrayRef) -> iceberg::Result<Self> {
+        Ok(Self {
+            primitive_type,
+            value_count: 0,
+            null_value_count: 0,
+            min: MinAccumulator::try_new(column.data_type()).map_err(metrics_error)?,
+            max: MaxAccumulator::try_new(column.data_type()).map_err(metrics_error)?,
+        })
+    }
+
+    fn update(&mut self, column: &ArrayRef) -> iceberg::Result<()> {
+        self.value_count += column.len() as u64;
+        self.null_value_count += column.null_count() as u64;
+        self.min
+            .update_batch(std::slice::from_ref(column))
+            .map_err(metrics_error)?;
+        self.max
+            .update_batch(std::slice::from_ref(column))
+            .map_err(metrics_error)?;
+        Ok(())
+    }
+
+    /// Lower and upper bounds of the column, `None` if it only holds nulls or NaNs, or if its
+    /// type has no bounds.
+    fn bounds(mut self) -> iceberg::Result<(Option<Datum>, Option<Datum>)> {
+        let min = self.min.evaluate().map_err(metrics_error)?;
+        let max = self.max.evaluate().map_err(metrics_error)?;
+        let lower_bound = scalar_to_datum(&self.primitive_type, min)
+            .and_then(|bound| truncate_bound(&bound, true));
+        let upper_bound = scalar_to_datum(&self.primitive_type, max)
+            .and_then(|bound| truncate_bound(&bound, false));
+        Ok((lower_bound, upper_bound))
+    }
+}
+
+/// Converts the min or max of a column into a bound of a field of type `primitive_type`,
+/// `None` if null, NaN or not representable.
+fn scalar_to_datum(primitive_type: &PrimitiveType, value: ScalarValue) -> Option<Datum> {
+    match (primitive_type, value) {
+        (PrimitiveType::Boolean, ScalarValue::Boolean(v)) => v.map(Datum::bool),
+        (PrimitiveType::Int, ScalarValue::Int32(v)) => v.map(Datum::int),
+        (PrimitiveType::Long, ScalarValue::Int64(v)) => v.map(Datum::long),
+        (PrimitiveType::Float, ScalarValue::Float32(v)) => {
+            v.filter(|v| !v.is_nan()).map(Datum::float)
+        }
+        (PrimitiveType::Double, ScalarValue::Float64(v)) => {
+            v.filter(|v| !v.is_nan()).map(Datum::double)
+        }
+        (PrimitiveType::Date, ScalarValue::Date32(v)) => v.map(Datum::date),
+        (PrimitiveType::Timestamp, ScalarValue::TimestampMicrosecond(v, None)) => {
+            v.map(Datum::timestamp_micros)
+        }
+        (PrimitiveType::Timestamptz, ScalarValue::TimestampMicrosecond(v, Some(_))) => {
+            v.map(Datum::timestamptz_micros)
+        }
+        (PrimitiveType::String, ScalarValue::Utf8(v) | ScalarValue::LargeUtf8(v)) => {
+            v.map(Datum::string)
+        }
+        (PrimitiveType::Binary, ScalarValue::Binary(v) | ScalarValue::LargeBinary(v)) => {
+            v.map(Datum::binary)
+        }
+        _ => None,
+    }
+}
+
+fn orc_error(e: orc_rust::error::OrcError) -> Error {
+    Error::new(ErrorKind::Unexpected, "Failed to write ORC file").with_source(e)
+}
+
+fn metrics_error(e: datafusion::error::DataFusionError) -> Error {
+    Error::new(
+        ErrorKind::Unexpected,
+        "Failed to compute ORC column metrics",
+    )
+    .with_source(e)
+}
+
+/// In-memory sink shared between the ORC writer and [`OrcWriter`], which uploads what the
+/// writer flushed after each batch.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use iceberg::io::FileIOBuilder;
+    use iceberg::spec::{NestedField, Struct};
+    use iceberg::writer::file_writer::location_generator::{
+        DefaultFileNameGenerator, DefaultLocationGenerator,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_with_metrics() {
+        let file_io = FileIOBuilder::new("memory").build().unwrap();
+        let schema = Arc::new(
+            Schema::builder()
+                .with_fields(vec![
+                    Arc::new(NestedField::required(
+                        1,
+                        "id",
+                        Type::Primitive(PrimitiveType::Long),
+                    )),
+                    Arc::new(NestedField::optional(
+                        2,
+                        "name",
+                        Type::Primitive(PrimitiveType::String),
+                    )),
+                ])
+                .build()
+                .unwrap(),
+        );
+        let mut writer = OrcWriterBuilder::new(
+            schema.clone(),
+            file_io.clone(),
+            DefaultLocationGenerator {
+                dir_path: "memory:///data".to_owned(),
+            },
+            DefaultFileNameGenerator::new("test".to_owned(), None, DataFileFormat::Orc),
+        )
+        .build()
+        .await
+        .unwrap();
+        let arrow_schema = Arc::new(schema_to_arrow_schema(&schema).unwrap());
+        for (ids, names) in [
+            (vec![3, 5], vec![Some("b"), None]),
+            (vec![1], vec![Some("x")]),
+        ] {
+            let batch = RecordBatch::try_new(
+                arrow_schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(ids)),
+                    Arc::new(StringArray::from(names)),
+                ],
+            )
+            .unwrap();
+            writer.write(&batch).await.unwrap();
+        }
+        let mut builders = writer.close().await.unwrap();
+        assert_eq!(builders.len(), 1);
+        let data_file = builders[0]
+            .partition(Struct::empty())
+            .partition_spec_id(0)
+            .build()
+            .unwrap();
+
+        assert_eq!(data_file.record_count(), 3);
+        let file_size = file_io
+            .new_input(data_file.file_path())
+            .unwrap()
+            .metadata()
+            .await
+            .unwrap()
+            .size;
+        assert_eq!(data_file.file_size_in_bytes(), file_size);
+        assert_eq!(data_file.value_counts()[&1], 3);
+        assert_eq!(data_file.null_value_counts()[&2], 1);
+        assert_eq!(data_file.lower_bounds()[&1], Datum::long(1));
+        assert_eq!(data_file.upper_bounds()[&1], Datum::long(5));
+        assert_eq!(data_file.lower_bounds()[&2], Datum::string("b"));
+        assert_eq!(data_file.upper_bounds()[&2], Datum::string("x"));
+    }
+}
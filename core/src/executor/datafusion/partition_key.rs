@@ -0,0 +1,206 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::array::new_empty_array;
+use datafusion::arrow::datatypes::{DataType, SchemaRef as ArrowSchemaRef};
+use datafusion::common::{Column, DFSchema};
+use datafusion::error::Result as DFResult;
+use datafusion::logical_expr::{
+    ColumnarValue, Expr, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+};
+use datafusion::physical_expr::create_physical_expr;
+use datafusion::physical_expr::execution_props::ExecutionProps;
+use datafusion::physical_plan::PhysicalExpr;
+use iceberg::spec::{PartitionSpec, Schema, Transform};
+use iceberg::transform::create_transform_function;
+use iceberg_datafusion::to_datafusion_error;
+
+use crate::error::Result;
+
+/// Builds the expressions computing the partition value of a row, used to hash partition the
+/// rows so that each partition is written by a single writer.
+///
+/// Returns `None` if the spec is unpartitioned, or if a source column is not a top-level column
+/// of `output_schema`, in which case rows are not routed by partition.
+pub(crate) fn partition_key_exprs(
+    partition_spec: &PartitionSpec,
+    schema: &Schema,
+    output_schema: &ArrowSchemaRef,
+) -> Result<Option<Vec<Arc<dyn PhysicalExpr>>>> {
+    let mut exprs = vec![];
+    for field in partition_spec.fields() {
+        if field.transform == Transform::Void {
+            continue;
+        }
+        let Some(source) = schema.field_by_id(field.source_id) else {
+            return Ok(None);
+        };
+        if output_schema.index_of(&source.name).is_err() {
+            return Ok(None);
+        }
+        exprs.push(partition_field_expr(
+            field.transform,
+            &source.name,
+            output_schema,
+        )?);
+    }
+    if exprs.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(exprs))
+}
+
+/// Builds the expression applying `transform` to the column `source` of `output_schema`, the
+/// same expression for the scan declaring its partitioning and for the repartition of its rows.
+pub(crate) fn partition_field_expr(
+    transform: Transform,
+    source: &str,
+    output_schema: &ArrowSchemaRef,
+) -> DFResult<Arc<dyn PhysicalExpr>> {
+    let column = Expr::Column(Column::new_unqualified(source));
+    let expr = match transform {
+        Transform::Identity => column,
+        transform => {
+            ScalarUDF::new_from_impl(PartitionTransformUdf::new(transform)).call(vec![column])
+        }
+    };
+    let df_schema = DFSchema::try_from(output_schema.as_ref().clone())?;
+    create_physical_expr(&expr, &df_schema, &ExecutionProps::new())
+}
+
+/// Applies an iceberg partition transform to a column.
+#[derive(Debug)]
+struct PartitionTransformUdf {
+    /// Includes the transform, e.g. `iceberg_bucket[16]`, so that different transforms of the
+    /// same column are different expressions
+    name: String,
+    transform: Transform,
+    signature: Signature,
+}
+
+impl PartitionTransformUdf {
+    fn new(transform: Transform) -> Self {
+        Self {
+            name: format!("iceberg_{}", transform),
+            transform,
+            signature: Signature::any(1, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for PartitionTransformUdf {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> DFResult<DataType> {
+        let function = create_transform_function(&self.transform).map_err(to_datafusion_error)?;
+        let result = function
+            .transform(new_empty_array(&arg_types[0]))
+            .map_err(to_datafusion_error)?;
+        Ok(result.data_type().clone())
+    }
+
+    fn invoke_batch(&self, args: &[ColumnarValue], number_rows: usize) -> DFResult<ColumnarValue> {
+        let function = create_transform_function(&self.transform).map_err(to_datafusion_error)?;
+        let input = args[0].clone().into_array(number_rows)?;
+        let result = function.transform(input).map_err(to_datafusion_error)?;
+        Ok(ColumnarValue::Array(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{Field, Schema as ArrowSchema};
+    use iceberg::spec::{NestedField, PrimitiveType, Type};
+
+    fn schema() -> Schema {
+        Schema::builder()
+            .with_fields(vec![
+                Arc::new(NestedField::required(
+                    1,
+                    "id",
+                    Type::Primitive(PrimitiveType::Long),
+                )),
+                Arc::new(NestedField::required(
+                    2,
+                    "name",
+                    Type::Primitive(PrimitiveType::String),
+                )),
+            ])
+            .build()
+            .unwrap()
+    }
+
+    fn output_schema() -> ArrowSchemaRef {
+        Arc::new(ArrowSchema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]))
+    }
+
+    #[test]
+    fn test_partition_key_exprs() {
+        let schema = schema();
+
+        let unpartitioned = PartitionSpec::unpartition_spec();
+        assert!(
+            partition_key_exprs(&unpartitioned, &schema, &output_schema())
+                .unwrap()
+                .is_none()
+        );
+
+        let spec = PartitionSpec::builder(Arc::new(schema.clone()))
+            .add_partition_field("name", "name", Transform::Identity)
+            .unwrap()
+            .add_partition_field("id", "id_bucket", Transform::Bucket(16))
+            .unwrap()
+            .build()
+            .unwrap();
+        let exprs = partition_key_exprs(&spec, &schema, &output_schema())
+            .unwrap()
+            .unwrap();
+        assert_eq!(exprs.len(), 2);
+        assert_eq!(
+            exprs[1].data_type(&output_schema()).unwrap(),
+            DataType::Int32
+        );
+
+        // the source column is projected away
+        let output_schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "name",
+            DataType::Utf8,
+            false,
+        )]));
+        assert!(
+            partition_key_exprs(&spec, &schema, &output_schema)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,279 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use datafusion::arrow::array::{AsArray, BooleanArray, RecordBatch};
+use datafusion::arrow::compute::filter_record_batch;
+use datafusion::arrow::datatypes::Int64Type;
+use datafusion::error::{DataFusionError, Result as DFResult};
+use futures::TryStreamExt;
+use futures::future::try_join_all;
+use iceberg::arrow::ArrowReader;
+use iceberg::io::FileIO;
+use iceberg::scan::FileScanTask;
+use roaring::RoaringTreemap;
+
+use super::deletion_vector::read_deletion_vector;
+use super::file_reader::{build_arrow_reader, read_file_scan_task};
+use crate::executor::DeletionVector;
+
+/// Field id of the `file_path` column of position delete files
+pub(crate) const FILE_PATH_FIELD_ID: i32 = 2147483546;
+/// Field id of the `pos` column of position delete files, the reserved `_pos` column
+pub(crate) const POS_FIELD_ID: i32 = 2147483545;
+
+/// Positions of the deleted rows of each data file, loaded from the position delete files and
+/// the deletion vectors.
+///
+/// Rows are dropped while their data file is scanned, so position deletes need neither a join
+/// nor file path and position columns on the data rows.
+#[derive(Debug, Default)]
+pub(crate) struct PositionDeleteIndex {
+    deletes: HashMap<String, RoaringTreemap>,
+}
+
+impl PositionDeleteIndex {
+    /// Reads the position delete files and deletion vectors concurrently and indexes their
+    /// positions by data file.
+    pub(crate) async fn load(
+        file_io: &FileIO,
+        position_delete_files: Vec<FileScanTask>,
+        deletion_vectors: Vec<DeletionVector>,
+    ) -> DFResult<Self> {
+        let reader = build_arrow_reader(file_io);
+        let (files, vectors) = futures::future::try_join(
+            try_join_all(
+                position_delete_files
+                    .into_iter()
+                    .map(|task| Self::load_file(file_io, &reader, task)),
+            ),
+            try_join_all(
+                deletion_vectors
+                    .iter()
+                    .map(|deletion_vector| read_deletion_vector(file_io, deletion_vector)),
+            ),
+        )
+        .await?;
+        let mut index = Self::default();
+        for other in files {
+            index.merge(other);
+        }
+        for (deletion_vector, positions) in deletion_vectors.into_iter().zip(vectors) {
+            *index
+                .deletes
+                .entry(deletion_vector.referenced_data_file)
+                .or_default() |= positions;
+        }
+        Ok(index)
+    }
+
+    async fn load_file(
+        file_io: &FileIO,
+        reader: &ArrowReader,
+        task: FileScanTask,
+    ) -> DFResult<Self> {
+        let mut index = Self::default();
+        let mut batches = read_file_scan_task(file_io, reader, task).await?.batches;
+        while let Some(batch) = batches.try_next().await? {
+            index.add_batch(&batch)?;
+        }
+        Ok(index)
+    }
+
+    /// Adds the rows of a position delete file, whose first columns are `file_path` and `pos`.
+    fn add_batch(&mut self, batch: &RecordBatch) -> DFResult<()> {
+        let columns = (batch.num_columns() >= 2)
+            .then(|| {
+                batch
+                    .column(0)
+                    .as_string_opt::<i32>()
+                    .zip(batch.column(1).as_primitive_opt::<Int64Type>())
+            })
+            .flatten();
+        let Some((file_paths, positions)) = columns else {
+            return Err(DataFusionError::Execution(format!(
+                "position delete file must start with file_path and pos columns, got {}",
+                batch.schema()
+            )));
+        };
+        for (file_path, pos) in file_paths.iter().zip(positions.iter()) {
+            let (Some(file_path), Some(pos)) = (file_path, pos) else {
+                return Err(DataFusionError::Execution(
+                    "position delete file has a null file_path or pos".to_owned(),
+                ));
+            };
+            let pos = u64::try_from(pos).map_err(|_| {
+                DataFusionError::Execution(format!("negative position {} in position delete", pos))
+            })?;
+            // position delete files are sorted by file path, so the lookup mostly hits
+            match self.deletes.get_mut(file_path) {
+                Some(bitmap) => {
+                    bitmap.insert(pos);
+                }
+                None => {
+                    self.deletes
+                        .insert(file_path.to_owned(), RoaringTreemap::from_iter([pos]));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: Self) {
+        for (file_path, bitmap) in other.deletes {
+            *self.deletes.entry(file_path).or_default() |= bitmap;
+        }
+    }
+
+    /// Positions deleted from the data file at `file_path`, if any.
+    pub(crate) fn get(&self, file_path: &str) -> Option<&RoaringTreemap> {
+        self.deletes.get(file_path)
+    }
+
+    /// Deleted positions of every data file, ordered by file path.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &RoaringTreemap)> {
+        let mut deletes = self
+            .deletes
+            .iter()
+            .map(|(file_path, bitmap)| (file_path.as_str(), bitmap))
+            .collect::<Vec<_>>();
+        deletes.sort_by_key(|(file_path, _)| *file_path);
+        deletes.into_iter()
+    }
+}
+
+impl FromIterator<(String, RoaringTreemap)> for PositionDeleteIndex {
+    fn from_iter<T: IntoIterator<Item = (String, RoaringTreemap)>>(iter: T) -> Self {
+        let mut index = Self::default();
+        for (file_path, bitmap) in iter {
+            *index.deletes.entry(file_path).or_default() |= bitmap;
+        }
+        index
+    }
+}
+
+/// Drops the rows of `batch` whose position is in `deletes`, the first row being at
+/// `first_row_position` in its data file.
+pub(crate) fn remove_deleted_rows(
+    deletes: &RoaringTreemap,
+    batch: RecordBatch,
+    first_row_position: i64,
+) -> DFResult<RecordBatch> {
+    let num_rows = batch.num_rows() as u64;
+    if num_rows == 0 {
+        return Ok(batch);
+    }
+    let start = u64::try_from(first_row_position).map_err(|_| {
+        DataFusionError::Execution(format!(
+            "negative first row position {} of a batch",
+            first_row_position
+        ))
+    })?;
+    let end = start + num_rows - 1;
+    let deleted = deletes.rank(end) - start.checked_sub(1).map_or(0, |pos| deletes.rank(pos));
+    if deleted == 0 {
+        return Ok(batch);
+    }
+    let keep = (start..=end)
+        .map(|pos| Some(!deletes.contains(pos)))
+        .collect::<BooleanArray>();
+    Ok(filter_record_batch(&batch, &keep)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+
+    use super::*;
+
+    fn position_delete_batch(rows: &[(&str, i64)]) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("file_path", DataType::Utf8, false),
+            Field::new("pos", DataType::Int64, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|(file_path, _)| *file_path),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|(_, pos)| *pos),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_index_position_deletes() {
+        let mut index = PositionDeleteIndex::default();
+        index
+            .add_batch(&position_delete_batch(&[("a", 1), ("a", 3), ("b", 0)]))
+            .unwrap();
+        let mut other = PositionDeleteIndex::default();
+        other
+            .add_batch(&position_delete_batch(&[("a", 2), ("c", 5)]))
+            .unwrap();
+        index.merge(other);
+
+        assert_eq!(
+            index.get("a").unwrap().iter().collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(index.get("b").unwrap().iter().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(index.get("c").unwrap().iter().collect::<Vec<_>>(), vec![5]);
+        assert!(index.get("d").is_none());
+
+        assert!(
+            index
+                .add_batch(&position_delete_batch(&[("a", -1)]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_remove_deleted_rows() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int64Array::from(vec![10, 11, 12, 13]))],
+        )
+        .unwrap();
+        let deletes = RoaringTreemap::from_iter([1, 11, 13, 20]);
+
+        // rows at positions 10..14, as read by a split of the file
+        let batch = remove_deleted_rows(&deletes, batch, 10).unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<Int64Type>()
+                .values()
+                .to_vec(),
+            vec![10, 12]
+        );
+
+        // positions 2 and 3 are not deleted
+        let batch = remove_deleted_rows(&deletes, batch, 2).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+
+        assert!(remove_deleted_rows(&deletes, batch, -1).is_err());
+    }
+}
@@ -16,7 +16,9 @@
 
 use async_trait::async_trait;
 
-use super::{CompactionExecutor, RewriteFilesRequest, RewriteFilesResponse};
+use super::{
+    CompactionExecutor, RewriteDeleteFilesRequest, RewriteFilesRequest, RewriteFilesResponse,
+};
 use crate::error::Result;
 
 pub struct MockExecutor;
@@ -26,4 +28,11 @@ impl CompactionExecutor for MockExecutor {
     async fn rewrite_files(&self, _request: RewriteFilesRequest) -> Result<RewriteFilesResponse> {
         Ok(RewriteFilesResponse::default())
     }
+
+    async fn rewrite_delete_files(
+        &self,
+        _request: RewriteDeleteFilesRequest,
+    ) -> Result<RewriteFilesResponse> {
+        Ok(RewriteFilesResponse::default())
+    }
 }
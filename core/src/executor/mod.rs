@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use async_trait::async_trait;
@@ -22,28 +23,48 @@ use iceberg::{io::FileIO, spec::PartitionSpec};
 
 use crate::parser::proto::RewriteFilesResponseProtoEncoder;
 use crate::{config::CompactionConfig, parser::proto::PbRewriteFilesRequestDecoder};
-use iceberg::spec::{DataFile, Schema};
+use iceberg::spec::{DataFile, Schema, SortOrderRef, Struct};
 
+mod bounds;
 pub mod mock;
 pub use mock::MockExecutor;
 pub mod datafusion;
-use crate::error::Result;
+pub mod parquet_concat;
+pub use parquet_concat::ParquetConcatExecutor;
+pub mod registry;
+pub mod remote;
+use crate::error::{CompactionError, Result};
 use bergloom_codegen::compactor::RewriteFilesRequest as PbRewriteFilesRequest;
 use bergloom_codegen::compactor::RewriteFilesResponse as PbRewriteFilesResponse;
 pub use datafusion::DataFusionExecutor;
+pub use registry::ExecutorRegistry;
+pub use remote::RemoteCompactionExecutor;
 
 #[async_trait]
 pub trait CompactionExecutor: Send + Sync + 'static {
     async fn rewrite_files(&self, request: RewriteFilesRequest) -> Result<RewriteFilesResponse>;
 
+    /// Rewrites the delete files of a merge-on-read table into fewer delete files, returned in
+    /// the `delete_files` of the response.
+    ///
+    /// Executors that only rewrite data files keep the default, which fails as unsupported.
+    async fn rewrite_delete_files(
+        &self,
+        _request: RewriteDeleteFilesRequest,
+    ) -> Result<RewriteFilesResponse> {
+        Err(CompactionError::Iceberg(iceberg::Error::new(
+            iceberg::ErrorKind::FeatureUnsupported,
+            "this executor does not rewrite delete files",
+        )))
+    }
+
     async fn rewrite_file_proto(
         &self,
         request: PbRewriteFilesRequest,
     ) -> Result<PbRewriteFilesResponse> {
         let request = PbRewriteFilesRequestDecoder::new(request).decode()?;
         let response = self.rewrite_files(request).await?;
-        let response = RewriteFilesResponseProtoEncoder::new(response).encode();
-        Ok(response)
+        RewriteFilesResponseProtoEncoder::new(response).encode()
     }
 }
 
@@ -54,6 +75,32 @@ pub struct RewriteFilesRequest {
     pub config: Arc<CompactionConfig>,
     pub dir_path: String,
     pub partition_spec: Arc<PartitionSpec>,
+    /// Properties of the table, `write.format.default` selects the output file format.
+    pub table_properties: HashMap<String, String>,
+    /// Default sort order of the table, `None` if the table is unsorted or the order is unknown.
+    pub sort_order: Option<SortOrderRef>,
+}
+
+/// Request to merge delete files.
+///
+/// Position delete files are merged into one file per partition, sorted by data file path and
+/// position, or with `write_deletion_vectors` into one deletion vector per data file. Equality
+/// delete files are deduplicated into one file per partition, set of equality ids and sequence
+/// number, as the deletes of a file only apply to data files older than it.
+pub struct RewriteDeleteFilesRequest {
+    pub file_io: FileIO,
+    pub schema: Arc<Schema>,
+    pub position_delete_files: Vec<FileScanTask>,
+    pub equality_delete_files: Vec<FileScanTask>,
+    /// Deletion vectors merged with the position delete files, only in v3 tables
+    pub deletion_vectors: Vec<DeletionVector>,
+    /// Whether position deletes are written as deletion vectors of a Puffin file, as v3 tables
+    /// require, instead of position delete files
+    pub write_deletion_vectors: bool,
+    /// Metadata of every delete file by path, giving its partition
+    pub delete_file_metadata: HashMap<String, DataFileMetadata>,
+    pub config: Arc<CompactionConfig>,
+    pub dir_path: String,
 }
 
 #[derive(Debug, Clone)]
@@ -62,6 +109,46 @@ pub struct InputFileScanTasks {
     pub data_files: Vec<FileScanTask>,
     pub position_delete_files: Vec<FileScanTask>,
     pub equality_delete_files: Vec<FileScanTask>,
+    /// Deletion vectors of the data files, which replace position delete files in v3 tables.
+    pub deletion_vectors: Vec<DeletionVector>,
+    /// Metadata of the data files by path, which may be missing for some or all files.
+    pub data_file_metadata: HashMap<String, DataFileMetadata>,
+    /// Metadata of the delete files by path, which may be missing for some or all files.
+    pub delete_file_metadata: HashMap<String, DataFileMetadata>,
+}
+
+/// Metadata of a data file that is not carried by its [`FileScanTask`].
+#[derive(Debug, Clone)]
+pub struct DataFileMetadata {
+    /// Id of the partition spec the file was written with
+    pub partition_spec_id: i32,
+    /// Partition value of the file under its partition spec, if known
+    pub partition: Option<Struct>,
+    pub sort_order_id: Option<i32>,
+}
+
+impl DataFileMetadata {
+    /// Metadata of a data file listed in a manifest of the partition spec `partition_spec_id`.
+    pub fn from_data_file(data_file: &DataFile, partition_spec_id: i32) -> Self {
+        Self {
+            partition_spec_id,
+            partition: Some(data_file.partition().clone()),
+            sort_order_id: data_file.sort_order_id(),
+        }
+    }
+}
+
+/// A deletion vector, stored as a `deletion-vector-v1` blob of a Puffin file, holding the deleted
+/// positions of a single data file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletionVector {
+    pub referenced_data_file: String,
+    pub puffin_file_path: String,
+    /// Offset of the blob in the Puffin file
+    pub content_offset: u64,
+    pub content_size_in_bytes: u64,
+    /// Number of deleted positions
+    pub cardinality: u64,
 }
 
 impl InputFileScanTasks {
@@ -69,12 +156,25 @@ impl InputFileScanTasks {
         self.data_files.len() as u32
             + self.position_delete_files.len() as u32
             + self.equality_delete_files.len() as u32
+            + self.deletion_vectors.len() as u32
     }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct RewriteFilesResponse {
     pub data_files: Vec<DataFile>,
+    /// Delete files written by a rewrite of delete files
+    pub delete_files: Vec<DataFile>,
+    /// Data sequence numbers the equality delete files of `delete_files` must be committed with,
+    /// by path, the one of the files they merge. Committed with a newer sequence number, they
+    /// would also delete the matching rows of data files written after the original deletes.
+    /// Position delete files only reference older data files and take any sequence number.
+    pub delete_file_sequence_numbers: HashMap<String, i64>,
+    /// Deletion vectors written by a rewrite of delete files
+    pub deletion_vectors: Vec<DeletionVector>,
+    /// Data sequence number the data files must be committed with, the max of the rewritten data
+    /// files, so that equality deletes committed concurrently still apply to their rows
+    pub data_sequence_number: Option<i64>,
     pub stat: RewriteFilesStat,
 }
 
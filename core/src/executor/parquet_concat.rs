@@ -0,0 +1,836 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compaction of delete-free Parquet data files by concatenating their row groups.
+//!
+//! The column chunks of the input files are copied byte for byte into the output files, only the
+//! footer is written anew, so no page is decoded or compressed again. Page indexes and bloom
+//! filters of the inputs are not carried over.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::{Buf, Bytes};
+use datafusion::error::DataFusionError;
+use datafusion::parquet::column::writer::ColumnCloseResult;
+use datafusion::parquet::errors::{ParquetError, Result as ParquetResult};
+use datafusion::parquet::file::metadata::{ParquetMetaData, RowGroupMetaData};
+use datafusion::parquet::file::properties::WriterProperties;
+use datafusion::parquet::file::reader::{ChunkReader, Length};
+use datafusion::parquet::file::statistics::{Statistics, ValueStatistics};
+use datafusion::parquet::file::writer::SerializedFileWriter;
+use datafusion::parquet::schema::types::TypePtr;
+use iceberg::io::{FileIO, FileRead, FileWrite};
+use iceberg::spec::{
+    DataContentType, DataFile, DataFileBuilder, DataFileFormat, Datum, PartitionSpec,
+    PrimitiveType, Schema, Struct, Type,
+};
+use iceberg::writer::file_writer::location_generator::{
+    DefaultFileNameGenerator, DefaultLocationGenerator, FileNameGenerator, LocationGenerator,
+};
+use sqlx::types::Uuid;
+
+use super::bounds::truncate_bound;
+use super::datafusion::file_reader::{read_parquet_metadata, row_group_offset};
+use super::{
+    CompactionExecutor, DataFileMetadata, DataFusionExecutor, RewriteDeleteFilesRequest,
+    RewriteFilesRequest, RewriteFilesResponse, RewriteFilesStat,
+};
+use crate::config::ExecutorConfig;
+use crate::error::{CompactionError, Result};
+
+const DEFAULT_PREFIX: &str = "10";
+/// Table property giving the size output files are rolled at.
+const WRITE_TARGET_FILE_SIZE_BYTES: &str = "write.target-file-size-bytes";
+const DEFAULT_TARGET_FILE_SIZE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Executor concatenating the row groups of Parquet data files without decoding them.
+///
+/// Only requests without deletes, reading and writing Parquet into an unsorted table, are
+/// concatenated. The input files of a partition are concatenated together when their Parquet
+/// schemas are identical. Other requests are run by the [`DataFusionExecutor`].
+///
+/// Column chunks are read one at a time with ranged reads, and each row group is uploaded once
+/// copied, so that no more than a row group is held in memory.
+#[derive(Default)]
+pub struct ParquetConcatExecutor {
+    fallback: DataFusionExecutor,
+}
+
+impl ParquetConcatExecutor {
+    /// Creates an executor whose fallback runs with the memory settings of `config`.
+    pub fn from_config(config: &ExecutorConfig) -> Self {
+        Self {
+            fallback: DataFusionExecutor::from_config(config),
+        }
+    }
+}
+
+/// Input files of an output partition sharing the same Parquet schema.
+struct ConcatGroup {
+    partition: Struct,
+    parquet_schema: TypePtr,
+    /// Paths of the input files with their footer
+    files: Vec<(String, Arc<ParquetMetaData>)>,
+}
+
+#[async_trait]
+impl CompactionExecutor for ParquetConcatExecutor {
+    async fn rewrite_files(&self, request: RewriteFilesRequest) -> Result<RewriteFilesResponse> {
+        request.config.validate()?;
+        if !Self::can_concat(&request)? {
+            return self.fallback.rewrite_files(request).await;
+        }
+        let data_file_prefix = request
+            .config
+            .data_file_prefix
+            .clone()
+            .unwrap_or(DEFAULT_PREFIX.to_owned());
+        let target_file_size = Self::target_file_size(&request.table_properties)?;
+        let data_sequence_number = request
+            .input_file_scan_tasks
+            .data_files
+            .iter()
+            .map(|task| task.sequence_number)
+            .max();
+
+        let groups = Self::group_files(&request).await?;
+        let mut stat = RewriteFilesStat {
+            rewritten_files_count: groups.iter().map(|group| group.files.len() as u32).sum(),
+            ..Default::default()
+        };
+        let location_generator = DefaultLocationGenerator {
+            dir_path: request.dir_path.clone(),
+        };
+        let file_name_generator = DefaultFileNameGenerator::new(
+            data_file_prefix,
+            Some(Uuid::now_v7().to_string()),
+            DataFileFormat::Parquet,
+        );
+        let mut output_data_files = vec![];
+        for group in groups {
+            let mut writer: Option<ConcatFileWriter> = None;
+            for (path, metadata) in &group.files {
+                let reader = request.file_io.new_input(path)?.reader().await?;
+                for row_group in metadata.row_groups() {
+                    if writer
+                        .as_ref()
+                        .is_some_and(|writer| writer.bytes_written() >= target_file_size)
+                    {
+                        output_data_files.push(
+                            writer
+                                .take()
+                                .unwrap()
+                                .finish(&request.schema, &request.partition_spec)
+                                .await?,
+                        );
+                    }
+                    if writer.is_none() {
+                        let path = location_generator
+                            .generate_location(&file_name_generator.generate_file_name());
+                        writer = Some(ConcatFileWriter::new(&group, &request.file_io, path).await?);
+                    }
+                    writer
+                        .as_mut()
+                        .unwrap()
+                        .append_row_group(&reader, row_group)
+                        .await?;
+                }
+            }
+            if let Some(writer) = writer {
+                output_data_files.push(
+                    writer
+                        .finish(&request.schema, &request.partition_spec)
+                        .await?,
+                );
+            }
+        }
+
+        stat.added_files_count = output_data_files.len() as u32;
+        stat.rewritten_bytes = output_data_files
+            .iter()
+            .map(|f| f.file_size_in_bytes())
+            .sum();
+        Ok(RewriteFilesResponse {
+            data_files: output_data_files,
+            delete_files: vec![],
+            deletion_vectors: vec![],
+            delete_file_sequence_numbers: HashMap::new(),
+            data_sequence_number,
+            stat,
+        })
+    }
+
+    async fn rewrite_delete_files(
+        &self,
+        request: RewriteDeleteFilesRequest,
+    ) -> Result<RewriteFilesResponse> {
+        self.fallback.rewrite_delete_files(request).await
+    }
+}
+
+impl ParquetConcatExecutor {
+    /// Whether the data files of `request` can be concatenated, ignoring their Parquet schemas.
+    fn can_concat(request: &RewriteFilesRequest) -> Result<bool> {
+        let tasks = &request.input_file_scan_tasks;
+        if !tasks.position_delete_files.is_empty()
+            || !tasks.equality_delete_files.is_empty()
+            || !tasks.deletion_vectors.is_empty()
+            || request.sort_order.is_some()
+            || DataFusionExecutor::write_format(&request.table_properties)?
+                != DataFileFormat::Parquet
+        {
+            return Ok(false);
+        }
+        Ok(tasks.data_files.iter().all(|task| {
+            task.data_file_format == DataFileFormat::Parquet
+                && task.deletes.is_empty()
+                && tasks
+                    .data_file_metadata
+                    .get(&task.data_file_path)
+                    .and_then(|metadata| Self::output_partition(metadata, &request.partition_spec))
+                    .is_some()
+        }))
+    }
+
+    /// Partition of the output of a data file, `None` if it cannot be kept as is.
+    fn output_partition(
+        metadata: &DataFileMetadata,
+        partition_spec: &PartitionSpec,
+    ) -> Option<Struct> {
+        if metadata.partition_spec_id != partition_spec.spec_id() {
+            return None;
+        }
+        match &metadata.partition {
+            Some(partition) => Some(partition.clone()),
+            None if partition_spec.fields().is_empty() => Some(Struct::empty()),
+            None => None,
+        }
+    }
+
+    /// Reads the footers of the data files and groups them by partition and Parquet schema.
+    async fn group_files(request: &RewriteFilesRequest) -> Result<Vec<ConcatGroup>> {
+        let tasks = &request.input_file_scan_tasks;
+        let mut groups: Vec<ConcatGroup> = vec![];
+        let mut seen = HashSet::new();
+        for task in &tasks.data_files {
+            // splits of the same file are concatenated once
+            if !seen.insert(task.data_file_path.as_str()) {
+                continue;
+            }
+            let partition = tasks
+                .data_file_metadata
+                .get(&task.data_file_path)
+                .and_then(|metadata| Self::output_partition(metadata, &request.partition_spec))
+                .unwrap_or_else(Struct::empty);
+            let metadata = read_parquet_metadata(&request.file_io, &task.data_file_path).await?;
+            let parquet_schema = metadata.file_metadata().schema_descr().root_schema_ptr();
+            let file = (task.data_file_path.clone(), metadata);
+            match groups.iter_mut().find(|group| {
+                group.partition == partition && group.parquet_schema == parquet_schema
+            }) {
+                Some(group) => group.files.push(file),
+                None => groups.push(ConcatGroup {
+                    partition,
+                    parquet_schema,
+                    files: vec![file],
+                }),
+            }
+        }
+        Ok(groups)
+    }
+
+    fn target_file_size(table_properties: &HashMap<String, String>) -> Result<u64> {
+        match table_properties.get(WRITE_TARGET_FILE_SIZE_BYTES) {
+            Some(size) => size.parse().map_err(|e| {
+                CompactionError::InvalidInput(format!(
+                    "Invalid {}: {}",
+                    WRITE_TARGET_FILE_SIZE_BYTES, e
+                ))
+            }),
+            None => Ok(DEFAULT_TARGET_FILE_SIZE_BYTES),
+        }
+    }
+}
+
+/// Writes the row groups of files sharing a Parquet schema into a Parquet file, uploading each
+/// row group once written.
+struct ConcatFileWriter {
+    /// Buffers the bytes of the row group being written
+    writer: SerializedFileWriter<Vec<u8>>,
+    output: Box<dyn FileWrite>,
+    path: String,
+    partition: Struct,
+}
+
+impl ConcatFileWriter {
+    async fn new(group: &ConcatGroup, file_io: &FileIO, path: String) -> Result<Self> {
+        let key_value_metadata = group
+            .files
+            .first()
+            .and_then(|(_, metadata)| metadata.file_metadata().key_value_metadata().cloned());
+        let properties = WriterProperties::builder()
+            .set_key_value_metadata(key_value_metadata)
+            .build();
+        let writer =
+            SerializedFileWriter::new(vec![], group.parquet_schema.clone(), Arc::new(properties))
+                .map_err(parquet_error)?;
+        let output = file_io.new_output(&path)?.writer().await?;
+        Ok(Self {
+            writer,
+            output,
+            path,
+            partition: group.partition.clone(),
+        })
+    }
+
+    fn bytes_written(&self) -> u64 {
+        self.writer.bytes_written() as u64
+    }
+
+    /// Copies the column chunks of `row_group` from `file`, reading one column chunk at a time,
+    /// and uploads the copied row group.
+    async fn append_row_group(
+        &mut self,
+        file: &impl FileRead,
+        row_group: &RowGroupMetaData,
+    ) -> Result<()> {
+        let mut row_group_writer = self.writer.next_row_group().map_err(parquet_error)?;
+        for column in row_group.columns() {
+            let (start, length) = column.byte_range();
+            let column_chunk = ColumnChunkBytes {
+                offset: start,
+                bytes: file.read(start..start + length).await?,
+            };
+            let close_result = ColumnCloseResult {
+                bytes_written: column.compressed_size() as u64,
+                rows_written: row_group.num_rows() as u64,
+                metadata: column.clone(),
+                bloom_filter: None,
+                column_index: None,
+                offset_index: None,
+            };
+            row_group_writer
+                .append_column(&column_chunk, close_result)
+                .map_err(parquet_error)?;
+        }
+        row_group_writer.close().map_err(parquet_error)?;
+        self.flush().await
+    }
+
+    /// Uploads the bytes written since the last flush.
+    async fn flush(&mut self) -> Result<()> {
+        let bytes = std::mem::take(self.writer.inner_mut());
+        if !bytes.is_empty() {
+            self.output.write(Bytes::from(bytes)).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the footer, closes the file and builds its data file from the metadata of its
+    /// row groups.
+    async fn finish(mut self, schema: &Schema, partition_spec: &PartitionSpec) -> Result<DataFile> {
+        self.writer.finish().map_err(parquet_error)?;
+        self.flush().await?;
+        self.output.close().await?;
+        let row_groups = self
+            .writer
+            .flushed_row_groups()
+            .iter()
+            .map(|row_group| RowGroupMetaData::clone(row_group))
+            .collect::<Vec<_>>();
+        let mut builder = DataFileBuilder::default();
+        builder
+            .content(DataContentType::Data)
+            .file_path(self.path)
+            .file_format(DataFileFormat::Parquet)
+            .partition(self.partition)
+            .partition_spec_id(partition_spec.spec_id())
+            .file_size_in_bytes(self.writer.bytes_written() as u64);
+        set_metrics(&mut builder, &row_groups, schema);
+        builder
+            .build()
+            .map_err(|e| CompactionError::Execution(e.to_string()))
+    }
+}
+
+/// Bytes of a column chunk read from a Parquet file, addressed by their offset in the file.
+struct ColumnChunkBytes {
+    offset: u64,
+    bytes: Bytes,
+}
+
+impl ColumnChunkBytes {
+    fn slice(&self, start: u64, length: usize) -> ParquetResult<Bytes> {
+        let start = start
+            .checked_sub(self.offset)
+            .map(|start| start as usize)
+            .filter(|start| start + length <= self.bytes.len())
+            .ok_or_else(|| {
+                ParquetError::General(format!(
+                    "range {}..{} is outside of the column chunk",
+                    start,
+                    start + length as u64
+                ))
+            })?;
+        Ok(self.bytes.slice(start..start + length))
+    }
+}
+
+impl Length for ColumnChunkBytes {
+    fn len(&self) -> u64 {
+        self.offset + self.bytes.len() as u64
+    }
+}
+
+impl ChunkReader for ColumnChunkBytes {
+    type T = bytes::buf::Reader<Bytes>;
+
+    fn get_read(&self, start: u64) -> ParquetResult<Self::T> {
+        let length = (self.len().saturating_sub(start)) as usize;
+        Ok(self.slice(start, length)?.reader())
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> ParquetResult<Bytes> {
+        self.slice(start, length)
+    }
+}
+
+/// Metrics of a column summed over the row groups of a file.
+#[derive(Default)]
+struct ColumnMetrics {
+    size: u64,
+    value_count: u64,
+    null_value_count: Option<u64>,
+    /// `None` once the lower bound of a row group is unknown, without which that of the file is
+    lower_bound: Option<Option<Datum>>,
+    upper_bound: Option<Option<Datum>>,
+}
+
+impl ColumnMetrics {
+    /// Widens `bound` with the bound of a row group, `first` comparing before the other bound.
+    fn merge_bound(
+        bound: &mut Option<Option<Datum>>,
+        row_group_bound: Option<Datum>,
+        all_nulls: bool,
+        first: std::cmp::Ordering,
+    ) {
+        let Some(file_bound) = bound else {
+            return;
+        };
+        match row_group_bound {
+            Some(row_group_bound) => {
+                if file_bound
+                    .as_ref()
+                    .is_none_or(|file_bound| row_group_bound.partial_cmp(file_bound) == Some(first))
+                {
+                    *file_bound = Some(row_group_bound);
+                }
+            }
+            // a column chunk of nulls only has no bounds
+            None if all_nulls => {}
+            None => *bound = None,
+        }
+    }
+}
+
+/// Sets the record count, split offsets and column metrics of a data file from the statistics
+/// of its row groups.
+fn set_metrics(builder: &mut DataFileBuilder, row_groups: &[RowGroupMetaData], schema: &Schema) {
+    let mut metrics: HashMap<i32, ColumnMetrics> = HashMap::new();
+    let columns = row_groups
+        .first()
+        .map(|row_group| row_group.schema_descr().columns())
+        .unwrap_or_default();
+    for (column_idx, column_descr) in columns.iter().enumerate() {
+        let basic_info = column_descr.self_type().get_basic_info();
+        if !basic_info.has_id() {
+            continue;
+        }
+        // bounds are only kept for top level and struct fields, not for list or map elements
+        let primitive_type = match schema.field_by_id(basic_info.id()).map(|f| &*f.field_type) {
+            Some(Type::Primitive(primitive_type)) if column_descr.max_rep_level() == 0 => {
+                Some(primitive_type)
+            }
+            _ => None,
+        };
+        let column_metrics = metrics
+            .entry(basic_info.id())
+            .or_insert_with(|| ColumnMetrics {
+                null_value_count: Some(0),
+                lower_bound: Some(None),
+                upper_bound: Some(None),
+                ..Default::default()
+            });
+        for row_group in row_groups {
+            let column = row_group.column(column_idx);
+            column_metrics.size += column.compressed_size() as u64;
+            column_metrics.value_count += column.num_values() as u64;
+            let Some(statistics) = column.statistics() else {
+                column_metrics.null_value_count = None;
+                column_metrics.lower_bound = None;
+                column_metrics.upper_bound = None;
+                continue;
+            };
+            column_metrics.null_value_count = column_metrics
+                .null_value_count
+                .zip(statistics.null_count_opt())
+                .map(|(total, count)| total + count);
+            let Some(primitive_type) = primitive_type else {
+                continue;
+            };
+            let all_nulls = statistics.min_bytes_opt().is_none();
+            ColumnMetrics::merge_bound(
+                &mut column_metrics.lower_bound,
+                statistics_bound(primitive_type, statistics, true),
+                all_nulls,
+                std::cmp::Ordering::Less,
+            );
+            ColumnMetrics::merge_bound(
+                &mut column_metrics.upper_bound,
+                statistics_bound(primitive_type, statistics, false),
+                all_nulls,
+                std::cmp::Ordering::Greater,
+            );
+        }
+    }
+
+    let mut column_sizes = HashMap::new();
+    let mut value_counts = HashMap::new();
+    let mut null_value_counts = HashMap::new();
+    let mut lower_bounds = HashMap::new();
+    let mut upper_bounds = HashMap::new();
+    for (field_id, column_metrics) in metrics {
+        column_sizes.insert(field_id, column_metrics.size);
+        value_counts.insert(field_id, column_metrics.value_count);
+        if let Some(null_value_count) = column_metrics.null_value_count {
+            null_value_counts.insert(field_id, null_value_count);
+        }
+        if let Some(lower_bound) = column_metrics
+            .lower_bound
+            .flatten()
+            .and_then(|bound| truncate_bound(&bound, true))
+        {
+            lower_bounds.insert(field_id, lower_bound);
+        }
+        if let Some(upper_bound) = column_metrics
+            .upper_bound
+            .flatten()
+            .and_then(|bound| truncate_bound(&bound, false))
+        {
+            upper_bounds.insert(field_id, upper_bound);
+        }
+    }
+    builder
+        .record_count(
+            row_groups
+                .iter()
+                .map(|row_group| row_group.num_rows() as u64)
+                .sum(),
+        )
+        .split_offsets(
+            row_groups
+                .iter()
+                .map(|row_group| row_group_offset(row_group) as i64)
+                .collect(),
+        )
+        .column_sizes(column_sizes)
+        .value_counts(value_counts)
+        .null_value_counts(null_value_counts)
+        .lower_bounds(lower_bounds)
+        .upper_bounds(upper_bounds);
+}
+
+/// Converts the min, or the max if not `lower`, of Parquet statistics into a bound of a field
+/// of type `primitive_type`, `None` if unknown, not exact or not representable.
+///
+/// Writers truncate long byte array statistics, which are then no longer bounds of the values.
+fn statistics_bound(
+    primitive_type: &PrimitiveType,
+    statistics: &Statistics,
+    lower: bool,
+) -> Option<Datum> {
+    fn value<T>(statistics: &ValueStatistics<T>, lower: bool) -> Option<&T> {
+        if lower {
+            statistics.min_opt()
+        } else {
+            statistics.max_opt()
+        }
+    }
+
+    let is_exact = if lower {
+        statistics.min_is_exact()
+    } else {
+        statistics.max_is_exact()
+    };
+    if !is_exact {
+        return None;
+    }
+    match (primitive_type, statistics) {
+        (PrimitiveType::Boolean, Statistics::Boolean(s)) => {
+            value(s, lower).map(|v| Datum::bool(*v))
+        }
+        (PrimitiveType::Int, Statistics::Int32(s)) => value(s, lower).map(|v| Datum::int(*v)),
+        (PrimitiveType::Date, Statistics::Int32(s)) => value(s, lower).map(|v| Datum::date(*v)),
+        // the file may predate a promotion from int to long
+        (PrimitiveType::Long, Statistics::Int32(s)) => {
+            value(s, lower).map(|v| Datum::long(*v as i64))
+        }
+        (PrimitiveType::Long, Statistics::Int64(s)) => value(s, lower).map(|v| Datum::long(*v)),
+        (PrimitiveType::Timestamp, Statistics::Int64(s)) => {
+            value(s, lower).map(|v| Datum::timestamp_micros(*v))
+        }
+        (PrimitiveType::Timestamptz, Statistics::Int64(s)) => {
+            value(s, lower).map(|v| Datum::timestamptz_micros(*v))
+        }
+        (PrimitiveType::Float, Statistics::Float(s)) => value(s, lower)
+            .filter(|v| !v.is_nan())
+            .map(|v| Datum::float(*v)),
+        (PrimitiveType::Double, Statistics::Float(s)) => value(s, lower)
+            .filter(|v| !v.is_nan())
+            .map(|v| Datum::double(*v as f64)),
+        (PrimitiveType::Double, Statistics::Double(s)) => value(s, lower)
+            .filter(|v| !v.is_nan())
+            .map(|v| Datum::double(*v)),
+        (PrimitiveType::String, Statistics::ByteArray(s)) => value(s, lower)
+            .and_then(|v| std::str::from_utf8(v.data()).ok())
+            .map(Datum::string),
+        (PrimitiveType::Binary, Statistics::ByteArray(s)) => {
+            value(s, lower).map(|v| Datum::binary(v.data().to_vec()))
+        }
+        _ => None,
+    }
+}
+
+fn parquet_error(e: ParquetError) -> CompactionError {
+    DataFusionError::from(e).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{Int64Array, RecordBatch, StringArray};
+    use datafusion::parquet::arrow::ArrowWriter;
+    use iceberg::arrow::schema_to_arrow_schema;
+    use iceberg::io::FileIOBuilder;
+    use iceberg::scan::FileScanTask;
+    use iceberg::spec::NestedField;
+
+    use super::*;
+    use crate::config::CompactionConfig;
+    use crate::executor::InputFileScanTasks;
+
+    fn schema() -> Arc<Schema> {
+        Arc::new(
+            Schema::builder()
+                .with_fields(vec![
+                    Arc::new(NestedField::required(
+                        1,
+                        "id",
+                        Type::Primitive(PrimitiveType::Long),
+                    )),
+                    Arc::new(NestedField::optional(
+                        2,
+                        "name",
+                        Type::Primitive(PrimitiveType::String),
+                    )),
+                ])
+                .build()
+                .unwrap(),
+        )
+    }
+
+    async fn write_file(
+        file_io: &FileIO,
+        path: &str,
+        ids: Vec<i64>,
+        names: Vec<Option<&str>>,
+    ) -> FileScanTask {
+        let schema = schema();
+        let arrow_schema = Arc::new(schema_to_arrow_schema(&schema).unwrap());
+        let batch = RecordBatch::try_new(
+            arrow_schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(ids)),
+                Arc::new(StringArray::from(names)),
+            ],
+        )
+        .unwrap();
+        let mut writer = ArrowWriter::try_new(vec![], arrow_schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        let bytes = writer.into_inner().unwrap();
+        let file_size_in_bytes = bytes.len() as u64;
+        file_io
+            .new_output(path)
+            .unwrap()
+            .write(bytes.into())
+            .await
+            .unwrap();
+        FileScanTask {
+            start: 0,
+            length: file_size_in_bytes,
+            record_count: None,
+            data_file_path: path.to_owned(),
+            data_file_content: DataContentType::Data,
+            data_file_format: DataFileFormat::Parquet,
+            schema,
+            project_field_ids: vec![1, 2],
+            predicate: None,
+            deletes: vec![],
+            sequence_number: 1,
+            equality_ids: vec![],
+            file_size_in_bytes,
+        }
+    }
+
+    fn request(file_io: FileIO, data_files: Vec<FileScanTask>) -> RewriteFilesRequest {
+        let data_file_metadata = data_files
+            .iter()
+            .map(|task| {
+                (
+                    task.data_file_path.clone(),
+                    DataFileMetadata {
+                        partition_spec_id: 0,
+                        partition: None,
+                        sort_order_id: None,
+                    },
+                )
+            })
+            .collect();
+        RewriteFilesRequest {
+            file_io,
+            schema: schema(),
+            input_file_scan_tasks: InputFileScanTasks {
+                data_files,
+                position_delete_files: vec![],
+                equality_delete_files: vec![],
+                deletion_vectors: vec![],
+                data_file_metadata,
+                delete_file_metadata: HashMap::new(),
+            },
+            config: Arc::new(CompactionConfig::default()),
+            dir_path: "memory:///data".to_owned(),
+            partition_spec: Arc::new(PartitionSpec::unpartition_spec()),
+            table_properties: HashMap::new(),
+            sort_order: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concat_files() {
+        let file_io = FileIOBuilder::new("memory").build().unwrap();
+        let data_files = vec![
+            write_file(
+                &file_io,
+                "memory:///a.parquet",
+                vec![3, 5],
+                vec![Some("c"), None],
+            )
+            .await,
+            write_file(&file_io, "memory:///b.parquet", vec![1], vec![Some("x")]).await,
+        ];
+        let request = request(file_io.clone(), data_files);
+        assert!(ParquetConcatExecutor::can_concat(&request).unwrap());
+
+        let response = ParquetConcatExecutor::default()
+            .rewrite_files(request)
+            .await
+            .unwrap();
+        assert_eq!(response.stat.rewritten_files_count, 2);
+        assert_eq!(response.data_files.len(), 1);
+        assert_eq!(response.data_sequence_number, Some(1));
+        let data_file = &response.data_files[0];
+        assert_eq!(data_file.record_count(), 3);
+        assert_eq!(data_file.split_offsets().len(), 2);
+        assert_eq!(data_file.value_counts()[&1], 3);
+        assert_eq!(data_file.null_value_counts()[&2], 1);
+        assert_eq!(data_file.lower_bounds()[&1], Datum::long(1));
+        assert_eq!(data_file.upper_bounds()[&1], Datum::long(5));
+        assert_eq!(data_file.lower_bounds()[&2], Datum::string("c"));
+        assert_eq!(data_file.upper_bounds()[&2], Datum::string("x"));
+
+        let metadata = read_parquet_metadata(&file_io, data_file.file_path())
+            .await
+            .unwrap();
+        assert_eq!(metadata.file_metadata().num_rows(), 3);
+        assert_eq!(metadata.num_row_groups(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_can_concat() {
+        let file_io = FileIOBuilder::new("memory").build().unwrap();
+        let task = write_file(&file_io, "memory:///a.parquet", vec![1], vec![None]).await;
+
+        let mut sorted = request(file_io.clone(), vec![task.clone()]);
+        sorted.sort_order = Some(Arc::new(iceberg::spec::SortOrder::unsorted_order()));
+        assert!(!ParquetConcatExecutor::can_concat(&sorted).unwrap());
+
+        let mut orc = request(file_io.clone(), vec![task.clone()]);
+        orc.table_properties =
+            HashMap::from([("write.format.default".to_owned(), "orc".to_owned())]);
+        assert!(!ParquetConcatExecutor::can_concat(&orc).unwrap());
+
+        let mut with_deletes = request(file_io.clone(), vec![task.clone()]);
+        with_deletes
+            .input_file_scan_tasks
+            .position_delete_files
+            .push(task.clone());
+        assert!(!ParquetConcatExecutor::can_concat(&with_deletes).unwrap());
+
+        let mut other_spec = request(file_io, vec![task]);
+        for metadata in other_spec
+            .input_file_scan_tasks
+            .data_file_metadata
+            .values_mut()
+        {
+            metadata.partition_spec_id = 1;
+        }
+        assert!(!ParquetConcatExecutor::can_concat(&other_spec).unwrap());
+    }
+
+    #[test]
+    fn test_statistics_bound_requires_exact_values() {
+        use datafusion::parquet::data_type::ByteArray;
+
+        let statistics = |max_is_exact| {
+            Statistics::ByteArray(
+                ValueStatistics::new(
+                    Some(ByteArray::from("a")),
+                    Some(ByteArray::from("b")),
+                    None,
+                    Some(0),
+                    false,
+                )
+                .with_max_is_exact(max_is_exact),
+            )
+        };
+        let exact = statistics(true);
+        assert_eq!(
+            statistics_bound(&PrimitiveType::String, &exact, false),
+            Some(Datum::string("b"))
+        );
+        // a truncated max is below the values it was truncated from
+        let truncated = statistics(false);
+        assert_eq!(
+            statistics_bound(&PrimitiveType::String, &truncated, true),
+            Some(Datum::string("a"))
+        );
+        assert_eq!(
+            statistics_bound(&PrimitiveType::String, &truncated, false),
+            None
+        );
+    }
+}
@@ -0,0 +1,154 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{
+    CompactionExecutor, DataFusionExecutor, MockExecutor, ParquetConcatExecutor,
+    RemoteCompactionExecutor,
+};
+use crate::config::ExecutorConfig;
+use crate::error::{CompactionError, Result};
+
+/// Name of the [`DataFusionExecutor`], used when the config names no executor.
+pub const DATAFUSION_EXECUTOR: &str = "datafusion";
+/// Name of the [`MockExecutor`].
+pub const MOCK_EXECUTOR: &str = "mock";
+/// Name of the [`ParquetConcatExecutor`].
+pub const PARQUET_CONCAT_EXECUTOR: &str = "parquet_concat";
+/// Name of the [`RemoteCompactionExecutor`].
+pub const REMOTE_EXECUTOR: &str = "remote";
+
+/// Builds an executor from the executor config.
+pub type ExecutorFactory =
+    Arc<dyn Fn(&ExecutorConfig) -> Result<Box<dyn CompactionExecutor>> + Send + Sync>;
+
+/// Executors by the name `ExecutorConfig::name` selects them with.
+///
+/// The default registry holds the built-in executors that rewrite files, other engines are added
+/// with [`ExecutorRegistry::register`]. The [`MockExecutor`] writes nothing and is only available
+/// once registered explicitly.
+#[derive(Clone)]
+pub struct ExecutorRegistry {
+    factories: HashMap<String, ExecutorFactory>,
+}
+
+impl Default for ExecutorRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(DATAFUSION_EXECUTOR, |config| {
+            Ok(Box::new(DataFusionExecutor::from_config(config)))
+        });
+        registry.register(PARQUET_CONCAT_EXECUTOR, |config| {
+            Ok(Box::new(ParquetConcatExecutor::from_config(config)))
+        });
+        registry.register(REMOTE_EXECUTOR, |config| {
+            Ok(Box::new(RemoteCompactionExecutor::from_config(config)?))
+        });
+        registry
+    }
+}
+
+impl ExecutorRegistry {
+    /// Creates a registry without any executor.
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Registers the executor built by `factory` under `name`, replacing any executor of the
+    /// same name.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&ExecutorConfig) -> Result<Box<dyn CompactionExecutor>> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.factories.insert(name.into(), Arc::new(factory));
+        self
+    }
+
+    /// Names of the registered executors, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self
+            .factories
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    /// Builds the executor named by `config`, the [`DataFusionExecutor`] if none is.
+    pub fn build(&self, config: &ExecutorConfig) -> Result<Box<dyn CompactionExecutor>> {
+        let name = config.name.as_deref().unwrap_or(DATAFUSION_EXECUTOR);
+        let factory = self.factories.get(name).ok_or_else(|| {
+            CompactionError::Config(format!(
+                "Unknown executor {}, expected one of {:?}",
+                name,
+                self.names()
+            ))
+        })?;
+        factory(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let mut registry = ExecutorRegistry::default();
+        assert_eq!(
+            registry.names(),
+            vec![
+                DATAFUSION_EXECUTOR,
+                PARQUET_CONCAT_EXECUTOR,
+                REMOTE_EXECUTOR
+            ]
+        );
+        assert!(registry.build(&ExecutorConfig::default()).is_ok());
+
+        let config = |executor: &str| ExecutorConfig {
+            name: Some(executor.to_owned()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            registry.build(&config(MOCK_EXECUTOR)),
+            Err(CompactionError::Config(_))
+        ));
+        registry.register(MOCK_EXECUTOR, |_| Ok(Box::new(MockExecutor)));
+        assert!(registry.build(&config(MOCK_EXECUTOR)).is_ok());
+        // the remote executor needs endpoints
+        assert!(registry.build(&config(REMOTE_EXECUTOR)).is_err());
+        let remote = ExecutorConfig {
+            remote_endpoints: vec!["http://compactor-0:7777".to_owned()],
+            ..config(REMOTE_EXECUTOR)
+        };
+        assert!(registry.build(&remote).is_ok());
+        assert!(matches!(
+            registry.build(&config("spark")),
+            Err(CompactionError::Config(_))
+        ));
+
+        let mut registry = ExecutorRegistry::empty();
+        registry.register("spark", |_| Ok(Box::new(MockExecutor)));
+        assert!(registry.build(&config("spark")).is_ok());
+        assert!(registry.build(&ExecutorConfig::default()).is_err());
+    }
+}
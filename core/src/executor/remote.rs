@@ -0,0 +1,460 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Executor distributing rewrites across a pool of compactor services.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bergloom_codegen::compactor::RewriteFilesRequest as PbRewriteFilesRequest;
+use bergloom_codegen::compactor::RewriteFilesResponse as PbRewriteFilesResponse;
+use bergloom_codegen::compactor::compactor_service_client::CompactorServiceClient;
+use futures::{StreamExt, TryStreamExt};
+use iceberg::scan::FileScanTask;
+use iceberg::spec::{Schema, Struct};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+
+use super::{
+    CompactionExecutor, DataFusionExecutor, InputFileScanTasks, RewriteDeleteFilesRequest,
+    RewriteFilesRequest, RewriteFilesResponse,
+};
+use crate::config::ExecutorConfig;
+use crate::error::{CompactionError, Result};
+use crate::parser::proto::{PbRewriteFilesResponseDecoder, RewriteFilesRequestProtoEncoder};
+
+const DEFAULT_REMOTE_GROUP_SIZE: u64 = 1024 * 1024 * 1024;
+const DEFAULT_REMOTE_REQUEST_TIMEOUT_SECS: u64 = 60 * 60;
+const REMOTE_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Executor sending the rewrites to the `RewriteFiles` RPC of compactor services.
+///
+/// The data files of a request are split by partition into groups of about `group_size` bytes,
+/// each sent with the delete files of the request as a separate request, so that the groups are
+/// rewritten on several compactors at once. A group refused by a compactor that is unavailable or
+/// overloaded is retried on the next ones.
+///
+/// Rewrites of delete files have no RPC and run in-process.
+pub struct RemoteCompactionExecutor {
+    endpoints: Vec<RemoteEndpoint>,
+    group_size: u64,
+    local: DataFusionExecutor,
+}
+
+/// A compactor service, with the channel shared by all the requests sent to it.
+struct RemoteEndpoint {
+    address: String,
+    endpoint: Endpoint,
+    /// Opened on first use, as opening it needs a runtime
+    channel: OnceLock<Channel>,
+}
+
+impl RemoteEndpoint {
+    fn new(address: String, request_timeout: Duration) -> Result<Self> {
+        let endpoint = Endpoint::from_shared(address.clone())
+            .map_err(|e| {
+                CompactionError::Config(format!("invalid remote endpoint {}: {}", address, e))
+            })?
+            .connect_timeout(REMOTE_CONNECT_TIMEOUT)
+            .timeout(request_timeout);
+        Ok(Self {
+            address,
+            endpoint,
+            channel: OnceLock::new(),
+        })
+    }
+
+    fn client(&self) -> CompactorServiceClient<Channel> {
+        let channel = self
+            .channel
+            .get_or_init(|| self.endpoint.connect_lazy())
+            .clone();
+        CompactorServiceClient::new(channel)
+    }
+}
+
+#[async_trait]
+impl CompactionExecutor for RemoteCompactionExecutor {
+    async fn rewrite_files(&self, request: RewriteFilesRequest) -> Result<RewriteFilesResponse> {
+        request.config.validate()?;
+        let schema = request.schema.clone();
+        let groups = self
+            .split_request(request)
+            .into_iter()
+            .map(|group| RewriteFilesRequestProtoEncoder::new(group).encode())
+            .collect::<Result<Vec<_>>>()?;
+        let responses = futures::stream::iter(
+            groups
+                .into_iter()
+                .enumerate()
+                .map(|(group_idx, group)| self.rewrite_group(group_idx, group, schema.clone())),
+        )
+        .buffer_unordered(self.endpoints.len())
+        .try_collect::<Vec<_>>()
+        .await?;
+
+        let mut merged = RewriteFilesResponse::default();
+        for response in responses {
+            merged.data_files.extend(response.data_files);
+            merged.delete_files.extend(response.delete_files);
+            merged.deletion_vectors.extend(response.deletion_vectors);
+            merged
+                .delete_file_sequence_numbers
+                .extend(response.delete_file_sequence_numbers);
+            merged.data_sequence_number = merged
+                .data_sequence_number
+                .max(response.data_sequence_number);
+            merged.stat.rewritten_files_count += response.stat.rewritten_files_count;
+            merged.stat.added_files_count += response.stat.added_files_count;
+            merged.stat.rewritten_bytes += response.stat.rewritten_bytes;
+            merged.stat.failed_data_files_count += response.stat.failed_data_files_count;
+        }
+        Ok(merged)
+    }
+
+    async fn rewrite_delete_files(
+        &self,
+        request: RewriteDeleteFilesRequest,
+    ) -> Result<RewriteFilesResponse> {
+        self.local.rewrite_delete_files(request).await
+    }
+}
+
+impl RemoteCompactionExecutor {
+    /// Creates an executor sending rewrites to `endpoints`, each request failing once it runs
+    /// longer than `request_timeout`.
+    pub fn new(endpoints: Vec<String>, group_size: u64, request_timeout: Duration) -> Result<Self> {
+        if endpoints.is_empty() {
+            return Err(CompactionError::Config(
+                "remote executor requires at least one endpoint".to_owned(),
+            ));
+        }
+        if group_size == 0 {
+            return Err(CompactionError::Config(
+                "remote_group_size must be greater than 0".to_owned(),
+            ));
+        }
+        let endpoints = endpoints
+            .into_iter()
+            .map(|address| RemoteEndpoint::new(address, request_timeout))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            endpoints,
+            group_size,
+            local: DataFusionExecutor::default(),
+        })
+    }
+
+    /// Creates an executor sending rewrites to the `remote_endpoints` of `config`.
+    pub fn from_config(config: &ExecutorConfig) -> Result<Self> {
+        Ok(Self {
+            local: DataFusionExecutor::from_config(config),
+            ..Self::new(
+                config.remote_endpoints.clone(),
+                config
+                    .remote_group_size
+                    .unwrap_or(DEFAULT_REMOTE_GROUP_SIZE),
+                Duration::from_secs(
+                    config
+                        .remote_request_timeout_secs
+                        .unwrap_or(DEFAULT_REMOTE_REQUEST_TIMEOUT_SECS),
+                ),
+            )?
+        })
+    }
+
+    /// Splits the data files of `request` by partition into groups of about `group_size` bytes.
+    ///
+    /// Every group gets the delete files of the request, as they may apply to any data file, but
+    /// only the deletion vectors of its own data files.
+    fn split_request(&self, request: RewriteFilesRequest) -> Vec<RewriteFilesRequest> {
+        let RewriteFilesRequest {
+            file_io,
+            schema,
+            input_file_scan_tasks,
+            config,
+            dir_path,
+            partition_spec,
+            table_properties,
+            sort_order,
+        } = request;
+        let InputFileScanTasks {
+            data_files,
+            position_delete_files,
+            equality_delete_files,
+            deletion_vectors,
+            mut data_file_metadata,
+            delete_file_metadata,
+        } = input_file_scan_tasks;
+
+        let mut data_files_by_partition: HashMap<(i32, Option<Struct>), Vec<FileScanTask>> =
+            HashMap::new();
+        for task in data_files {
+            let key = data_file_metadata
+                .get(&task.data_file_path)
+                .map(|metadata| (metadata.partition_spec_id, metadata.partition.clone()))
+                .unwrap_or((partition_spec.spec_id(), None));
+            data_files_by_partition.entry(key).or_default().push(task);
+        }
+        let mut groups = vec![];
+        for tasks in data_files_by_partition.into_values() {
+            let mut group: Vec<FileScanTask> = vec![];
+            let mut group_size = 0;
+            for task in tasks {
+                if !group.is_empty() && group_size + task.length > self.group_size {
+                    groups.push(std::mem::take(&mut group));
+                    group_size = 0;
+                }
+                group_size += task.length;
+                group.push(task);
+            }
+            if !group.is_empty() {
+                groups.push(group);
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|data_files| {
+                let data_file_paths = data_files
+                    .iter()
+                    .map(|task| task.data_file_path.clone())
+                    .collect::<HashSet<_>>();
+                RewriteFilesRequest {
+                    file_io: file_io.clone(),
+                    schema: schema.clone(),
+                    input_file_scan_tasks: InputFileScanTasks {
+                        position_delete_files: position_delete_files.clone(),
+                        equality_delete_files: equality_delete_files.clone(),
+                        deletion_vectors: deletion_vectors
+                            .iter()
+                            .filter(|deletion_vector| {
+                                data_file_paths.contains(&deletion_vector.referenced_data_file)
+                            })
+                            .cloned()
+                            .collect(),
+                        data_file_metadata: data_file_paths
+                            .iter()
+                            .filter_map(|path| data_file_metadata.remove_entry(path))
+                            .collect(),
+                        delete_file_metadata: delete_file_metadata.clone(),
+                        data_files,
+                    },
+                    config: config.clone(),
+                    dir_path: dir_path.clone(),
+                    partition_spec: partition_spec.clone(),
+                    table_properties: table_properties.clone(),
+                    sort_order: sort_order.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Sends a group to a compactor, trying the next ones while the compactors refuse it.
+    async fn rewrite_group(
+        &self,
+        group_idx: usize,
+        request: PbRewriteFilesRequest,
+        schema: Arc<Schema>,
+    ) -> Result<RewriteFilesResponse> {
+        let mut last_status = None;
+        for attempt in 0..self.endpoints.len() {
+            let endpoint = &self.endpoints[(group_idx + attempt) % self.endpoints.len()];
+            match Self::send(endpoint, request.clone()).await {
+                Ok(response) => {
+                    return PbRewriteFilesResponseDecoder::new(response, schema).decode();
+                }
+                Err(status) if is_retryable_elsewhere(&status) => last_status = Some(status),
+                Err(status) => return Err(status_to_compaction_error(&endpoint.address, &status)),
+            }
+        }
+        let status = last_status.unwrap_or_else(|| Status::unavailable("no endpoint"));
+        let addresses = self
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.address.as_str())
+            .collect::<Vec<_>>();
+        Err(status_to_compaction_error(&addresses.join(","), &status))
+    }
+
+    async fn send(
+        endpoint: &RemoteEndpoint,
+        request: PbRewriteFilesRequest,
+    ) -> std::result::Result<PbRewriteFilesResponse, Status> {
+        Ok(endpoint.client().rewrite_files(request).await?.into_inner())
+    }
+}
+
+/// Whether a request failing with `status` may be sent to another compactor: the compactor could
+/// not be reached, or its admission control rejected the request before running it.
+///
+/// Other failures may leave the rewrite running or its files written, so sending the group again
+/// would rewrite it twice.
+fn is_retryable_elsewhere(status: &Status) -> bool {
+    matches!(status.code(), Code::Unavailable | Code::ResourceExhausted)
+}
+
+/// Converts the status returned by a compactor to a compaction error, the inverse of the
+/// mapping of the compactor service.
+fn status_to_compaction_error(endpoint: &str, status: &Status) -> CompactionError {
+    let message = format!("compactor {}: {}", endpoint, status.message());
+    match status.code() {
+        Code::InvalidArgument => CompactionError::InvalidInput(message),
+        Code::NotFound => CompactionError::NotFound(message),
+        Code::PermissionDenied => CompactionError::PermissionDenied(message),
+        Code::Unavailable | Code::ResourceExhausted => CompactionError::StorageThrottled(message),
+        Code::Aborted => CompactionError::CommitConflict(message),
+        Code::Cancelled => CompactionError::Cancelled(message),
+        _ => CompactionError::Execution(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg::io::FileIOBuilder;
+    use iceberg::spec::{DataContentType, DataFileFormat, PartitionSpec};
+
+    use super::*;
+    use crate::config::CompactionConfig;
+    use crate::executor::{DataFileMetadata, DeletionVector};
+
+    fn task(path: &str, length: u64, content: DataContentType) -> FileScanTask {
+        FileScanTask {
+            start: 0,
+            length,
+            record_count: None,
+            data_file_path: path.to_owned(),
+            data_file_content: content,
+            data_file_format: DataFileFormat::Parquet,
+            schema: Arc::new(Schema::builder().build().unwrap()),
+            project_field_ids: vec![],
+            predicate: None,
+            deletes: vec![],
+            sequence_number: 1,
+            equality_ids: vec![],
+            file_size_in_bytes: length,
+        }
+    }
+
+    #[test]
+    fn test_split_request() {
+        let data_files = vec![
+            task("a.parquet", 60, DataContentType::Data),
+            task("b.parquet", 60, DataContentType::Data),
+            task("c.parquet", 30, DataContentType::Data),
+            task("d.parquet", 10, DataContentType::Data),
+        ];
+        let data_file_metadata = data_files
+            .iter()
+            .map(|task| {
+                (
+                    task.data_file_path.clone(),
+                    DataFileMetadata {
+                        // d.parquet is in a partition of its own
+                        partition_spec_id: if task.data_file_path == "d.parquet" {
+                            1
+                        } else {
+                            0
+                        },
+                        partition: None,
+                        sort_order_id: None,
+                    },
+                )
+            })
+            .collect();
+        let request = RewriteFilesRequest {
+            file_io: FileIOBuilder::new("memory").build().unwrap(),
+            schema: Arc::new(Schema::builder().build().unwrap()),
+            input_file_scan_tasks: InputFileScanTasks {
+                data_files,
+                position_delete_files: vec![task(
+                    "pos.parquet",
+                    10,
+                    DataContentType::PositionDeletes,
+                )],
+                equality_delete_files: vec![],
+                deletion_vectors: vec![DeletionVector {
+                    referenced_data_file: "a.parquet".to_owned(),
+                    puffin_file_path: "dv.puffin".to_owned(),
+                    content_offset: 4,
+                    content_size_in_bytes: 10,
+                    cardinality: 1,
+                }],
+                data_file_metadata,
+                delete_file_metadata: HashMap::new(),
+            },
+            config: Arc::new(CompactionConfig::default()),
+            dir_path: "memory:///data".to_owned(),
+            partition_spec: Arc::new(PartitionSpec::unpartition_spec()),
+            table_properties: HashMap::new(),
+            sort_order: None,
+        };
+        let executor = RemoteCompactionExecutor::new(
+            vec!["http://a".to_owned()],
+            100,
+            Duration::from_secs(60),
+        )
+        .unwrap();
+
+        let mut groups = executor
+            .split_request(request)
+            .into_iter()
+            .map(|group| {
+                assert_eq!(group.input_file_scan_tasks.position_delete_files.len(), 1);
+                let mut paths = group
+                    .input_file_scan_tasks
+                    .data_files
+                    .iter()
+                    .map(|task| task.data_file_path.clone())
+                    .collect::<Vec<_>>();
+                paths.sort();
+                let deletion_vectors = group.input_file_scan_tasks.deletion_vectors.len();
+                (paths, deletion_vectors)
+            })
+            .collect::<Vec<_>>();
+        groups.sort();
+        assert_eq!(
+            groups,
+            vec![
+                (vec!["a.parquet".to_owned()], 1),
+                (vec!["b.parquet".to_owned(), "c.parquet".to_owned()], 0),
+                (vec!["d.parquet".to_owned()], 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_status_to_compaction_error() {
+        assert!(is_retryable_elsewhere(&Status::unavailable("down")));
+        assert!(is_retryable_elsewhere(&Status::resource_exhausted("busy")));
+        assert!(!is_retryable_elsewhere(&Status::invalid_argument("bad")));
+        // the request may still be running on the compactor
+        assert!(!is_retryable_elsewhere(&Status::deadline_exceeded("slow")));
+        assert!(!is_retryable_elsewhere(&Status::unknown("reset")));
+        assert!(matches!(
+            status_to_compaction_error("http://a", &Status::invalid_argument("bad")),
+            CompactionError::InvalidInput(_)
+        ));
+        assert!(
+            status_to_compaction_error("http://a", &Status::unavailable("down")).is_retryable()
+        );
+        let timeout = Duration::from_secs(60);
+        assert!(RemoteCompactionExecutor::new(vec![], 100, timeout).is_err());
+        assert!(RemoteCompactionExecutor::new(vec!["not a uri".to_owned()], 100, timeout).is_err());
+    }
+}
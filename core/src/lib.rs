@@ -23,6 +23,6 @@ pub mod error;
 pub mod executor;
 pub mod parser;
 
-pub use config::CompactionConfig;
+pub use config::{CompactionConfig, ExecutorConfig};
 pub use error::{CompactionError, Result};
 pub use executor::CompactionExecutor;
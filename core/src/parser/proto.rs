@@ -1,27 +1,40 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::error::Result;
 use bergloom_codegen::compactor::DataFile;
+use bergloom_codegen::compactor::DeletionVectorDescriptor;
 use bergloom_codegen::compactor::FileIoBuilder;
 use bergloom_codegen::compactor::FileScanTaskDescriptor;
 use bergloom_codegen::compactor::Literal;
 use bergloom_codegen::compactor::MapLiteral;
+use bergloom_codegen::compactor::MapType;
 use bergloom_codegen::compactor::NestedFieldDescriptor;
+use bergloom_codegen::compactor::NullOrder;
 use bergloom_codegen::compactor::OptionalLiteral;
+use bergloom_codegen::compactor::PartitionField;
 use bergloom_codegen::compactor::PartitionSpec;
 use bergloom_codegen::compactor::PrimitiveLiteral;
+use bergloom_codegen::compactor::PrimitiveType;
 use bergloom_codegen::compactor::RewriteFilesRequest as PbRewriteFilesRequest;
 use bergloom_codegen::compactor::RewriteFilesResponse as PbRewriteFilesResponse;
 use bergloom_codegen::compactor::RewriteFilesStat as PbRewriteFilesStat;
 use bergloom_codegen::compactor::SchemaDescriptor;
+use bergloom_codegen::compactor::SortDirection;
+use bergloom_codegen::compactor::SortField;
+use bergloom_codegen::compactor::SortOrder;
 use bergloom_codegen::compactor::StructLiteralDescriptor;
+use bergloom_codegen::compactor::StructType;
 use bergloom_codegen::compactor::Transform;
 use bergloom_codegen::compactor::literal;
 use bergloom_codegen::compactor::nested_field_descriptor::FieldType;
 use bergloom_codegen::compactor::primitive_literal::KindLiteral;
 use bergloom_codegen::compactor::primitive_literal::KindWithoutInnerLiteral;
+use bergloom_codegen::compactor::primitive_type::Decimal;
 use bergloom_codegen::compactor::primitive_type::Kind;
 use bergloom_codegen::compactor::primitive_type::KindWithoutInner;
+use bergloom_codegen::compactor::transform::Params;
+use bergloom_codegen::compactor::transform::TransformWithoutInner;
 use iceberg::spec::DataContentType;
 use iceberg::spec::NestedField;
 use iceberg::spec::Type;
@@ -29,21 +42,33 @@ use iceberg::{scan::FileScanTask, spec::Schema};
 
 use crate::CompactionConfig;
 use crate::CompactionError;
+use crate::ExecutorConfig;
+use crate::executor::DataFileMetadata;
+use crate::executor::DeletionVector;
 use crate::executor::InputFileScanTasks;
 use crate::executor::RewriteFilesRequest;
 use crate::executor::RewriteFilesResponse;
+use crate::executor::RewriteFilesStat;
 
 pub struct PbRewriteFilesRequestDecoder {
     rewrite_file_request_proto: PbRewriteFilesRequest,
+    default_config: Option<CompactionConfig>,
 }
 
 impl PbRewriteFilesRequestDecoder {
     pub fn new(rewrite_file_request_proto: PbRewriteFilesRequest) -> Self {
         Self {
             rewrite_file_request_proto,
+            default_config: None,
         }
     }
 
+    /// Sets the config that `rewrite_file_config` of the request is merged on top of.
+    pub fn with_default_config(mut self, default_config: CompactionConfig) -> Self {
+        self.default_config = Some(default_config);
+        self
+    }
+
     pub fn decode(self) -> Result<RewriteFilesRequest> {
         let PbRewriteFilesRequest {
             file_io_builder,
@@ -52,18 +77,41 @@ impl PbRewriteFilesRequestDecoder {
             dir_path,
             rewrite_file_config,
             partition_spec,
+            table_properties,
+            deletion_vectors,
+            sort_order,
         } = self.rewrite_file_request_proto;
+        let default_config = self.default_config;
         let file_io = Self::decode_file_io(
             file_io_builder
-                .ok_or_else(|| CompactionError::Config("file_io is required".to_owned()))?,
+                .ok_or_else(|| CompactionError::InvalidInput("file_io is required".to_owned()))?,
         )?;
-        let (input_file_scan_tasks, schema) = Self::decode_file_scan_tasks_and_schema(
+        // files without a partition spec id were written with the spec of the request
+        let partition_spec_id = partition_spec.as_ref().map_or(
+            iceberg::spec::PartitionSpec::unpartition_spec().spec_id(),
+            |partition_spec| partition_spec.spec_id,
+        );
+        let (mut input_file_scan_tasks, schema) = Self::decode_file_scan_tasks_and_schema(
             file_scan_task_descriptor,
-            schema.ok_or_else(|| CompactionError::Config("schema is required".to_owned()))?,
+            schema.ok_or_else(|| CompactionError::InvalidInput("schema is required".to_owned()))?,
+            partition_spec_id,
         )
         .map_err(|e| {
-            CompactionError::Config(format!("Failed to decode file scan tasks schema: {}", e))
+            CompactionError::InvalidInput(format!("Failed to decode file scan tasks schema: {}", e))
         })?;
+        input_file_scan_tasks.deletion_vectors = Self::decode_deletion_vectors(deletion_vectors)?;
+        // the executor settings are only taken from the compactor config: they decide where the
+        // files and the storage credentials of a request are sent, and which memory and disk of
+        // the compactor a request uses
+        if let Some(key) = ExecutorConfig::field_names()
+            .into_iter()
+            .find(|key| rewrite_file_config.contains_key(key))
+        {
+            return Err(CompactionError::InvalidInput(format!(
+                "{} cannot be set in rewrite_file_config",
+                key
+            )));
+        }
         let config = serde_json::from_value::<CompactionConfig>(
             serde_json::to_value(rewrite_file_config).map_err(|e| {
                 CompactionError::Config(format!(
@@ -75,9 +123,18 @@ impl PbRewriteFilesRequestDecoder {
         .map_err(|e| {
             CompactionError::Config(format!("Failed to decode CompactionConfig: {}", e))
         })?;
+        let config = match default_config {
+            Some(default_config) => default_config.merge(&config),
+            None => config,
+        };
+        config.validate()?;
 
         let partition_spec = Self::decode_partition_spec(partition_spec, schema.clone())?
             .unwrap_or_else(iceberg::spec::PartitionSpec::unpartition_spec);
+        let sort_order = sort_order
+            .map(|sort_order| Self::decode_sort_order(sort_order, &schema))
+            .transpose()?
+            .flatten();
 
         Ok(RewriteFilesRequest {
             file_io,
@@ -86,6 +143,8 @@ impl PbRewriteFilesRequestDecoder {
             config: Arc::new(config),
             dir_path,
             partition_spec: Arc::new(partition_spec),
+            table_properties,
+            sort_order,
         })
     }
 
@@ -101,17 +160,21 @@ impl PbRewriteFilesRequestDecoder {
     ///
     /// This function converts protobuf descriptors into Iceberg file scan tasks and schema.
     /// It handles different types of files: data files, position delete files, and equality delete files.
+    /// Files without a partition spec id get `partition_spec_id`, the spec of the request.
     pub fn decode_file_scan_tasks_and_schema(
         file_scan_task_descriptors: Vec<FileScanTaskDescriptor>,
         schema: SchemaDescriptor,
+        partition_spec_id: i32,
     ) -> Result<(InputFileScanTasks, Arc<Schema>)> {
         let mut data_files = vec![];
         let mut position_delete_files = vec![];
         let mut equality_delete_files = vec![];
+        let mut data_file_metadata = HashMap::new();
+        let mut delete_file_metadata = HashMap::new();
         let schema = Arc::new(Self::decode_schema(schema)?);
         for file_scan_task_descriptor in file_scan_task_descriptors {
             let mut file_scan_task = FileScanTask {
-                start: 0,
+                start: file_scan_task_descriptor.start,
                 length: file_scan_task_descriptor.length,
                 record_count: Some(file_scan_task_descriptor.record_count),
                 data_file_path: file_scan_task_descriptor.data_file_path,
@@ -120,7 +183,7 @@ impl PbRewriteFilesRequestDecoder {
                 )?,
                 data_file_format: Self::decode_data_file_format(
                     file_scan_task_descriptor.data_file_format,
-                ),
+                )?,
                 schema: schema.clone(),
                 project_field_ids: file_scan_task_descriptor.project_field_ids,
                 predicate: None,
@@ -129,16 +192,29 @@ impl PbRewriteFilesRequestDecoder {
                 equality_ids: file_scan_task_descriptor.equality_ids,
                 file_size_in_bytes: 0,
             };
+            let metadata = DataFileMetadata {
+                partition_spec_id: file_scan_task_descriptor
+                    .partition_spec_id
+                    .unwrap_or(partition_spec_id),
+                partition: file_scan_task_descriptor
+                    .partition
+                    .map(PbRewriteFilesResponseDecoder::decode_struct)
+                    .transpose()?,
+                sort_order_id: file_scan_task_descriptor.sort_order_id,
+            };
             match file_scan_task.data_file_content {
                 iceberg::spec::DataContentType::Data => {
+                    data_file_metadata.insert(file_scan_task.data_file_path.clone(), metadata);
                     data_files.push(file_scan_task);
                 }
                 iceberg::spec::DataContentType::PositionDeletes => {
                     file_scan_task.project_field_ids = vec![];
+                    delete_file_metadata.insert(file_scan_task.data_file_path.clone(), metadata);
                     position_delete_files.push(file_scan_task);
                 }
                 iceberg::spec::DataContentType::EqualityDeletes => {
                     file_scan_task.project_field_ids = file_scan_task.equality_ids.clone();
+                    delete_file_metadata.insert(file_scan_task.data_file_path.clone(), metadata);
                     equality_delete_files.push(file_scan_task);
                 }
             }
@@ -148,11 +224,49 @@ impl PbRewriteFilesRequestDecoder {
                 data_files,
                 position_delete_files,
                 equality_delete_files,
+                deletion_vectors: vec![],
+                data_file_metadata,
+                delete_file_metadata,
             },
             schema,
         ))
     }
 
+    /// Decodes the deletion vectors of the data files from protobuf descriptors
+    fn decode_deletion_vectors(
+        deletion_vectors: Vec<DeletionVectorDescriptor>,
+    ) -> Result<Vec<DeletionVector>> {
+        deletion_vectors
+            .into_iter()
+            .map(|deletion_vector| {
+                if deletion_vector.referenced_data_file.is_empty()
+                    || deletion_vector.puffin_file_path.is_empty()
+                {
+                    return Err(CompactionError::InvalidInput(
+                        "deletion vector requires a data file and a puffin file".to_owned(),
+                    ));
+                }
+                if deletion_vector
+                    .content_offset
+                    .checked_add(deletion_vector.content_size_in_bytes)
+                    .is_none()
+                {
+                    return Err(CompactionError::InvalidInput(format!(
+                        "deletion vector of {} overflows its puffin file",
+                        deletion_vector.referenced_data_file
+                    )));
+                }
+                Ok(DeletionVector {
+                    referenced_data_file: deletion_vector.referenced_data_file,
+                    puffin_file_path: deletion_vector.puffin_file_path,
+                    content_offset: deletion_vector.content_offset,
+                    content_size_in_bytes: deletion_vector.content_size_in_bytes,
+                    cardinality: deletion_vector.cardinality,
+                })
+            })
+            .collect()
+    }
+
     /// decode an Iceberg schema from a protobuf schema descriptor
     fn decode_schema(schema: SchemaDescriptor) -> Result<Schema> {
         let iceberg_schema_builder = Schema::builder();
@@ -164,7 +278,13 @@ impl PbRewriteFilesRequestDecoder {
                 Ok::<Arc<NestedField>, CompactionError>(Arc::new(iceberg_field))
             })
             .collect::<Result<Vec<_>>>()?;
-        Ok(iceberg_schema_builder.with_fields(fields).build()?)
+        let schema_id = i32::try_from(schema.schema_id).map_err(|_| {
+            CompactionError::InvalidInput(format!("schema id {} out of range", schema.schema_id))
+        })?;
+        Ok(iceberg_schema_builder
+            .with_schema_id(schema_id)
+            .with_fields(fields)
+            .build()?)
     }
 
     /// Builds an Iceberg nested field from a protobuf field descriptor
@@ -279,12 +399,15 @@ impl PbRewriteFilesRequestDecoder {
     }
 
     /// Converts a protobuf data file format to an Iceberg data file format
-    fn decode_data_file_format(data_file_format: i32) -> iceberg::spec::DataFileFormat {
+    fn decode_data_file_format(data_file_format: i32) -> Result<iceberg::spec::DataFileFormat> {
         match data_file_format {
-            0 => iceberg::spec::DataFileFormat::Avro,
-            1 => iceberg::spec::DataFileFormat::Orc,
-            2 => iceberg::spec::DataFileFormat::Parquet,
-            _ => unreachable!(),
+            0 => Ok(iceberg::spec::DataFileFormat::Avro),
+            1 => Ok(iceberg::spec::DataFileFormat::Orc),
+            2 => Ok(iceberg::spec::DataFileFormat::Parquet),
+            _ => Err(CompactionError::InvalidInput(format!(
+                "unknown data file format: {}",
+                data_file_format
+            ))),
         }
     }
 
@@ -375,6 +498,54 @@ impl PbRewriteFilesRequestDecoder {
             )),
         }
     }
+
+    /// Builds an Iceberg sort order from a protobuf SortOrder, `None` if it sorts by nothing
+    fn decode_sort_order(
+        sort_order: SortOrder,
+        schema: &Schema,
+    ) -> Result<Option<iceberg::spec::SortOrderRef>> {
+        if sort_order.fields.is_empty() {
+            return Ok(None);
+        }
+        let fields = sort_order
+            .fields
+            .into_iter()
+            .map(|field| {
+                if schema.field_by_id(field.source_id).is_none() {
+                    return Err(CompactionError::InvalidInput(format!(
+                        "sort field source id {} not found in schema",
+                        field.source_id
+                    )));
+                }
+                let direction = match SortDirection::try_from(field.direction).map_err(|e| {
+                    CompactionError::InvalidInput(format!("failed to parse sort direction: {}", e))
+                })? {
+                    SortDirection::Ascending => iceberg::spec::SortDirection::Ascending,
+                    SortDirection::Descending => iceberg::spec::SortDirection::Descending,
+                };
+                let null_order = match NullOrder::try_from(field.null_order).map_err(|e| {
+                    CompactionError::InvalidInput(format!("failed to parse null order: {}", e))
+                })? {
+                    NullOrder::NullsFirst => iceberg::spec::NullOrder::First,
+                    NullOrder::NullsLast => iceberg::spec::NullOrder::Last,
+                };
+                Ok(iceberg::spec::SortField {
+                    source_id: field.source_id,
+                    transform: Self::decode_transform(&field.transform.ok_or_else(|| {
+                        CompactionError::InvalidInput(
+                            "cannot find transform from sort_field".to_owned(),
+                        )
+                    })?)?,
+                    direction,
+                    null_order,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(Arc::new(iceberg::spec::SortOrder {
+            order_id: sort_order.order_id,
+            fields,
+        })))
+    }
 }
 
 pub struct RewriteFilesResponseProtoEncoder {
@@ -388,21 +559,45 @@ impl RewriteFilesResponseProtoEncoder {
         }
     }
 
-    pub fn encode(self) -> PbRewriteFilesResponse {
-        let RewriteFilesResponse { data_files, stat } = self.rewrite_files_response;
-        let data_files = data_files.into_iter().map(Self::encode_data_file).collect();
+    pub fn encode(self) -> Result<PbRewriteFilesResponse> {
+        let RewriteFilesResponse {
+            data_files,
+            delete_files,
+            deletion_vectors,
+            delete_file_sequence_numbers,
+            data_sequence_number,
+            stat,
+        } = self.rewrite_files_response;
+        let data_files = data_files
+            .into_iter()
+            .map(Self::encode_data_file)
+            .collect::<Result<Vec<_>>>()?;
+        let delete_files = delete_files
+            .into_iter()
+            .map(Self::encode_data_file)
+            .collect::<Result<Vec<_>>>()?;
         let stat = Some(PbRewriteFilesStat {
             rewritten_files_count: stat.rewritten_files_count,
             added_files_count: stat.added_files_count,
             rewritten_bytes: stat.rewritten_bytes,
             failed_data_files_count: stat.failed_data_files_count,
         });
-        PbRewriteFilesResponse { data_files, stat }
+        Ok(PbRewriteFilesResponse {
+            data_files,
+            stat,
+            delete_files,
+            data_sequence_number,
+            deletion_vectors: deletion_vectors
+                .into_iter()
+                .map(RewriteFilesRequestProtoEncoder::encode_deletion_vector)
+                .collect(),
+            delete_file_sequence_numbers,
+        })
     }
 
     /// Converts an Iceberg data file to a protobuf DataFile
-    pub fn encode_data_file(data_file: iceberg::spec::DataFile) -> DataFile {
-        DataFile {
+    pub fn encode_data_file(data_file: iceberg::spec::DataFile) -> Result<DataFile> {
+        Ok(DataFile {
             content: data_file.content_type() as i32,
             file_path: data_file.file_path().to_owned(),
             file_format: Self::encode_data_file_format(data_file.file_format()),
@@ -413,24 +608,22 @@ impl RewriteFilesResponseProtoEncoder {
             value_counts: data_file.value_counts().clone(),
             null_value_counts: data_file.null_value_counts().clone(),
             nan_value_counts: data_file.nan_value_counts().clone(),
-            lower_bounds: data_file
-                .lower_bounds()
-                .clone()
-                .into_iter()
-                .map(|(k, v)| (k, v.to_bytes().unwrap().into_vec()))
-                .collect(),
-            upper_bounds: data_file
-                .lower_bounds()
-                .clone()
-                .into_iter()
-                .map(|(k, v)| (k, v.to_bytes().unwrap().into_vec()))
-                .collect(),
+            lower_bounds: Self::encode_bounds(data_file.lower_bounds())?,
+            upper_bounds: Self::encode_bounds(data_file.upper_bounds())?,
             key_metadata: data_file.key_metadata().map(|k| k.to_vec()),
             split_offsets: data_file.split_offsets().to_vec(),
             equality_ids: data_file.equality_ids().to_vec(),
             sort_order_id: data_file.sort_order_id(),
-            partition_spec_id: 0,
-        }
+            partition_spec_id: data_file.partition_spec_id(),
+        })
+    }
+
+    /// Converts the column bounds of a data file to their binary form
+    fn encode_bounds(bounds: &HashMap<i32, iceberg::spec::Datum>) -> Result<HashMap<i32, Vec<u8>>> {
+        bounds
+            .iter()
+            .map(|(field_id, datum)| Ok((*field_id, datum.to_bytes()?.into_vec())))
+            .collect()
     }
 
     /// Converts an Iceberg data file format to a protobuf data file format
@@ -546,10 +739,495 @@ impl RewriteFilesResponseProtoEncoder {
     }
 }
 
+/// Encodes a rewrite request into the protobuf request of a compactor service.
+pub struct RewriteFilesRequestProtoEncoder {
+    rewrite_files_request: RewriteFilesRequest,
+}
+
+impl RewriteFilesRequestProtoEncoder {
+    pub fn new(rewrite_files_request: RewriteFilesRequest) -> Self {
+        Self {
+            rewrite_files_request,
+        }
+    }
+
+    pub fn encode(self) -> Result<PbRewriteFilesRequest> {
+        let RewriteFilesRequest {
+            file_io,
+            schema,
+            input_file_scan_tasks,
+            config,
+            dir_path,
+            partition_spec,
+            table_properties,
+            sort_order,
+        } = self.rewrite_files_request;
+        let InputFileScanTasks {
+            data_files,
+            position_delete_files,
+            equality_delete_files,
+            deletion_vectors,
+            data_file_metadata,
+            delete_file_metadata,
+        } = input_file_scan_tasks;
+        let file_scan_task_descriptor = data_files
+            .into_iter()
+            .chain(position_delete_files)
+            .chain(equality_delete_files)
+            .map(|task| {
+                let metadata = data_file_metadata
+                    .get(&task.data_file_path)
+                    .or_else(|| delete_file_metadata.get(&task.data_file_path));
+                let metadata = metadata.cloned().unwrap_or(DataFileMetadata {
+                    partition_spec_id: partition_spec.spec_id(),
+                    partition: None,
+                    sort_order_id: None,
+                });
+                Self::encode_file_scan_task(task, metadata)
+            })
+            .collect();
+        Ok(PbRewriteFilesRequest {
+            file_scan_task_descriptor,
+            rewrite_file_config: Self::encode_config(&config)?,
+            dir_path,
+            file_io_builder: Some(Self::encode_file_io(file_io)),
+            schema: Some(Self::encode_schema(&schema)),
+            partition_spec: Some(Self::encode_partition_spec(&partition_spec)),
+            table_properties,
+            deletion_vectors: deletion_vectors
+                .into_iter()
+                .map(Self::encode_deletion_vector)
+                .collect(),
+            sort_order: sort_order.map(|sort_order| Self::encode_sort_order(&sort_order)),
+        })
+    }
+
+    fn encode_file_scan_task(
+        task: FileScanTask,
+        metadata: DataFileMetadata,
+    ) -> FileScanTaskDescriptor {
+        FileScanTaskDescriptor {
+            start: task.start,
+            length: task.length,
+            record_count: task.record_count.unwrap_or_default(),
+            data_file_path: task.data_file_path,
+            data_file_content: task.data_file_content as i32,
+            data_file_format: RewriteFilesResponseProtoEncoder::encode_data_file_format(
+                task.data_file_format,
+            ),
+            project_field_ids: task.project_field_ids,
+            sequence_number: task.sequence_number,
+            equality_ids: task.equality_ids,
+            partition_spec_id: Some(metadata.partition_spec_id),
+            partition: metadata
+                .partition
+                .map(RewriteFilesResponseProtoEncoder::encode_struct),
+            sort_order_id: metadata.sort_order_id,
+        }
+    }
+
+    /// Encodes the set fields of a config as strings, lists being comma separated.
+    fn encode_config(config: &CompactionConfig) -> Result<HashMap<String, String>> {
+        let fields = match serde_json::to_value(config) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            Ok(value) => {
+                return Err(CompactionError::Config(format!(
+                    "CompactionConfig encoded as {} instead of an object",
+                    value
+                )));
+            }
+            Err(e) => {
+                return Err(CompactionError::Config(format!(
+                    "Failed to encode CompactionConfig: {}",
+                    e
+                )));
+            }
+        };
+        Ok(fields
+            .into_iter()
+            .filter_map(|(key, value)| match value {
+                serde_json::Value::Null => None,
+                serde_json::Value::String(value) => Some((key, value)),
+                value => Some((key, value.to_string())),
+            })
+            .collect())
+    }
+
+    fn encode_file_io(file_io: iceberg::io::FileIO) -> FileIoBuilder {
+        let (scheme, props) = file_io.into_builder().into_parts();
+        FileIoBuilder {
+            // decoded with `FileIO::from_path`, which takes the scheme from a location
+            scheme_str: format!("{}://", scheme),
+            props,
+        }
+    }
+
+    fn encode_schema(schema: &Schema) -> SchemaDescriptor {
+        SchemaDescriptor {
+            schema_id: schema.schema_id() as u64,
+            fields: schema
+                .as_struct()
+                .fields()
+                .iter()
+                .map(|field| Self::encode_field(field))
+                .collect(),
+        }
+    }
+
+    fn encode_field(field: &NestedField) -> NestedFieldDescriptor {
+        let field_type = match &*field.field_type {
+            Type::Primitive(primitive_type) => {
+                FieldType::Primitive(Self::encode_primitive_type(primitive_type))
+            }
+            Type::Struct(struct_type) => FieldType::Struct(StructType {
+                fields: struct_type
+                    .fields()
+                    .iter()
+                    .map(|field| Self::encode_field(field))
+                    .collect(),
+            }),
+            Type::List(list_type) => {
+                FieldType::List(Box::new(Self::encode_field(&list_type.element_field)))
+            }
+            Type::Map(map_type) => FieldType::Map(Box::new(MapType {
+                key_field: Some(Box::new(Self::encode_field(&map_type.key_field))),
+                value_field: Some(Box::new(Self::encode_field(&map_type.value_field))),
+            })),
+        };
+        NestedFieldDescriptor {
+            id: field.id,
+            name: field.name.clone(),
+            required: field.required,
+            field_type: Some(field_type),
+        }
+    }
+
+    fn encode_primitive_type(primitive_type: &iceberg::spec::PrimitiveType) -> PrimitiveType {
+        let kind_without_inner = |kind: KindWithoutInner| Kind::KindWithoutInner(kind as i32);
+        let kind = match primitive_type {
+            iceberg::spec::PrimitiveType::Boolean => kind_without_inner(KindWithoutInner::Boolean),
+            iceberg::spec::PrimitiveType::Int => kind_without_inner(KindWithoutInner::Int),
+            iceberg::spec::PrimitiveType::Long => kind_without_inner(KindWithoutInner::Long),
+            iceberg::spec::PrimitiveType::Float => kind_without_inner(KindWithoutInner::Float),
+            iceberg::spec::PrimitiveType::Double => kind_without_inner(KindWithoutInner::Double),
+            iceberg::spec::PrimitiveType::Decimal { precision, scale } => Kind::Decimal(Decimal {
+                precision: *precision,
+                scale: *scale,
+            }),
+            iceberg::spec::PrimitiveType::Date => kind_without_inner(KindWithoutInner::Date),
+            iceberg::spec::PrimitiveType::Time => kind_without_inner(KindWithoutInner::Time),
+            iceberg::spec::PrimitiveType::Timestamp => {
+                kind_without_inner(KindWithoutInner::Timestamp)
+            }
+            iceberg::spec::PrimitiveType::Timestamptz => {
+                kind_without_inner(KindWithoutInner::Timestamptz)
+            }
+            iceberg::spec::PrimitiveType::TimestampNs => {
+                kind_without_inner(KindWithoutInner::TimestampNs)
+            }
+            iceberg::spec::PrimitiveType::TimestamptzNs => {
+                kind_without_inner(KindWithoutInner::TimestamptzNs)
+            }
+            iceberg::spec::PrimitiveType::String => kind_without_inner(KindWithoutInner::String),
+            iceberg::spec::PrimitiveType::Uuid => kind_without_inner(KindWithoutInner::Uuid),
+            iceberg::spec::PrimitiveType::Fixed(size) => Kind::Fixed(*size),
+            iceberg::spec::PrimitiveType::Binary => kind_without_inner(KindWithoutInner::Binary),
+        };
+        PrimitiveType { kind: Some(kind) }
+    }
+
+    fn encode_partition_spec(partition_spec: &iceberg::spec::PartitionSpec) -> PartitionSpec {
+        PartitionSpec {
+            spec_id: partition_spec.spec_id(),
+            partition_fields: partition_spec
+                .fields()
+                .iter()
+                .map(|field| PartitionField {
+                    source_id: field.source_id,
+                    field_id: Some(field.field_id),
+                    name: field.name.clone(),
+                    transform: Some(Self::encode_transform(field.transform)),
+                })
+                .collect(),
+        }
+    }
+
+    fn encode_transform(transform: iceberg::spec::Transform) -> Transform {
+        let without_inner =
+            |transform: TransformWithoutInner| Params::TransformWithoutInner(transform as i32);
+        let params = match transform {
+            iceberg::spec::Transform::Identity => without_inner(TransformWithoutInner::Identity),
+            iceberg::spec::Transform::Year => without_inner(TransformWithoutInner::Year),
+            iceberg::spec::Transform::Month => without_inner(TransformWithoutInner::Month),
+            iceberg::spec::Transform::Day => without_inner(TransformWithoutInner::Day),
+            iceberg::spec::Transform::Hour => without_inner(TransformWithoutInner::Hour),
+            iceberg::spec::Transform::Void => without_inner(TransformWithoutInner::Void),
+            iceberg::spec::Transform::Unknown => without_inner(TransformWithoutInner::Unknown),
+            iceberg::spec::Transform::Bucket(bucket_num) => Params::Bucket(bucket_num),
+            iceberg::spec::Transform::Truncate(width) => Params::Truncate(width),
+        };
+        Transform {
+            params: Some(params),
+        }
+    }
+
+    fn encode_sort_order(sort_order: &iceberg::spec::SortOrder) -> SortOrder {
+        SortOrder {
+            order_id: sort_order.order_id,
+            fields: sort_order
+                .fields
+                .iter()
+                .map(|field| SortField {
+                    source_id: field.source_id,
+                    transform: Some(Self::encode_transform(field.transform)),
+                    direction: match field.direction {
+                        iceberg::spec::SortDirection::Ascending => SortDirection::Ascending,
+                        iceberg::spec::SortDirection::Descending => SortDirection::Descending,
+                    } as i32,
+                    null_order: match field.null_order {
+                        iceberg::spec::NullOrder::First => NullOrder::NullsFirst,
+                        iceberg::spec::NullOrder::Last => NullOrder::NullsLast,
+                    } as i32,
+                })
+                .collect(),
+        }
+    }
+
+    fn encode_deletion_vector(deletion_vector: DeletionVector) -> DeletionVectorDescriptor {
+        DeletionVectorDescriptor {
+            referenced_data_file: deletion_vector.referenced_data_file,
+            puffin_file_path: deletion_vector.puffin_file_path,
+            content_offset: deletion_vector.content_offset,
+            content_size_in_bytes: deletion_vector.content_size_in_bytes,
+            cardinality: deletion_vector.cardinality,
+        }
+    }
+}
+
+/// Decodes the protobuf response of a compactor service.
+///
+/// The bounds of the data files are decoded with the types of `schema`.
+pub struct PbRewriteFilesResponseDecoder {
+    rewrite_files_response_proto: PbRewriteFilesResponse,
+    schema: Arc<Schema>,
+}
+
+impl PbRewriteFilesResponseDecoder {
+    pub fn new(rewrite_files_response_proto: PbRewriteFilesResponse, schema: Arc<Schema>) -> Self {
+        Self {
+            rewrite_files_response_proto,
+            schema,
+        }
+    }
+
+    pub fn decode(self) -> Result<RewriteFilesResponse> {
+        let PbRewriteFilesResponse {
+            data_files,
+            stat,
+            delete_files,
+            data_sequence_number,
+            deletion_vectors,
+            delete_file_sequence_numbers,
+        } = self.rewrite_files_response_proto;
+        let data_files = data_files
+            .into_iter()
+            .map(|data_file| Self::decode_data_file(data_file, &self.schema))
+            .collect::<Result<Vec<_>>>()?;
+        let delete_files = delete_files
+            .into_iter()
+            .map(|data_file| Self::decode_data_file(data_file, &self.schema))
+            .collect::<Result<Vec<_>>>()?;
+        let stat = stat
+            .map(|stat| RewriteFilesStat {
+                rewritten_files_count: stat.rewritten_files_count,
+                added_files_count: stat.added_files_count,
+                rewritten_bytes: stat.rewritten_bytes,
+                failed_data_files_count: stat.failed_data_files_count,
+            })
+            .unwrap_or_default();
+        Ok(RewriteFilesResponse {
+            data_files,
+            delete_files,
+            deletion_vectors: PbRewriteFilesRequestDecoder::decode_deletion_vectors(
+                deletion_vectors,
+            )?,
+            delete_file_sequence_numbers,
+            data_sequence_number,
+            stat,
+        })
+    }
+
+    /// Converts a protobuf DataFile to an Iceberg data file
+    pub fn decode_data_file(
+        data_file: DataFile,
+        schema: &Schema,
+    ) -> Result<iceberg::spec::DataFile> {
+        let DataFile {
+            content,
+            file_path,
+            file_format,
+            partition,
+            record_count,
+            file_size_in_bytes,
+            column_sizes,
+            value_counts,
+            null_value_counts,
+            nan_value_counts,
+            lower_bounds,
+            upper_bounds,
+            key_metadata,
+            split_offsets,
+            equality_ids,
+            sort_order_id,
+            partition_spec_id,
+        } = data_file;
+        let partition = match partition {
+            Some(partition) => Self::decode_struct(partition)?,
+            None => iceberg::spec::Struct::empty(),
+        };
+        let mut builder = iceberg::spec::DataFileBuilder::default();
+        builder
+            .content(DataContentType::try_from(content)?)
+            .file_path(file_path)
+            .file_format(PbRewriteFilesRequestDecoder::decode_data_file_format(
+                file_format,
+            )?)
+            .partition(partition)
+            .partition_spec_id(partition_spec_id)
+            .record_count(record_count)
+            .file_size_in_bytes(file_size_in_bytes)
+            .column_sizes(column_sizes)
+            .value_counts(value_counts)
+            .null_value_counts(null_value_counts)
+            .nan_value_counts(nan_value_counts)
+            .lower_bounds(Self::decode_bounds(lower_bounds, schema)?)
+            .upper_bounds(Self::decode_bounds(upper_bounds, schema)?)
+            .key_metadata(key_metadata)
+            .split_offsets(split_offsets)
+            .equality_ids(equality_ids)
+            .sort_order_id(sort_order_id);
+        builder
+            .build()
+            .map_err(|e| CompactionError::InvalidInput(format!("invalid data file: {}", e)))
+    }
+
+    /// Decodes the bounds of the columns of a data file from their single-value serialization
+    fn decode_bounds(
+        bounds: HashMap<i32, Vec<u8>>,
+        schema: &Schema,
+    ) -> Result<HashMap<i32, iceberg::spec::Datum>> {
+        bounds
+            .into_iter()
+            .map(|(field_id, bytes)| {
+                let primitive_type = match schema.field_by_id(field_id).map(|f| &*f.field_type) {
+                    Some(Type::Primitive(primitive_type)) => primitive_type.clone(),
+                    _ => {
+                        return Err(CompactionError::InvalidInput(format!(
+                            "bound of field {} which is not a primitive field of the schema",
+                            field_id
+                        )));
+                    }
+                };
+                Ok((
+                    field_id,
+                    iceberg::spec::Datum::try_from_bytes(&bytes, primitive_type)?,
+                ))
+            })
+            .collect()
+    }
+
+    fn decode_struct(structs: StructLiteralDescriptor) -> Result<iceberg::spec::Struct> {
+        let literals = structs
+            .inner
+            .into_iter()
+            .map(|literal| literal.value.map(Self::decode_literal).transpose())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(iceberg::spec::Struct::from_iter(literals))
+    }
+
+    fn decode_literal(literal: Literal) -> Result<iceberg::spec::Literal> {
+        match literal
+            .literal
+            .ok_or_else(|| CompactionError::InvalidInput("literal is null".to_owned()))?
+        {
+            literal::Literal::Primitive(primitive_literal) => {
+                Self::decode_primitive_literal(primitive_literal)
+            }
+            literal::Literal::Struct(literals) => Ok(iceberg::spec::Literal::Struct(
+                Self::decode_struct(literals)?,
+            )),
+            literal::Literal::List(literals) => Ok(iceberg::spec::Literal::List(
+                literals
+                    .inner
+                    .into_iter()
+                    .map(|literal| literal.value.map(Self::decode_literal).transpose())
+                    .collect::<Result<Vec<_>>>()?,
+            )),
+            literal::Literal::Map(MapLiteral { keys, values }) => {
+                let mut map = iceberg::spec::Map::new();
+                for (key, value) in keys.into_iter().zip(values) {
+                    map.insert(
+                        Self::decode_literal(key)?,
+                        value.value.map(Self::decode_literal).transpose()?,
+                    );
+                }
+                Ok(iceberg::spec::Literal::Map(map))
+            }
+        }
+    }
+
+    fn decode_primitive_literal(
+        primitive_literal: PrimitiveLiteral,
+    ) -> Result<iceberg::spec::Literal> {
+        let to_bytes = |bytes: Vec<u8>| -> Result<[u8; 16]> {
+            bytes.try_into().map_err(|bytes: Vec<u8>| {
+                CompactionError::InvalidInput(format!("128-bit literal of {} bytes", bytes.len()))
+            })
+        };
+        Ok(
+            match primitive_literal.kind_literal.ok_or_else(|| {
+                CompactionError::InvalidInput("primitive literal is null".to_owned())
+            })? {
+                KindLiteral::Boolean(b) => iceberg::spec::Literal::bool(b),
+                KindLiteral::Int(i) => iceberg::spec::Literal::int(i),
+                KindLiteral::Long(l) => iceberg::spec::Literal::long(l),
+                KindLiteral::Float(f) => iceberg::spec::Literal::float(f),
+                KindLiteral::Double(f) => iceberg::spec::Literal::double(f),
+                KindLiteral::String(s) => iceberg::spec::Literal::string(s),
+                KindLiteral::Binary(b) => iceberg::spec::Literal::binary(b),
+                KindLiteral::Int128(bytes) => {
+                    iceberg::spec::Literal::decimal(i128::from_be_bytes(to_bytes(bytes)?))
+                }
+                KindLiteral::Uint128(bytes) => iceberg::spec::Literal::Primitive(
+                    iceberg::spec::PrimitiveLiteral::UInt128(u128::from_be_bytes(to_bytes(bytes)?)),
+                ),
+                KindLiteral::KindWithoutInnerLiteral(kind) => {
+                    match KindWithoutInnerLiteral::try_from(kind).map_err(|e| {
+                        CompactionError::InvalidInput(format!("failed to parse literal: {}", e))
+                    })? {
+                        KindWithoutInnerLiteral::AboveMax => iceberg::spec::Literal::Primitive(
+                            iceberg::spec::PrimitiveLiteral::AboveMax,
+                        ),
+                        KindWithoutInnerLiteral::BelowMin => iceberg::spec::Literal::Primitive(
+                            iceberg::spec::PrimitiveLiteral::BelowMin,
+                        ),
+                    }
+                }
+            },
+        )
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use bergloom_codegen::compactor::{MapType, NestedFieldDescriptor, PrimitiveType, StructType};
+    use crate::executor::datafusion::datafusion_processor::{
+        DataFusionTaskContext, DatafusionProcessor, SYS_HIDDEN_SEQ_NUM,
+    };
+    use datafusion::prelude::{SessionConfig, SessionContext};
+    use iceberg::io::FileIOBuilder;
+    use proptest::prelude::*;
+    use std::collections::HashMap;
 
     /// Test building a struct field from protobuf
     #[test]
@@ -783,4 +1461,996 @@ mod test {
             _ => panic!("Expected Map type"),
         }
     }
+
+    fn arb_name() -> impl Strategy<Value = String> {
+        prop_oneof![
+            Just("id".to_owned()),
+            Just("name".to_owned()),
+            Just(SYS_HIDDEN_SEQ_NUM.to_owned()),
+            "[a-z ]{0,8}",
+        ]
+    }
+
+    fn arb_field_id() -> impl Strategy<Value = i32> {
+        prop_oneof![0..8i32, any::<i32>()]
+    }
+
+    fn arb_primitive_type() -> impl Strategy<Value = PrimitiveType> {
+        let kind = prop_oneof![
+            (0..14i32).prop_map(Kind::KindWithoutInner),
+            any::<i32>().prop_map(Kind::KindWithoutInner),
+            (any::<u32>(), any::<u32>())
+                .prop_map(|(precision, scale)| Kind::Decimal(Decimal { precision, scale })),
+            any::<u64>().prop_map(Kind::Fixed),
+        ];
+        proptest::option::weighted(0.9, kind).prop_map(|kind| PrimitiveType { kind })
+    }
+
+    fn arb_field() -> impl Strategy<Value = NestedFieldDescriptor> {
+        let leaf =
+            proptest::option::weighted(0.9, arb_primitive_type().prop_map(FieldType::Primitive));
+        let field_type = leaf.prop_recursive(3, 24, 4, |inner| {
+            let field = (arb_field_id(), arb_name(), any::<bool>(), inner)
+                .prop_map(|(id, name, required, field_type)| NestedFieldDescriptor {
+                    id,
+                    name,
+                    required,
+                    field_type,
+                })
+                .boxed();
+            prop_oneof![
+                prop::collection::vec(field.clone(), 0..4)
+                    .prop_map(|fields| Some(FieldType::Struct(StructType { fields }))),
+                field
+                    .clone()
+                    .prop_map(|element| Some(FieldType::List(Box::new(element)))),
+                (
+                    proptest::option::weighted(0.9, field.clone()),
+                    proptest::option::weighted(0.9, field),
+                )
+                    .prop_map(|(key_field, value_field)| {
+                        Some(FieldType::Map(Box::new(MapType {
+                            key_field: key_field.map(Box::new),
+                            value_field: value_field.map(Box::new),
+                        })))
+                    }),
+            ]
+        });
+        (arb_field_id(), arb_name(), any::<bool>(), field_type).prop_map(
+            |(id, name, required, field_type)| NestedFieldDescriptor {
+                id,
+                name,
+                required,
+                field_type,
+            },
+        )
+    }
+
+    fn arb_file_scan_task_descriptor() -> impl Strategy<Value = FileScanTaskDescriptor> {
+        (
+            any::<u64>(),
+            prop_oneof![0..3i32, any::<i32>()],
+            prop_oneof![0..3i32, any::<i32>()],
+            prop::collection::vec(arb_field_id(), 0..4),
+            any::<i64>(),
+            prop::collection::vec(arb_field_id(), 0..4),
+        )
+            .prop_map(
+                |(
+                    length,
+                    data_file_content,
+                    data_file_format,
+                    project_field_ids,
+                    sequence_number,
+                    equality_ids,
+                )| FileScanTaskDescriptor {
+                    length,
+                    data_file_path: "memory:///data.parquet".to_owned(),
+                    data_file_content,
+                    data_file_format,
+                    project_field_ids,
+                    sequence_number,
+                    equality_ids,
+                    ..Default::default()
+                },
+            )
+    }
+
+    fn arb_deletion_vector_descriptor() -> impl Strategy<Value = DeletionVectorDescriptor> {
+        (
+            prop_oneof![
+                Just(String::new()),
+                Just("memory:///data.parquet".to_owned())
+            ],
+            prop_oneof![
+                Just(String::new()),
+                Just("memory:///deletes.puffin".to_owned())
+            ],
+            any::<u64>(),
+            any::<u64>(),
+            any::<u64>(),
+        )
+            .prop_map(
+                |(
+                    referenced_data_file,
+                    puffin_file_path,
+                    content_offset,
+                    content_size_in_bytes,
+                    cardinality,
+                )| DeletionVectorDescriptor {
+                    referenced_data_file,
+                    puffin_file_path,
+                    content_offset,
+                    content_size_in_bytes,
+                    cardinality,
+                },
+            )
+    }
+
+    fn arb_partition_spec() -> impl Strategy<Value = Option<PartitionSpec>> {
+        let params = prop_oneof![
+            prop_oneof![0..7i32, any::<i32>()].prop_map(Params::TransformWithoutInner),
+            any::<u32>().prop_map(Params::Bucket),
+            any::<u32>().prop_map(Params::Truncate),
+        ];
+        let field = (
+            arb_field_id(),
+            proptest::option::of(any::<i32>()),
+            arb_name(),
+            proptest::option::weighted(0.9, params),
+        )
+            .prop_map(|(source_id, field_id, name, params)| PartitionField {
+                source_id,
+                field_id,
+                name,
+                transform: Some(Transform { params }),
+            });
+        proptest::option::of((any::<i32>(), prop::collection::vec(field, 0..3)).prop_map(
+            |(spec_id, partition_fields)| PartitionSpec {
+                spec_id,
+                partition_fields,
+            },
+        ))
+    }
+
+    fn arb_rewrite_file_config() -> impl Strategy<Value = HashMap<String, String>> {
+        prop::collection::hash_map(
+            prop_oneof![
+                Just("batch_parallelism".to_owned()),
+                Just("target_partitions".to_owned()),
+                Just("memory_limit".to_owned()),
+                Just("data_file_prefix".to_owned()),
+                "[a-z_]{0,8}",
+            ],
+            prop_oneof![Just("0".to_owned()), "[0-9]{1,3}", ".{0,4}"],
+            0..4,
+        )
+    }
+
+    fn arb_rewrite_files_request() -> impl Strategy<Value = PbRewriteFilesRequest> {
+        (
+            prop::collection::vec(arb_file_scan_task_descriptor(), 0..6),
+            arb_rewrite_file_config(),
+            prop_oneof![
+                Just("memory:///".to_owned()),
+                Just("unknown://bucket".to_owned()),
+                Just(String::new()),
+            ],
+            proptest::option::weighted(0.9, prop::collection::vec(arb_field(), 0..5)),
+            arb_partition_spec(),
+            prop::collection::vec(arb_deletion_vector_descriptor(), 0..3),
+        )
+            .prop_map(
+                |(
+                    file_scan_task_descriptor,
+                    rewrite_file_config,
+                    scheme_str,
+                    fields,
+                    partition_spec,
+                    deletion_vectors,
+                )| {
+                    PbRewriteFilesRequest {
+                        file_scan_task_descriptor,
+                        rewrite_file_config,
+                        dir_path: "memory:///output".to_owned(),
+                        file_io_builder: Some(FileIoBuilder {
+                            scheme_str,
+                            props: HashMap::new(),
+                        }),
+                        schema: fields.map(|fields| SchemaDescriptor {
+                            schema_id: 0,
+                            fields,
+                        }),
+                        partition_spec,
+                        table_properties: HashMap::new(),
+                        deletion_vectors,
+                        sort_order: None,
+                    }
+                },
+            )
+    }
+
+    proptest! {
+        /// Malformed requests must be rejected with an error, never crash the compactor.
+        #[test]
+        fn test_decode_arbitrary_request_never_panics(request in arb_rewrite_files_request()) {
+            let Ok(request) = PbRewriteFilesRequestDecoder::new(request).decode() else {
+                return Ok(());
+            };
+            let InputFileScanTasks {
+                data_files,
+                position_delete_files,
+                equality_delete_files,
+                deletion_vectors,
+                data_file_metadata,
+                delete_file_metadata,
+            } = request.input_file_scan_tasks;
+            let Ok(datafusion_task_ctx) = DataFusionTaskContext::builder().and_then(|builder| {
+                builder
+                    .with_schema(request.schema)
+                    .with_partition_spec(request.partition_spec)
+                    .with_sort_order(request.sort_order)
+                    .with_data_file_metadata(data_file_metadata)
+                    .with_delete_file_metadata(delete_file_metadata)
+                    .with_datafile(data_files)
+                    .with_position_delete_files(position_delete_files)
+                    .with_deletion_vectors(deletion_vectors)
+                    .with_equality_delete_files(equality_delete_files)
+                    .build_merge_on_read()
+            }) else {
+                return Ok(());
+            };
+            // the files don't exist, registering the tables and planning the rewrite may fail
+            // but must not panic
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let ctx = Arc::new(SessionContext::new_with_config(
+                SessionConfig::new().with_target_partitions(4),
+            ));
+            let _ = runtime.block_on(
+                DatafusionProcessor::new(
+                    ctx,
+                    datafusion_task_ctx,
+                    4,
+                    4,
+                    2,
+                    1024,
+                    FileIOBuilder::new("memory").build().unwrap(),
+                )
+                .execute(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_deletion_vectors() {
+        let descriptor = DeletionVectorDescriptor {
+            referenced_data_file: "memory:///data.parquet".to_owned(),
+            puffin_file_path: "memory:///deletes.puffin".to_owned(),
+            content_offset: 4,
+            content_size_in_bytes: 40,
+            cardinality: 3,
+        };
+        let deletion_vectors =
+            PbRewriteFilesRequestDecoder::decode_deletion_vectors(vec![descriptor.clone()])
+                .unwrap();
+        assert_eq!(
+            deletion_vectors,
+            vec![DeletionVector {
+                referenced_data_file: "memory:///data.parquet".to_owned(),
+                puffin_file_path: "memory:///deletes.puffin".to_owned(),
+                content_offset: 4,
+                content_size_in_bytes: 40,
+                cardinality: 3,
+            }]
+        );
+
+        let result =
+            PbRewriteFilesRequestDecoder::decode_deletion_vectors(vec![DeletionVectorDescriptor {
+                puffin_file_path: String::new(),
+                ..descriptor.clone()
+            }]);
+        assert!(matches!(result, Err(CompactionError::InvalidInput(_))));
+
+        let result =
+            PbRewriteFilesRequestDecoder::decode_deletion_vectors(vec![DeletionVectorDescriptor {
+                content_offset: u64::MAX,
+                ..descriptor
+            }]);
+        assert!(matches!(result, Err(CompactionError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_decode_partition_spec_ids() {
+        let descriptor =
+            |data_file_path: &str, data_file_content, partition_spec_id| FileScanTaskDescriptor {
+                data_file_path: data_file_path.to_owned(),
+                data_file_content,
+                data_file_format: 2,
+                partition_spec_id,
+                ..Default::default()
+            };
+        let (input_file_scan_tasks, _) =
+            PbRewriteFilesRequestDecoder::decode_file_scan_tasks_and_schema(
+                vec![
+                    descriptor("memory:///a.parquet", 0, Some(0)),
+                    descriptor("memory:///b.parquet", 0, Some(2)),
+                    descriptor("memory:///c.parquet", 0, None),
+                    descriptor("memory:///deletes.parquet", 1, Some(2)),
+                ],
+                SchemaDescriptor::default(),
+                3,
+            )
+            .unwrap();
+        let data_file_metadata = &input_file_scan_tasks.data_file_metadata;
+        assert_eq!(data_file_metadata.len(), 3);
+        assert_eq!(
+            data_file_metadata["memory:///a.parquet"].partition_spec_id,
+            0
+        );
+        assert_eq!(
+            data_file_metadata["memory:///c.parquet"].partition_spec_id,
+            3
+        );
+        assert_eq!(
+            data_file_metadata["memory:///b.parquet"].partition_spec_id,
+            2
+        );
+        assert!(
+            data_file_metadata["memory:///b.parquet"]
+                .partition
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_decode_partition_and_sort_order() {
+        let schema = SchemaDescriptor {
+            schema_id: 0,
+            fields: vec![NestedFieldDescriptor {
+                id: 1,
+                name: "id".to_owned(),
+                required: false,
+                field_type: Some(FieldType::Primitive(PrimitiveType {
+                    kind: Some(Kind::KindWithoutInner(KindWithoutInner::Long as i32)),
+                })),
+            }],
+        };
+        let partition = StructLiteralDescriptor {
+            inner: vec![OptionalLiteral {
+                value: Some(Literal {
+                    literal: Some(literal::Literal::Primitive(PrimitiveLiteral {
+                        kind_literal: Some(KindLiteral::Long(7)),
+                    })),
+                }),
+            }],
+        };
+        let (input_file_scan_tasks, schema) =
+            PbRewriteFilesRequestDecoder::decode_file_scan_tasks_and_schema(
+                vec![FileScanTaskDescriptor {
+                    data_file_path: "memory:///a.parquet".to_owned(),
+                    data_file_format: 2,
+                    partition_spec_id: Some(1),
+                    partition: Some(partition),
+                    sort_order_id: Some(3),
+                    ..Default::default()
+                }],
+                schema,
+                1,
+            )
+            .unwrap();
+        let metadata = &input_file_scan_tasks.data_file_metadata["memory:///a.parquet"];
+        assert_eq!(
+            metadata.partition,
+            Some(iceberg::spec::Struct::from_iter([Some(
+                iceberg::spec::Literal::long(7)
+            )]))
+        );
+        assert_eq!(metadata.sort_order_id, Some(3));
+
+        let sort_field = |source_id| SortField {
+            source_id,
+            transform: Some(Transform {
+                params: Some(Params::TransformWithoutInner(
+                    TransformWithoutInner::Identity as i32,
+                )),
+            }),
+            direction: SortDirection::Descending as i32,
+            null_order: NullOrder::NullsLast as i32,
+        };
+        let sort_order = PbRewriteFilesRequestDecoder::decode_sort_order(
+            SortOrder {
+                order_id: 3,
+                fields: vec![sort_field(1)],
+            },
+            &schema,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(sort_order.order_id, 3);
+        assert_eq!(
+            sort_order.fields[0].direction,
+            iceberg::spec::SortDirection::Descending
+        );
+        assert_eq!(
+            sort_order.fields[0].null_order,
+            iceberg::spec::NullOrder::Last
+        );
+
+        let unsorted =
+            PbRewriteFilesRequestDecoder::decode_sort_order(SortOrder::default(), &schema);
+        assert!(unsorted.unwrap().is_none());
+        let result = PbRewriteFilesRequestDecoder::decode_sort_order(
+            SortOrder {
+                order_id: 3,
+                fields: vec![sort_field(2)],
+            },
+            &schema,
+        );
+        assert!(matches!(result, Err(CompactionError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_decode_rejects_executor_config() {
+        let request = |key: &str, value: &str| PbRewriteFilesRequest {
+            file_io_builder: Some(FileIoBuilder {
+                scheme_str: "memory:///".to_owned(),
+                props: HashMap::new(),
+            }),
+            schema: Some(SchemaDescriptor::default()),
+            dir_path: "memory:///output".to_owned(),
+            rewrite_file_config: HashMap::from([(key.to_owned(), value.to_owned())]),
+            ..Default::default()
+        };
+        assert!(
+            PbRewriteFilesRequestDecoder::new(request("batch_parallelism", "4"))
+                .decode()
+                .is_ok()
+        );
+        for (key, value) in [
+            ("name", "remote"),
+            ("remote_endpoints", "http://attacker:7777"),
+            ("remote_group_size", "1024"),
+            ("remote_request_timeout_secs", "0"),
+            ("memory_limit", "1024"),
+            ("spill_dir", "/etc"),
+        ] {
+            let result = PbRewriteFilesRequestDecoder::new(request(key, value)).decode();
+            assert!(matches!(result, Err(CompactionError::InvalidInput(_))));
+        }
+    }
+
+    #[test]
+    fn test_decode_schema_id_out_of_range() {
+        let result = PbRewriteFilesRequestDecoder::decode_schema(SchemaDescriptor {
+            schema_id: u64::MAX,
+            fields: vec![],
+        });
+        assert!(matches!(result, Err(CompactionError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_decode_unknown_data_file_format() {
+        let result = PbRewriteFilesRequestDecoder::decode_data_file_format(3);
+        assert!(matches!(result, Err(CompactionError::InvalidInput(_))));
+    }
+
+    fn arb_literal() -> impl Strategy<Value = iceberg::spec::Literal> {
+        let primitive = prop_oneof![
+            any::<bool>().prop_map(iceberg::spec::Literal::bool),
+            any::<i32>().prop_map(iceberg::spec::Literal::int),
+            any::<i64>().prop_map(iceberg::spec::Literal::long),
+            any::<f32>().prop_map(iceberg::spec::Literal::float),
+            any::<f64>().prop_map(iceberg::spec::Literal::double),
+            ".{0,8}".prop_map(iceberg::spec::Literal::string),
+            prop::collection::vec(any::<u8>(), 0..8).prop_map(iceberg::spec::Literal::binary),
+            any::<i128>().prop_map(iceberg::spec::Literal::decimal),
+            any::<u128>().prop_map(|value| iceberg::spec::Literal::Primitive(
+                iceberg::spec::PrimitiveLiteral::UInt128(value)
+            )),
+            Just(iceberg::spec::Literal::Primitive(
+                iceberg::spec::PrimitiveLiteral::AboveMax
+            )),
+            Just(iceberg::spec::Literal::Primitive(
+                iceberg::spec::PrimitiveLiteral::BelowMin
+            )),
+        ];
+        primitive.prop_recursive(3, 24, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(proptest::option::of(inner.clone()), 0..4).prop_map(
+                    |literals| iceberg::spec::Literal::Struct(iceberg::spec::Struct::from_iter(
+                        literals
+                    ))
+                ),
+                prop::collection::vec(proptest::option::of(inner.clone()), 0..4)
+                    .prop_map(iceberg::spec::Literal::List),
+                prop::collection::vec((inner.clone(), proptest::option::of(inner)), 0..4).prop_map(
+                    |entries| {
+                        let mut map = iceberg::spec::Map::new();
+                        for (key, value) in entries {
+                            map.insert(key, value);
+                        }
+                        iceberg::spec::Literal::Map(map)
+                    }
+                ),
+            ]
+        })
+    }
+
+    fn arb_transform() -> impl Strategy<Value = iceberg::spec::Transform> {
+        prop_oneof![
+            Just(iceberg::spec::Transform::Identity),
+            Just(iceberg::spec::Transform::Year),
+            Just(iceberg::spec::Transform::Month),
+            Just(iceberg::spec::Transform::Day),
+            Just(iceberg::spec::Transform::Hour),
+            Just(iceberg::spec::Transform::Void),
+            Just(iceberg::spec::Transform::Unknown),
+            any::<u32>().prop_map(iceberg::spec::Transform::Bucket),
+            any::<u32>().prop_map(iceberg::spec::Transform::Truncate),
+        ]
+    }
+
+    /// Schema of the data files of the round-trip tests, with a field of each type bounds are
+    /// kept for.
+    fn round_trip_schema() -> Schema {
+        Schema::builder()
+            .with_schema_id(3)
+            .with_fields(vec![
+                NestedField::required(1, "id", Type::Primitive(iceberg::spec::PrimitiveType::Long))
+                    .into(),
+                NestedField::optional(
+                    2,
+                    "name",
+                    Type::Primitive(iceberg::spec::PrimitiveType::String),
+                )
+                .into(),
+                NestedField::optional(
+                    3,
+                    "score",
+                    Type::Primitive(iceberg::spec::PrimitiveType::Double),
+                )
+                .into(),
+                NestedField::optional(
+                    4,
+                    "day",
+                    Type::Primitive(iceberg::spec::PrimitiveType::Date),
+                )
+                .into(),
+                NestedField::optional(
+                    5,
+                    "ts",
+                    Type::Primitive(iceberg::spec::PrimitiveType::Timestamp),
+                )
+                .into(),
+                NestedField::optional(
+                    6,
+                    "deleted",
+                    Type::Primitive(iceberg::spec::PrimitiveType::Boolean),
+                )
+                .into(),
+                NestedField::optional(
+                    7,
+                    "tags",
+                    Type::List(iceberg::spec::ListType::new(
+                        NestedField::list_element(
+                            8,
+                            Type::Primitive(iceberg::spec::PrimitiveType::String),
+                            false,
+                        )
+                        .into(),
+                    )),
+                )
+                .into(),
+                NestedField::optional(
+                    9,
+                    "prices",
+                    Type::Map(iceberg::spec::MapType::new(
+                        NestedField::map_key_element(
+                            10,
+                            Type::Primitive(iceberg::spec::PrimitiveType::String),
+                        )
+                        .into(),
+                        NestedField::map_value_element(
+                            11,
+                            Type::Primitive(iceberg::spec::PrimitiveType::Decimal {
+                                precision: 10,
+                                scale: 2,
+                            }),
+                            false,
+                        )
+                        .into(),
+                    )),
+                )
+                .into(),
+            ])
+            .build()
+            .unwrap()
+    }
+
+    fn arb_bounds() -> impl Strategy<Value = HashMap<i32, iceberg::spec::Datum>> {
+        (
+            proptest::option::of(any::<i64>().prop_map(iceberg::spec::Datum::long)),
+            proptest::option::of(".{0,8}".prop_map(iceberg::spec::Datum::string)),
+            proptest::option::of(any::<f64>().prop_map(iceberg::spec::Datum::double)),
+            proptest::option::of(any::<i32>().prop_map(iceberg::spec::Datum::date)),
+            proptest::option::of(any::<i64>().prop_map(iceberg::spec::Datum::timestamp_micros)),
+            proptest::option::of(any::<bool>().prop_map(iceberg::spec::Datum::bool)),
+        )
+            .prop_map(|(id, name, score, day, ts, deleted)| {
+                [
+                    (1, id),
+                    (2, name),
+                    (3, score),
+                    (4, day),
+                    (5, ts),
+                    (6, deleted),
+                ]
+                .into_iter()
+                .filter_map(|(field_id, datum)| datum.map(|datum| (field_id, datum)))
+                .collect()
+            })
+    }
+
+    fn arb_data_file() -> impl Strategy<Value = iceberg::spec::DataFile> {
+        (
+            prop_oneof![
+                Just(iceberg::spec::DataContentType::Data),
+                Just(iceberg::spec::DataContentType::PositionDeletes),
+                Just(iceberg::spec::DataContentType::EqualityDeletes),
+            ],
+            prop::collection::vec(proptest::option::of(arb_literal()), 0..4),
+            (any::<u64>(), any::<u64>()),
+            prop::collection::hash_map(1..7i32, any::<u64>(), 0..4),
+            (arb_bounds(), arb_bounds()),
+            proptest::option::of(prop::collection::vec(any::<u8>(), 0..8)),
+            prop::collection::vec(any::<i64>(), 0..4),
+            prop::collection::vec(1..7i32, 0..3),
+            (proptest::option::of(any::<i32>()), any::<i32>()),
+        )
+            .prop_map(
+                |(
+                    content,
+                    partition,
+                    (record_count, file_size_in_bytes),
+                    counts,
+                    (lower_bounds, upper_bounds),
+                    key_metadata,
+                    split_offsets,
+                    equality_ids,
+                    (sort_order_id, partition_spec_id),
+                )| {
+                    iceberg::spec::DataFileBuilder::default()
+                        .content(content)
+                        .file_path("memory:///data.parquet".to_owned())
+                        .file_format(iceberg::spec::DataFileFormat::Parquet)
+                        .partition(iceberg::spec::Struct::from_iter(partition))
+                        .partition_spec_id(partition_spec_id)
+                        .record_count(record_count)
+                        .file_size_in_bytes(file_size_in_bytes)
+                        .column_sizes(counts.clone())
+                        .value_counts(counts.clone())
+                        .null_value_counts(counts.clone())
+                        .nan_value_counts(counts)
+                        .lower_bounds(lower_bounds)
+                        .upper_bounds(upper_bounds)
+                        .key_metadata(key_metadata)
+                        .split_offsets(split_offsets)
+                        .equality_ids(equality_ids)
+                        .sort_order_id(sort_order_id)
+                        .build()
+                        .unwrap()
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn test_literal_round_trip(literal in arb_literal()) {
+            let encoded = RewriteFilesResponseProtoEncoder::encode_literal(literal.clone());
+            let decoded = PbRewriteFilesResponseDecoder::decode_literal(encoded).unwrap();
+            prop_assert_eq!(decoded, literal);
+        }
+
+        #[test]
+        fn test_transform_round_trip(transform in arb_transform()) {
+            let encoded = RewriteFilesRequestProtoEncoder::encode_transform(transform);
+            let decoded = PbRewriteFilesRequestDecoder::decode_transform(&encoded).unwrap();
+            prop_assert_eq!(decoded, transform);
+        }
+
+        #[test]
+        fn test_data_file_round_trip(data_file in arb_data_file()) {
+            let encoded =
+                RewriteFilesResponseProtoEncoder::encode_data_file(data_file.clone()).unwrap();
+            let decoded =
+                PbRewriteFilesResponseDecoder::decode_data_file(encoded, &round_trip_schema())
+                    .unwrap();
+            prop_assert_eq!(decoded, data_file);
+        }
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let schema = Arc::new(round_trip_schema());
+        let data_file = |file_path: &str, content| {
+            iceberg::spec::DataFileBuilder::default()
+                .content(content)
+                .file_path(file_path.to_owned())
+                .file_format(iceberg::spec::DataFileFormat::Parquet)
+                .partition(iceberg::spec::Struct::from_iter([Some(
+                    iceberg::spec::Literal::int(4),
+                )]))
+                .partition_spec_id(2)
+                .record_count(10)
+                .file_size_in_bytes(1024)
+                .lower_bounds(HashMap::from([(1, iceberg::spec::Datum::long(-3))]))
+                .upper_bounds(HashMap::from([(1, iceberg::spec::Datum::long(42))]))
+                .build()
+                .unwrap()
+        };
+        let response = RewriteFilesResponse {
+            data_files: vec![data_file(
+                "memory:///data.parquet",
+                iceberg::spec::DataContentType::Data,
+            )],
+            delete_files: vec![data_file(
+                "memory:///deletes.parquet",
+                iceberg::spec::DataContentType::PositionDeletes,
+            )],
+            deletion_vectors: vec![DeletionVector {
+                referenced_data_file: "memory:///data.parquet".to_owned(),
+                puffin_file_path: "memory:///deletes.puffin".to_owned(),
+                content_offset: 4,
+                content_size_in_bytes: 40,
+                cardinality: 3,
+            }],
+            delete_file_sequence_numbers: HashMap::from([(
+                "memory:///deletes.parquet".to_owned(),
+                5,
+            )]),
+            data_sequence_number: Some(7),
+            stat: RewriteFilesStat {
+                rewritten_files_count: 3,
+                added_files_count: 1,
+                rewritten_bytes: 4096,
+                failed_data_files_count: 0,
+            },
+        };
+
+        let encoded = RewriteFilesResponseProtoEncoder::new(response.clone())
+            .encode()
+            .unwrap();
+        let decoded = PbRewriteFilesResponseDecoder::new(encoded, schema.clone())
+            .decode()
+            .unwrap();
+        assert_eq!(decoded.data_files, response.data_files);
+        assert_eq!(decoded.delete_files, response.delete_files);
+        assert_eq!(decoded.deletion_vectors, response.deletion_vectors);
+        assert_eq!(
+            decoded.delete_file_sequence_numbers,
+            response.delete_file_sequence_numbers
+        );
+        assert_eq!(decoded.data_sequence_number, Some(7));
+        assert_eq!(decoded.stat.rewritten_files_count, 3);
+        assert_eq!(decoded.stat.added_files_count, 1);
+        assert_eq!(decoded.stat.rewritten_bytes, 4096);
+        assert_eq!(decoded.stat.failed_data_files_count, 0);
+
+        // a rewrite of delete files rewrites no data files
+        let encoded = RewriteFilesResponseProtoEncoder::new(RewriteFilesResponse::default())
+            .encode()
+            .unwrap();
+        let decoded = PbRewriteFilesResponseDecoder::new(encoded, schema)
+            .decode()
+            .unwrap();
+        assert_eq!(decoded.data_sequence_number, None);
+
+        // a bound of a column missing from the schema can't be decoded
+        let encoded = RewriteFilesResponseProtoEncoder::new(response)
+            .encode()
+            .unwrap();
+        let result = PbRewriteFilesResponseDecoder::new(
+            encoded,
+            Arc::new(Schema::builder().build().unwrap()),
+        )
+        .decode();
+        assert!(matches!(result, Err(CompactionError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_request_round_trip() {
+        let schema = Arc::new(round_trip_schema());
+        let partition_spec = iceberg::spec::PartitionSpec::builder(schema.clone())
+            .with_spec_id(2)
+            .add_partition_field("id", "id_bucket", iceberg::spec::Transform::Bucket(8))
+            .unwrap()
+            .add_partition_field("name", "name_trunc", iceberg::spec::Transform::Truncate(4))
+            .unwrap()
+            .add_partition_field("ts", "ts_day", iceberg::spec::Transform::Day)
+            .unwrap()
+            .build()
+            .unwrap();
+        let task = |data_file_path: &str, data_file_content, equality_ids: Vec<i32>| FileScanTask {
+            start: 4,
+            length: 100,
+            record_count: Some(10),
+            data_file_path: data_file_path.to_owned(),
+            data_file_content,
+            data_file_format: iceberg::spec::DataFileFormat::Parquet,
+            schema: schema.clone(),
+            project_field_ids: match data_file_content {
+                DataContentType::Data => vec![1, 2, 3],
+                DataContentType::PositionDeletes => vec![],
+                DataContentType::EqualityDeletes => equality_ids.clone(),
+            },
+            predicate: None,
+            deletes: vec![],
+            sequence_number: 5,
+            equality_ids,
+            file_size_in_bytes: 0,
+        };
+        let metadata = |partition_spec_id| DataFileMetadata {
+            partition_spec_id,
+            partition: None,
+            sort_order_id: None,
+        };
+        let partition = iceberg::spec::Struct::from_iter([
+            Some(iceberg::spec::Literal::int(3)),
+            Some(iceberg::spec::Literal::string("abcd")),
+            None,
+        ]);
+        let sort_order = Arc::new(iceberg::spec::SortOrder {
+            order_id: 1,
+            fields: vec![iceberg::spec::SortField {
+                source_id: 2,
+                transform: iceberg::spec::Transform::Identity,
+                direction: iceberg::spec::SortDirection::Descending,
+                null_order: iceberg::spec::NullOrder::Last,
+            }],
+        });
+        let deletion_vector = DeletionVector {
+            referenced_data_file: "memory:///a.parquet".to_owned(),
+            puffin_file_path: "memory:///deletes.puffin".to_owned(),
+            content_offset: 4,
+            content_size_in_bytes: 40,
+            cardinality: 3,
+        };
+        let input_file_scan_tasks = InputFileScanTasks {
+            data_files: vec![
+                task("memory:///a.parquet", DataContentType::Data, vec![]),
+                task("memory:///b.parquet", DataContentType::Data, vec![]),
+            ],
+            position_delete_files: vec![task(
+                "memory:///pos.parquet",
+                DataContentType::PositionDeletes,
+                vec![],
+            )],
+            equality_delete_files: vec![task(
+                "memory:///eq.parquet",
+                DataContentType::EqualityDeletes,
+                vec![1, 2],
+            )],
+            deletion_vectors: vec![deletion_vector.clone()],
+            data_file_metadata: HashMap::from([(
+                "memory:///a.parquet".to_owned(),
+                DataFileMetadata {
+                    partition: Some(partition.clone()),
+                    sort_order_id: Some(1),
+                    ..metadata(1)
+                },
+            )]),
+            delete_file_metadata: HashMap::from([("memory:///eq.parquet".to_owned(), metadata(1))]),
+        };
+        let config = CompactionConfig {
+            batch_parallelism: Some(4),
+            data_file_prefix: Some("compacted".to_owned()),
+            target_split_size: Some(1 << 20),
+            rewrite_to_current_spec: Some(true),
+            ..Default::default()
+        };
+        let request = RewriteFilesRequest {
+            file_io: iceberg::io::FileIOBuilder::new("memory").build().unwrap(),
+            schema: schema.clone(),
+            input_file_scan_tasks: input_file_scan_tasks.clone(),
+            config: Arc::new(config.clone()),
+            dir_path: "memory:///output".to_owned(),
+            partition_spec: Arc::new(partition_spec.clone()),
+            table_properties: HashMap::from([(
+                "write.format.default".to_owned(),
+                "parquet".to_owned(),
+            )]),
+            sort_order: Some(sort_order.clone()),
+        };
+
+        let encoded = RewriteFilesRequestProtoEncoder::new(request)
+            .encode()
+            .unwrap();
+        let decoded = PbRewriteFilesRequestDecoder::new(encoded).decode().unwrap();
+
+        assert_eq!(decoded.schema.schema_id(), schema.schema_id());
+        assert_eq!(decoded.schema.as_struct(), schema.as_struct());
+        assert_eq!(*decoded.partition_spec, partition_spec);
+        assert_eq!(decoded.dir_path, "memory:///output");
+        assert_eq!(decoded.table_properties["write.format.default"], "parquet");
+        assert_eq!(decoded.file_io.into_builder().into_parts().0, "memory");
+        assert_eq!(decoded.sort_order, Some(sort_order));
+        assert_eq!(
+            serde_json::to_value(&*decoded.config).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+
+        let fields = |tasks: &[FileScanTask]| {
+            tasks
+                .iter()
+                .map(|task| {
+                    (
+                        task.start,
+                        task.length,
+                        task.record_count,
+                        task.data_file_path.clone(),
+                        task.data_file_content,
+                        task.data_file_format,
+                        task.project_field_ids.clone(),
+                        task.sequence_number,
+                        task.equality_ids.clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let decoded_tasks = decoded.input_file_scan_tasks;
+        assert_eq!(
+            fields(&decoded_tasks.data_files),
+            fields(&input_file_scan_tasks.data_files)
+        );
+        assert_eq!(
+            fields(&decoded_tasks.position_delete_files),
+            fields(&input_file_scan_tasks.position_delete_files)
+        );
+        assert_eq!(
+            fields(&decoded_tasks.equality_delete_files),
+            fields(&input_file_scan_tasks.equality_delete_files)
+        );
+        assert_eq!(decoded_tasks.deletion_vectors, vec![deletion_vector]);
+
+        // files without metadata get the spec of the request
+        let spec_id = |metadata: &HashMap<String, DataFileMetadata>, path: &str| {
+            metadata[path].partition_spec_id
+        };
+        assert_eq!(
+            spec_id(&decoded_tasks.data_file_metadata, "memory:///a.parquet"),
+            1
+        );
+        assert_eq!(
+            spec_id(&decoded_tasks.data_file_metadata, "memory:///b.parquet"),
+            2
+        );
+        assert_eq!(
+            spec_id(&decoded_tasks.delete_file_metadata, "memory:///pos.parquet"),
+            2
+        );
+        assert_eq!(
+            spec_id(&decoded_tasks.delete_file_metadata, "memory:///eq.parquet"),
+            1
+        );
+        assert_eq!(
+            decoded_tasks.data_file_metadata["memory:///a.parquet"].partition,
+            Some(partition)
+        );
+        assert!(
+            decoded_tasks.data_file_metadata["memory:///b.parquet"]
+                .partition
+                .is_none()
+        );
+        assert_eq!(
+            decoded_tasks.data_file_metadata["memory:///a.parquet"].sort_order_id,
+            Some(1)
+        );
+        assert_eq!(
+            decoded_tasks.data_file_metadata["memory:///b.parquet"].sort_order_id,
+            None
+        );
+    }
 }
@@ -0,0 +1,379 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::AdmissionConfig;
+
+/// Input bytes are tracked in MiB so that the byte budget fits into semaphore permits.
+const INPUT_BYTES_PER_PERMIT: u64 = 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum AdmissionError {
+    #[error("request input of {input_bytes} bytes exceeds the limit of {max_input_bytes} bytes")]
+    InputTooLarge {
+        input_bytes: u64,
+        max_input_bytes: u64,
+    },
+
+    #[error("too many queued requests, limit is {max_queued_jobs}")]
+    QueueFull { max_queued_jobs: usize },
+
+    #[error("admission controller is closed")]
+    Closed,
+}
+
+/// Limits the number of concurrent rewrite jobs and the total estimated input bytes held by them.
+///
+/// Requests that cannot be admitted immediately wait in a bounded queue, and are rejected once
+/// the queue is full. The limits can be changed at runtime with [`AdmissionController::update_limits`].
+pub struct AdmissionController {
+    jobs: Arc<ResizableSemaphore>,
+    input_permits: Arc<ResizableSemaphore>,
+    max_concurrent_jobs: AtomicUsize,
+    max_input_permits: AtomicUsize,
+    max_queued_jobs: AtomicUsize,
+    queued_jobs: AtomicUsize,
+}
+
+/// Holds the admitted resources until the job is finished.
+pub struct AdmissionPermit {
+    _job: ResizablePermit,
+    _input: ResizablePermit,
+}
+
+impl AdmissionController {
+    pub fn new(config: &AdmissionConfig) -> Self {
+        let max_input_permits = Self::bytes_to_permits(config.max_input_bytes);
+        Self {
+            jobs: Arc::new(ResizableSemaphore::new(config.max_concurrent_jobs)),
+            input_permits: Arc::new(ResizableSemaphore::new(max_input_permits)),
+            max_concurrent_jobs: AtomicUsize::new(config.max_concurrent_jobs),
+            max_input_permits: AtomicUsize::new(max_input_permits),
+            max_queued_jobs: AtomicUsize::new(config.max_queued_jobs),
+            queued_jobs: AtomicUsize::new(0),
+        }
+    }
+
+    /// Applies new limits. Lowered limits take effect as running jobs release their permits.
+    pub fn update_limits(&self, config: &AdmissionConfig) {
+        let old_max_concurrent_jobs = self
+            .max_concurrent_jobs
+            .swap(config.max_concurrent_jobs, Ordering::SeqCst);
+        self.jobs
+            .resize(old_max_concurrent_jobs, config.max_concurrent_jobs);
+
+        let max_input_permits = Self::bytes_to_permits(config.max_input_bytes);
+        let old_max_input_permits = self
+            .max_input_permits
+            .swap(max_input_permits, Ordering::SeqCst);
+        self.input_permits
+            .resize(old_max_input_permits, max_input_permits);
+
+        self.max_queued_jobs
+            .store(config.max_queued_jobs, Ordering::SeqCst);
+    }
+
+    /// Admits a job with the given estimated input size, waiting in the queue if necessary.
+    pub async fn admit(&self, input_bytes: u64) -> Result<AdmissionPermit, AdmissionError> {
+        let input_permits = Self::bytes_to_permits(input_bytes);
+        let max_input_permits = self.max_input_permits.load(Ordering::SeqCst);
+        if input_permits > max_input_permits {
+            return Err(AdmissionError::InputTooLarge {
+                input_bytes,
+                max_input_bytes: max_input_permits as u64 * INPUT_BYTES_PER_PERMIT,
+            });
+        }
+        // permits are clamped to u32 in `bytes_to_permits`, so the cast is lossless
+        let input_permits = input_permits as u32;
+
+        if let Some(permit) = self.try_admit(input_permits) {
+            return Ok(permit);
+        }
+
+        let max_queued_jobs = self.max_queued_jobs.load(Ordering::SeqCst);
+        if self.queued_jobs.fetch_add(1, Ordering::SeqCst) >= max_queued_jobs {
+            self.queued_jobs.fetch_sub(1, Ordering::SeqCst);
+            return Err(AdmissionError::QueueFull { max_queued_jobs });
+        }
+        // decrement the queue length even if the request is cancelled while waiting
+        let _queued = QueuedGuard(&self.queued_jobs);
+
+        // take the input bytes first, so that a job waiting for them does not hold a job slot
+        // that smaller jobs could run in
+        let input = self.input_permits.acquire(input_permits).await?;
+        let job = self.jobs.acquire(1).await?;
+        Ok(AdmissionPermit {
+            _job: job,
+            _input: input,
+        })
+    }
+
+    fn try_admit(&self, input_permits: u32) -> Option<AdmissionPermit> {
+        let input = self.input_permits.try_acquire(input_permits)?;
+        let job = self.jobs.try_acquire(1)?;
+        Some(AdmissionPermit {
+            _job: job,
+            _input: input,
+        })
+    }
+
+    fn bytes_to_permits(bytes: u64) -> usize {
+        let permits = bytes.div_ceil(INPUT_BYTES_PER_PERMIT);
+        permits
+            .min(u32::MAX as u64)
+            .min(Semaphore::MAX_PERMITS as u64) as usize
+    }
+}
+
+/// A semaphore whose number of permits can be lowered below the permits currently held.
+///
+/// The permits that could not be taken back when shrinking are recorded as a deficit, and are
+/// forgotten as the held permits are released rather than returned to the semaphore. Growing
+/// pays the deficit off first, so that the permits always add up to the latest limit.
+struct ResizableSemaphore {
+    semaphore: Arc<Semaphore>,
+    /// Held permits to forget on release. Locked while permits are forgotten or returned, so
+    /// that a release cannot slip in between shrinking the semaphore and recording the rest.
+    deficit: Mutex<usize>,
+}
+
+/// Permits of a [`ResizableSemaphore`], released against its deficit on drop.
+struct ResizablePermit {
+    semaphore: Arc<ResizableSemaphore>,
+    permit: Option<OwnedSemaphorePermit>,
+    permits: usize,
+}
+
+impl ResizableSemaphore {
+    fn new(permits: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(permits)),
+            deficit: Mutex::new(0),
+        }
+    }
+
+    fn resize(&self, old_permits: usize, new_permits: usize) {
+        let mut deficit = self.deficit.lock().unwrap();
+        if new_permits > old_permits {
+            let grow = new_permits - old_permits;
+            let paid = grow.min(*deficit);
+            *deficit -= paid;
+            self.semaphore.add_permits(grow - paid);
+        } else if new_permits < old_permits {
+            // the rest are held by running jobs, take them back once they are released
+            let shrink = old_permits - new_permits;
+            *deficit += shrink - self.semaphore.forget_permits(shrink);
+        }
+    }
+
+    async fn acquire(self: &Arc<Self>, permits: u32) -> Result<ResizablePermit, AdmissionError> {
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_many_owned(permits)
+            .await
+            .map_err(|_| AdmissionError::Closed)?;
+        Ok(self.wrap(permit, permits))
+    }
+
+    fn try_acquire(self: &Arc<Self>, permits: u32) -> Option<ResizablePermit> {
+        let permit = self
+            .semaphore
+            .clone()
+            .try_acquire_many_owned(permits)
+            .ok()?;
+        Some(self.wrap(permit, permits))
+    }
+
+    fn wrap(self: &Arc<Self>, permit: OwnedSemaphorePermit, permits: u32) -> ResizablePermit {
+        ResizablePermit {
+            semaphore: self.clone(),
+            permit: Some(permit),
+            permits: permits as usize,
+        }
+    }
+}
+
+impl Drop for ResizablePermit {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+        let mut deficit = self.semaphore.deficit.lock().unwrap();
+        let forgotten = self.permits.min(*deficit);
+        if forgotten == 0 {
+            drop(permit);
+            return;
+        }
+        *deficit -= forgotten;
+        permit.forget();
+        self.semaphore
+            .semaphore
+            .add_permits(self.permits - forgotten);
+    }
+}
+
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_controller(
+        max_concurrent_jobs: usize,
+        max_input_bytes: u64,
+        max_queued_jobs: usize,
+    ) -> Arc<AdmissionController> {
+        Arc::new(AdmissionController::new(&AdmissionConfig {
+            max_concurrent_jobs,
+            max_input_bytes,
+            max_queued_jobs,
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_admit_within_limits() {
+        let controller = build_controller(2, 10 * INPUT_BYTES_PER_PERMIT, 0);
+        let _p1 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+        let _p2 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reject_input_too_large() {
+        let controller = build_controller(2, 10 * INPUT_BYTES_PER_PERMIT, 4);
+        let result = controller.admit(11 * INPUT_BYTES_PER_PERMIT).await;
+        assert!(matches!(result, Err(AdmissionError::InputTooLarge { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_reject_when_queue_full() {
+        let controller = build_controller(1, 10 * INPUT_BYTES_PER_PERMIT, 0);
+        let _p1 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+        let result = controller.admit(INPUT_BYTES_PER_PERMIT).await;
+        assert!(matches!(result, Err(AdmissionError::QueueFull { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_queued_job_admitted_after_release() {
+        let controller = build_controller(1, 10 * INPUT_BYTES_PER_PERMIT, 1);
+        let p1 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+
+        let queued = tokio::spawn({
+            let controller = controller.clone();
+            async move { controller.admit(INPUT_BYTES_PER_PERMIT).await.map(|_| ()) }
+        });
+        while controller.queued_jobs.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        drop(p1);
+        queued.await.unwrap().unwrap();
+        assert_eq!(controller.queued_jobs.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_job_waiting_for_input_bytes_holds_no_job_slot() {
+        let controller = build_controller(2, 10 * INPUT_BYTES_PER_PERMIT, 1);
+        let p1 = controller.admit(8 * INPUT_BYTES_PER_PERMIT).await.unwrap();
+
+        let queued = tokio::spawn({
+            let controller = controller.clone();
+            async move {
+                controller
+                    .admit(4 * INPUT_BYTES_PER_PERMIT)
+                    .await
+                    .map(|_| ())
+            }
+        });
+        while controller.queued_jobs.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        // the queued job waits for input bytes, the second job slot is still free
+        let p2 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+
+        drop(p1);
+        drop(p2);
+        queued.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_limits() {
+        let controller = build_controller(1, 10 * INPUT_BYTES_PER_PERMIT, 0);
+        let p1 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+
+        controller.update_limits(&AdmissionConfig {
+            max_concurrent_jobs: 2,
+            max_input_bytes: 10 * INPUT_BYTES_PER_PERMIT,
+            max_queued_jobs: 0,
+        });
+        let p2 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+
+        controller.update_limits(&AdmissionConfig {
+            max_concurrent_jobs: 1,
+            max_input_bytes: 2 * INPUT_BYTES_PER_PERMIT,
+            max_queued_jobs: 0,
+        });
+        let result = controller.admit(3 * INPUT_BYTES_PER_PERMIT).await;
+        assert!(matches!(result, Err(AdmissionError::InputTooLarge { .. })));
+
+        drop(p1);
+        drop(p2);
+        // the permit released beyond the new limit is taken back
+        assert_eq!(controller.jobs.semaphore.available_permits(), 1);
+        let _p3 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+        let result = controller.admit(INPUT_BYTES_PER_PERMIT).await;
+        assert!(matches!(result, Err(AdmissionError::QueueFull { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_grow_after_shrink_keeps_capacity() {
+        let controller = build_controller(2, 10 * INPUT_BYTES_PER_PERMIT, 0);
+        let p1 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+        let p2 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+
+        // shrink below the held permits, then grow again before they are released
+        let limits = |max_concurrent_jobs| AdmissionConfig {
+            max_concurrent_jobs,
+            max_input_bytes: 10 * INPUT_BYTES_PER_PERMIT,
+            max_queued_jobs: 0,
+        };
+        controller.update_limits(&limits(1));
+        controller.update_limits(&limits(3));
+        let p3 = controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap();
+
+        drop(p1);
+        drop(p2);
+        drop(p3);
+        assert_eq!(controller.jobs.semaphore.available_permits(), 3);
+        let _permits = [
+            controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap(),
+            controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap(),
+            controller.admit(INPUT_BYTES_PER_PERMIT).await.unwrap(),
+        ];
+        let result = controller.admit(INPUT_BYTES_PER_PERMIT).await;
+        assert!(matches!(result, Err(AdmissionError::QueueFull { .. })));
+    }
+}
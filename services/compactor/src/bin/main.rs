@@ -14,26 +14,63 @@
  * limitations under the License.
  */
 
+use bergloom_service_compactor::admission::AdmissionController;
+use bergloom_service_compactor::rpc::CompactorServiceImpl;
 use bergloom_service_compactor::{config::Config, server::grpc_compactor_serve};
-use tracing_subscriber::fmt;
+use clap::Parser;
+use tokio::signal::unix::{SignalKind, signal};
+use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Registry, fmt, reload};
 
-use std::{env, net::SocketAddr, path::PathBuf};
+use std::{env, net::SocketAddr, path::PathBuf, process::ExitCode, sync::Arc};
+
+#[derive(Debug, Parser)]
+#[command(about = "BergLoom compactor server")]
+struct Args {
+    /// Path to the YAML config file, searched in the default locations if not set.
+    /// Every field can be overridden with `BERGLOOM_*` environment variables.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
 
 #[tokio::main]
-async fn main() {
-    let config_path = find_config_file();
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let config_path = args.config.or_else(find_config_file);
 
-    let config = Config::from_file(config_path).unwrap();
-    unsafe {
-        env::set_var("RUST_LOG", &config.logging.level);
-    }
-    tracing_subscriber::registry().with(fmt::layer()).init();
+    let config = match Config::load(config_path.as_deref(), env::vars()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load config: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    // the level has been validated while loading the config
+    let level = config.logging.level_filter().unwrap_or(LevelFilter::INFO);
+    let (level_layer, level_handle) = reload::Layer::new(level);
+    tracing_subscriber::registry()
+        .with(level_layer)
+        .with(fmt::layer())
+        .init();
+
+    let admission_controller = Arc::new(AdmissionController::new(&config.admission));
+    spawn_config_reloader(
+        config_path,
+        config.clone(),
+        level_handle,
+        admission_controller.clone(),
+    );
 
     // read ip and port from env
     let listen_addr = SocketAddr::new(config.server.host, config.server.port);
-    let join_handle = grpc_compactor_serve(listen_addr).await;
+    let compactor_srv = CompactorServiceImpl::new(
+        admission_controller,
+        config.compaction.clone(),
+        config.executor.clone(),
+    );
+    let join_handle = grpc_compactor_serve(listen_addr, compactor_srv).await;
     tracing::info!("Start server successful {:?}", listen_addr);
 
     // join_handle
@@ -45,9 +82,64 @@ async fn main() {
             tracing::error!("Server stopped with error: {}", e);
         }
     }
+    ExitCode::SUCCESS
+}
+
+/// Reloads the config on SIGHUP and applies the fields that are safe to change live,
+/// namely the log level and the admission limits. Invalid configs are logged and ignored.
+fn spawn_config_reloader(
+    config_path: Option<PathBuf>,
+    mut current: Config,
+    level_handle: reload::Handle<LevelFilter, Registry>,
+    admission_controller: Arc<AdmissionController>,
+) {
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGHUP, config reload disabled: {}", e);
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            let config = match Config::load(config_path.as_deref(), env::vars()) {
+                Ok(config) => config,
+                Err(e) => {
+                    tracing::error!("Failed to reload config, keeping the current one: {}", e);
+                    continue;
+                }
+            };
+
+            if config.server != current.server {
+                tracing::warn!(
+                    "Server address changes require a restart, keeping {}:{}",
+                    current.server.host,
+                    current.server.port
+                );
+            }
+            if config.logging.format != current.logging.format {
+                tracing::warn!("Logging format changes require a restart");
+            }
+            if config.executor != current.executor {
+                tracing::warn!("Executor changes require a restart");
+            }
+            match config.logging.level_filter() {
+                Ok(level) => {
+                    if let Err(e) = level_handle.reload(level) {
+                        tracing::error!("Failed to reload log level: {}", e);
+                    }
+                }
+                Err(e) => tracing::error!("Failed to reload log level: {}", e),
+            }
+            admission_controller.update_limits(&config.admission);
+
+            tracing::info!("Config reloaded: {:?}", config);
+            current = config;
+        }
+    });
 }
 
-fn find_config_file() -> PathBuf {
+fn find_config_file() -> Option<PathBuf> {
     let current_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
     let possible_paths = [
         current_dir.join("config.yaml"),
@@ -55,11 +147,5 @@ fn find_config_file() -> PathBuf {
         PathBuf::from("/app/config.yaml"),
     ];
 
-    for path in possible_paths.iter() {
-        if path.exists() {
-            return path.clone();
-        }
-    }
-
-    current_dir.join("config.yaml")
+    possible_paths.into_iter().find(|path| path.exists())
 }
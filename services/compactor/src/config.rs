@@ -14,28 +14,117 @@
  * limitations under the License.
  */
 
+use bergloom_core::executor::ExecutorRegistry;
+use bergloom_core::executor::registry::REMOTE_EXECUTOR;
+use bergloom_core::{CompactionConfig, ExecutorConfig};
 use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
 use std::fs;
 use std::net::IpAddr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+use tracing_subscriber::filter::LevelFilter;
 
-#[derive(Debug, Deserialize)]
+/// Prefix of the environment variables overriding config fields.
+///
+/// Nested fields are separated by a double underscore, e.g. `BERGLOOM_SERVER__PORT=7777` or
+/// `BERGLOOM_ADMISSION__MAX_CONCURRENT_JOBS=8`.
+pub const ENV_PREFIX: &str = "BERGLOOM_";
+const ENV_SEPARATOR: &str = "__";
+
+const LOGGING_FORMATS: [&str; 1] = ["text"];
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("failed to read config file {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("failed to parse config: {0}")]
+    Parse(#[from] serde_yaml::Error),
+
+    #[error("invalid environment override {key}: {reason}")]
+    EnvOverride { key: String, reason: String },
+
+    #[error("invalid value for `{field}`: {reason}")]
+    Invalid { field: &'static str, reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct ServerConfig {
     #[serde(deserialize_with = "deserialize_ip_addr")]
     pub host: IpAddr,
     pub port: u16,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct LoggingConfig {
     pub level: String,
     pub format: String,
 }
 
-#[derive(Debug, Deserialize)]
+/// Limits applied to incoming rewrite requests before they are executed.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AdmissionConfig {
+    /// Maximum number of rewrite jobs running at the same time
+    pub max_concurrent_jobs: usize,
+    /// Maximum total estimated input bytes of all running jobs
+    pub max_input_bytes: u64,
+    /// Maximum number of jobs waiting for admission, further jobs are rejected
+    pub max_queued_jobs: usize,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_jobs: 4,
+            max_input_bytes: 8 * 1024 * 1024 * 1024,
+            max_queued_jobs: 16,
+        }
+    }
+}
+
+/// Compaction settings used when a request does not carry them in `rewrite_file_config`.
+///
+/// A request may select one of `profiles` by name, which is merged on top of `default`.
+/// Settings in the request itself are merged on top of both.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CompactionProfilesConfig {
+    pub default: CompactionConfig,
+    pub profiles: HashMap<String, CompactionConfig>,
+}
+
+impl CompactionProfilesConfig {
+    /// Resolves the base config of a request, `None` if the profile does not exist.
+    pub fn resolve(&self, profile: Option<&str>) -> Option<CompactionConfig> {
+        match profile {
+            None => Some(self.default.clone()),
+            Some(name) => self
+                .profiles
+                .get(name)
+                .map(|profile| self.default.merge(profile)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub admission: AdmissionConfig,
+    #[serde(default)]
+    pub compaction: CompactionProfilesConfig,
+    /// Executor running the rewrites, requests cannot select another one. It cannot be the
+    /// `remote` executor, which would forward the requests of the service to other services
+    #[serde(default)]
+    pub executor: ExecutorConfig,
 }
 
 fn deserialize_ip_addr<'de, D>(deserializer: D) -> Result<IpAddr, D::Error>
@@ -46,10 +135,380 @@ where
     s.parse().map_err(serde::de::Error::custom)
 }
 
+impl LoggingConfig {
+    pub fn level_filter(&self) -> Result<LevelFilter, ConfigError> {
+        LevelFilter::from_str(&self.level).map_err(|e| ConfigError::Invalid {
+            field: "logging.level",
+            reason: format!("{} ({})", e, self.level),
+        })
+    }
+}
+
 impl Config {
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
-        let contents = fs::read_to_string(path)?;
-        let config: Config = serde_yaml::from_str(&contents)?;
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::load(Some(path.as_ref()), std::env::vars())
+    }
+
+    /// Loads the config in layers: the YAML file if any, then `BERGLOOM_*` environment
+    /// overrides on top, and validates the result.
+    pub fn load(
+        path: Option<&Path>,
+        env_vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let mut value = match path {
+            Some(path) => {
+                let contents = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+                    path: path.to_owned(),
+                    source,
+                })?;
+                serde_yaml::from_str(&contents)?
+            }
+            None => Value::Mapping(Mapping::new()),
+        };
+        let overrides = apply_env_overrides(&mut value, env_vars)?;
+        let config = Self::deserialize_with_overrides(value, &overrides)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Deserializes the config, failing on environment overrides that match no config field.
+    ///
+    /// Unknown fields of the YAML file are ignored.
+    fn deserialize_with_overrides(
+        value: Value,
+        overrides: &[(String, String)],
+    ) -> Result<Self, ConfigError> {
+        let mut unknown_fields = vec![];
+        let config: Config =
+            serde_ignored::deserialize(value, |path| unknown_fields.push(path.to_string()))?;
+        for (key, field) in overrides {
+            if let Some(unknown_field) = unknown_fields.iter().find(|unknown_field| {
+                field == *unknown_field || field.starts_with(&format!("{}.", unknown_field))
+            }) {
+                return Err(ConfigError::EnvOverride {
+                    key: key.clone(),
+                    reason: format!("`{}` is not a config field", unknown_field),
+                });
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.server.port == 0 {
+            return Err(ConfigError::Invalid {
+                field: "server.port",
+                reason: "must not be 0".to_owned(),
+            });
+        }
+        self.logging.level_filter()?;
+        if !LOGGING_FORMATS.contains(&self.logging.format.as_str()) {
+            return Err(ConfigError::Invalid {
+                field: "logging.format",
+                reason: format!(
+                    "unsupported format {}, expected one of {:?}",
+                    self.logging.format, LOGGING_FORMATS
+                ),
+            });
+        }
+        if self.admission.max_concurrent_jobs == 0 {
+            return Err(ConfigError::Invalid {
+                field: "admission.max_concurrent_jobs",
+                reason: "must be greater than 0".to_owned(),
+            });
+        }
+        if self.admission.max_input_bytes == 0 {
+            return Err(ConfigError::Invalid {
+                field: "admission.max_input_bytes",
+                reason: "must be greater than 0".to_owned(),
+            });
+        }
+        // the executor serves the rewrites sent by remote executors, forwarding them again could
+        // send a request around the compactors forever
+        if self.executor.name.as_deref() == Some(REMOTE_EXECUTOR) {
+            return Err(ConfigError::Invalid {
+                field: "executor.name",
+                reason: format!(
+                    "the compactor service cannot use the {} executor",
+                    REMOTE_EXECUTOR
+                ),
+            });
+        }
+        if let Err(e) = ExecutorRegistry::default().build(&self.executor) {
+            return Err(ConfigError::Invalid {
+                field: "executor",
+                reason: e.to_string(),
+            });
+        }
+        let compaction_configs =
+            std::iter::once(&self.compaction.default).chain(self.compaction.profiles.values());
+        for compaction_config in compaction_configs {
+            if compaction_config.batch_parallelism == Some(0) {
+                return Err(ConfigError::Invalid {
+                    field: "compaction.batch_parallelism",
+                    reason: "must be greater than 0".to_owned(),
+                });
+            }
+            if compaction_config.target_partitions == Some(0) {
+                return Err(ConfigError::Invalid {
+                    field: "compaction.target_partitions",
+                    reason: "must be greater than 0".to_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Applies `BERGLOOM_*` environment variables on top of the parsed YAML document.
+///
+/// Returns the environment variables applied, with the path of the field each one sets.
+fn apply_env_overrides(
+    value: &mut Value,
+    env_vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<Vec<(String, String)>, ConfigError> {
+    let mut overrides = vec![];
+    for (key, raw) in env_vars {
+        let Some(path) = key.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let segments = path
+            .split(ENV_SEPARATOR)
+            .map(|segment| segment.to_lowercase())
+            .collect::<Vec<_>>();
+        if segments.iter().any(|segment| segment.is_empty()) {
+            return Err(ConfigError::EnvOverride {
+                key,
+                reason: "empty field name".to_owned(),
+            });
+        }
+        // parse the value as a YAML scalar or sequence, e.g. `[a, b]`, so that numbers, booleans
+        // and lists keep their type. List fields also accept comma-separated strings
+        let parsed = match serde_yaml::from_str::<Value>(&raw) {
+            Ok(
+                parsed
+                @ (Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Sequence(_)),
+            ) => parsed,
+            _ => Value::String(raw),
+        };
+
+        let mut current = &mut *value;
+        for (depth, segment) in segments.iter().enumerate() {
+            if current.is_null() {
+                *current = Value::Mapping(Mapping::new());
+            }
+            let Value::Mapping(mapping) = current else {
+                return Err(ConfigError::EnvOverride {
+                    key,
+                    reason: format!("`{}` is not a section", segments[..depth].join(".")),
+                });
+            };
+            current = mapping
+                .entry(Value::String(segment.clone()))
+                .or_insert(Value::Null);
+        }
+        *current = parsed;
+        overrides.push((key, segments.join(".")));
+    }
+    Ok(overrides)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_with_env(yaml: &str, env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+        let overrides = apply_env_overrides(
+            &mut value,
+            env.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+        )?;
+        let config = Config::deserialize_with_overrides(value, &overrides)?;
+        config.validate()?;
         Ok(config)
     }
+
+    const BASE_CONFIG: &str = r#"
+server:
+  host: "127.0.0.1"
+  port: 7777
+logging:
+  level: "info"
+  format: "text"
+"#;
+
+    #[test]
+    fn test_env_overrides_nested_fields() {
+        let config = parse_with_env(
+            BASE_CONFIG,
+            &[
+                ("BERGLOOM_SERVER__PORT", "8888"),
+                ("BERGLOOM_LOGGING__LEVEL", "debug"),
+                ("BERGLOOM_ADMISSION__MAX_CONCURRENT_JOBS", "2"),
+                ("UNRELATED", "ignored"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.server.port, 8888);
+        assert_eq!(config.logging.level, "debug");
+        assert_eq!(config.admission.max_concurrent_jobs, 2);
+        assert_eq!(config.admission.max_queued_jobs, 16);
+    }
+
+    #[test]
+    fn test_env_only_config() {
+        let config = parse_with_env(
+            "",
+            &[
+                ("BERGLOOM_SERVER__HOST", "0.0.0.0"),
+                ("BERGLOOM_SERVER__PORT", "7777"),
+                ("BERGLOOM_LOGGING__LEVEL", "warn"),
+                ("BERGLOOM_LOGGING__FORMAT", "text"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.server.host.to_string(), "0.0.0.0");
+    }
+
+    #[test]
+    fn test_compaction_profiles() {
+        let yaml = format!(
+            "{}{}",
+            BASE_CONFIG,
+            r#"
+compaction:
+  default:
+    batch_parallelism: 4
+    target_partitions: 4
+  profiles:
+    large:
+      target_partitions: 16
+"#
+        );
+        let config = parse_with_env(
+            &yaml,
+            &[(
+                "BERGLOOM_COMPACTION__PROFILES__LARGE__FILE_PREFETCH_DEPTH",
+                "4",
+            )],
+        )
+        .unwrap();
+        let default = config.compaction.resolve(None).unwrap();
+        assert_eq!(default.target_partitions, Some(4));
+        let large = config.compaction.resolve(Some("large")).unwrap();
+        assert_eq!(large.batch_parallelism, Some(4));
+        assert_eq!(large.target_partitions, Some(16));
+        assert_eq!(large.file_prefetch_depth, Some(4));
+        assert!(config.compaction.resolve(Some("small")).is_none());
+    }
+
+    #[test]
+    fn test_executor_config() {
+        let config = parse_with_env(BASE_CONFIG, &[]).unwrap();
+        assert_eq!(config.executor, ExecutorConfig::default());
+
+        let yaml = format!(
+            "{}{}",
+            BASE_CONFIG,
+            r#"
+executor:
+  name: "datafusion"
+  memory_limit: 1073741824
+  spill_dir: "/tmp/bergloom"
+"#
+        );
+        let config = parse_with_env(&yaml, &[]).unwrap();
+        assert_eq!(config.executor.name.as_deref(), Some("datafusion"));
+        assert_eq!(config.executor.memory_limit, Some(1073741824));
+        assert_eq!(config.executor.spill_dir.as_deref(), Some("/tmp/bergloom"));
+
+        // the service would forward the requests sent by remote executors
+        let yaml = format!(
+            "{}{}",
+            BASE_CONFIG,
+            r#"
+executor:
+  name: "remote"
+  remote_endpoints:
+    - "http://compactor-0:7777"
+    - "http://compactor-1:7777"
+"#
+        );
+        let err = parse_with_env(&yaml, &[]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                field: "executor.name",
+                ..
+            }
+        ));
+        let err = parse_with_env(BASE_CONFIG, &[("BERGLOOM_EXECUTOR__NAME", "mock")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                field: "executor",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let err = parse_with_env(BASE_CONFIG, &[("BERGLOOM_LOGGING__LEVEL", "loud")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                field: "logging.level",
+                ..
+            }
+        ));
+
+        let err =
+            parse_with_env(BASE_CONFIG, &[("BERGLOOM_SERVER__PORT__INNER", "1")]).unwrap_err();
+        assert!(matches!(err, ConfigError::EnvOverride { .. }));
+
+        let err =
+            parse_with_env(BASE_CONFIG, &[("BERGLOOM_SERVER__PORT", "not_a_port")]).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse(_)));
+    }
+
+    #[test]
+    fn test_env_override_lists() {
+        let endpoints = vec![
+            "http://compactor-0:7777".to_owned(),
+            "http://compactor-1:7777".to_owned(),
+        ];
+        for raw in [
+            r#"["http://compactor-0:7777", "http://compactor-1:7777"]"#,
+            "http://compactor-0:7777,http://compactor-1:7777",
+        ] {
+            let config =
+                parse_with_env(BASE_CONFIG, &[("BERGLOOM_EXECUTOR__REMOTE_ENDPOINTS", raw)])
+                    .unwrap();
+            assert_eq!(config.executor.remote_endpoints, endpoints);
+        }
+    }
+
+    #[test]
+    fn test_unknown_env_overrides_are_rejected() {
+        for key in [
+            "BERGLOOM_SERVER__PROT",
+            "BERGLOOM_ADMISSION__MAX_JOBS",
+            "BERGLOOM_EXECUTOR__REMOTE_ENDPOINT",
+            "BERGLOOM_COMPACTION__PROFILES__LARGE__BATCH_PARALELLISM",
+            "BERGLOOM_UNKNOWN__FIELD",
+        ] {
+            let err = parse_with_env(BASE_CONFIG, &[(key, "1")]).unwrap_err();
+            assert!(
+                matches!(&err, ConfigError::EnvOverride { key: err_key, .. } if err_key == key),
+                "{}: {}",
+                key,
+                err
+            );
+        }
+
+        // unknown fields of the YAML file are still ignored
+        let yaml = format!("{}{}", BASE_CONFIG, "unknown: 1\n");
+        assert!(parse_with_env(&yaml, &[]).is_ok());
+    }
 }
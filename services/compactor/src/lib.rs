@@ -14,6 +14,8 @@
  * limitations under the License.
  */
 
+pub mod admission;
 pub mod config;
 pub mod rpc;
 pub mod server;
+pub mod status;
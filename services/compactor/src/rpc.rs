@@ -14,17 +14,49 @@
  * limitations under the License.
  */
 
+use std::sync::Arc;
+
 use bergloom_codegen::compactor::compactor_service_server::CompactorService;
 use bergloom_codegen::compactor::{EchoRequest, EchoResponse};
-use bergloom_core::CompactionExecutor;
-use bergloom_core::executor::DataFusionExecutor;
+use bergloom_core::ExecutorConfig;
+use bergloom_core::executor::ExecutorRegistry;
+use bergloom_core::parser::proto::{
+    PbRewriteFilesRequestDecoder, RewriteFilesResponseProtoEncoder,
+};
 
 use bergloom_codegen::compactor::{
     RewriteFilesRequest as PbRewriteFilesRequest, RewriteFilesResponse as PbRewriteFilesResponse,
 };
 
-#[derive(Default)]
-pub struct CompactorServiceImpl;
+use crate::admission::AdmissionController;
+use crate::config::CompactionProfilesConfig;
+use crate::status::compaction_error_to_status;
+
+/// Key in `rewrite_file_config` selecting a compaction profile of the server config.
+pub const COMPACTION_PROFILE_KEY: &str = "profile";
+
+pub struct CompactorServiceImpl {
+    admission_controller: Arc<AdmissionController>,
+    compaction_profiles: CompactionProfilesConfig,
+    executor_registry: ExecutorRegistry,
+    /// Selects the executor of every request, `rewrite_file_config` cannot override it
+    executor_config: ExecutorConfig,
+}
+
+impl CompactorServiceImpl {
+    pub fn new(
+        admission_controller: Arc<AdmissionController>,
+        compaction_profiles: CompactionProfilesConfig,
+        executor_config: ExecutorConfig,
+    ) -> Self {
+        Self {
+            admission_controller,
+            compaction_profiles,
+            executor_registry: ExecutorRegistry::default(),
+            executor_config,
+        }
+    }
+}
 
 #[async_trait::async_trait]
 impl CompactorService for CompactorServiceImpl {
@@ -32,14 +64,46 @@ impl CompactorService for CompactorServiceImpl {
         &self,
         request: tonic::Request<PbRewriteFilesRequest>,
     ) -> std::result::Result<tonic::Response<PbRewriteFilesResponse>, tonic::Status> {
-        let request = request.into_inner();
-        let response = DataFusionExecutor::default()
-            .rewrite_file_proto(request)
+        let mut request = request.into_inner();
+        let profile = request.rewrite_file_config.remove(COMPACTION_PROFILE_KEY);
+        let default_config = self
+            .compaction_profiles
+            .resolve(profile.as_deref())
+            .ok_or_else(|| {
+                tonic::Status::invalid_argument(format!(
+                    "Unknown compaction profile: {}",
+                    profile.unwrap_or_default()
+                ))
+            })?;
+        // estimate the input size from the file scan tasks, including delete files
+        let input_bytes = request
+            .file_scan_task_descriptor
+            .iter()
+            .fold(0u64, |total, task| total.saturating_add(task.length));
+        let _permit = self
+            .admission_controller
+            .admit(input_bytes)
             .await
             .map_err(|e| {
-                tracing::error!("Error processing request: {:?}", e);
-                tonic::Status::internal(format!("Internal error: {}", e))
+                tracing::warn!("Rejecting request: {}", e);
+                tonic::Status::resource_exhausted(e.to_string())
             })?;
+        let response = async {
+            let request = PbRewriteFilesRequestDecoder::new(request)
+                .with_default_config(default_config)
+                .decode()?;
+            let response = self
+                .executor_registry
+                .build(&self.executor_config)?
+                .rewrite_files(request)
+                .await?;
+            RewriteFilesResponseProtoEncoder::new(response).encode()
+        }
+        .await
+        .map_err(|e| {
+            tracing::error!("Error processing request: {:?}", e);
+            compaction_error_to_status(&e)
+        })?;
         Ok(tonic::Response::new(response))
     }
 
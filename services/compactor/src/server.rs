@@ -22,9 +22,8 @@ use tonic::transport::Server;
 
 pub async fn grpc_compactor_serve(
     listen_addr: SocketAddr,
+    compactor_srv: CompactorServiceImpl,
 ) -> JoinHandle<Result<(), tonic::transport::Error>> {
-    let compactor_srv = CompactorServiceImpl {};
-
     let server = Server::builder()
         .add_service(CompactorServiceServer::new(compactor_srv))
         .serve(listen_addr);
@@ -0,0 +1,172 @@
+/*
+ * Copyright 2025 BergLoom
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use bergloom_core::CompactionError;
+use bytes::Bytes;
+use datafusion::error::DataFusionError;
+use prost::Message;
+use tonic::{Code, Status};
+
+const RETRY_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+/// `google.rpc.Status`, carried in the `grpc-status-details-bin` trailer.
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<prost_types::Any>,
+}
+
+/// `google.rpc.RetryInfo`
+#[derive(Clone, PartialEq, Message)]
+struct RetryInfo {
+    #[prost(message, optional, tag = "1")]
+    retry_delay: Option<prost_types::Duration>,
+}
+
+/// Converts a compaction error to a gRPC status. Retryable errors carry a `google.rpc.RetryInfo`
+/// detail with the suggested delay before retrying.
+pub fn compaction_error_to_status(err: &CompactionError) -> Status {
+    let code = status_code(err);
+    let message = err.to_string();
+    if !err.is_retryable() {
+        return Status::new(code, message);
+    }
+
+    let delay = retry_delay(code);
+    let retry_info = RetryInfo {
+        retry_delay: Some(prost_types::Duration {
+            seconds: delay.as_secs() as i64,
+            nanos: delay.subsec_nanos() as i32,
+        }),
+    };
+    let details = RpcStatus {
+        code: code as i32,
+        message: message.clone(),
+        details: vec![prost_types::Any {
+            type_url: RETRY_INFO_TYPE_URL.to_owned(),
+            value: retry_info.encode_to_vec(),
+        }],
+    };
+    Status::with_details(code, message, Bytes::from(details.encode_to_vec()))
+}
+
+fn status_code(err: &CompactionError) -> Code {
+    match err {
+        CompactionError::Config(_) | CompactionError::InvalidInput(_) => Code::InvalidArgument,
+        CompactionError::NotFound(_) => Code::NotFound,
+        CompactionError::PermissionDenied(_) => Code::PermissionDenied,
+        CompactionError::StorageThrottled(_) => Code::Unavailable,
+        CompactionError::CommitConflict(_) => Code::Aborted,
+        CompactionError::Cancelled(_) => Code::Cancelled,
+        CompactionError::Io(e) => match e.kind() {
+            std::io::ErrorKind::NotFound => Code::NotFound,
+            std::io::ErrorKind::PermissionDenied => Code::PermissionDenied,
+            _ if err.is_retryable() => Code::Unavailable,
+            _ => Code::Internal,
+        },
+        CompactionError::Iceberg(e) => match e.kind() {
+            iceberg::ErrorKind::FeatureUnsupported => Code::Unimplemented,
+            _ => Code::Internal,
+        },
+        CompactionError::DataFusion(DataFusionError::ResourcesExhausted(_)) => {
+            Code::ResourceExhausted
+        }
+        CompactionError::DataFusion(DataFusionError::NotImplemented(_)) => Code::Unimplemented,
+        CompactionError::Execution(_) | CompactionError::DataFusion(_) => Code::Internal,
+    }
+}
+
+fn retry_delay(code: Code) -> Duration {
+    match code {
+        // another writer committed first, the caller can re-plan right away
+        Code::Aborted => Duration::from_millis(100),
+        // give running jobs time to release memory
+        Code::ResourceExhausted => Duration::from_secs(5),
+        _ => Duration::from_secs(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_retry_delay(status: &Status) -> Option<Duration> {
+        let details = RpcStatus::decode(status.details()).ok()?;
+        let any = details
+            .details
+            .iter()
+            .find(|any| any.type_url == RETRY_INFO_TYPE_URL)?;
+        let delay = RetryInfo::decode(any.value.as_slice()).ok()?.retry_delay?;
+        Some(Duration::new(delay.seconds as u64, delay.nanos as u32))
+    }
+
+    #[test]
+    fn test_non_retryable_error_status() {
+        let status =
+            compaction_error_to_status(&CompactionError::InvalidInput("bad schema".to_owned()));
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert!(status.details().is_empty());
+
+        let status = compaction_error_to_status(&CompactionError::NotFound("a.parquet".to_owned()));
+        assert_eq!(status.code(), Code::NotFound);
+        assert!(decode_retry_delay(&status).is_none());
+
+        let status = compaction_error_to_status(&CompactionError::Execution("boom".to_owned()));
+        assert_eq!(status.code(), Code::Internal);
+        assert!(decode_retry_delay(&status).is_none());
+    }
+
+    #[test]
+    fn test_retryable_error_status() {
+        let status =
+            compaction_error_to_status(&CompactionError::StorageThrottled("slow down".to_owned()));
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(decode_retry_delay(&status), Some(Duration::from_secs(1)));
+
+        let status =
+            compaction_error_to_status(&CompactionError::CommitConflict("conflict".to_owned()));
+        assert_eq!(status.code(), Code::Aborted);
+        assert_eq!(
+            decode_retry_delay(&status),
+            Some(Duration::from_millis(100))
+        );
+
+        let err = CompactionError::from(iceberg::Error::new(
+            iceberg::ErrorKind::CatalogCommitConflicts,
+            "Requirement failed: current snapshot id does not match",
+        ));
+        assert!(err.is_retryable());
+        assert_eq!(compaction_error_to_status(&err).code(), Code::Aborted);
+
+        let status = compaction_error_to_status(&CompactionError::DataFusion(
+            DataFusionError::ResourcesExhausted("out of memory".to_owned()),
+        ));
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(decode_retry_delay(&status), Some(Duration::from_secs(5)));
+
+        let status = compaction_error_to_status(&CompactionError::Io(std::io::Error::from(
+            std::io::ErrorKind::TimedOut,
+        )));
+        assert_eq!(status.code(), Code::Unavailable);
+        assert!(decode_retry_delay(&status).is_some());
+    }
+}